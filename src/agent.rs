@@ -0,0 +1,542 @@
+use crate::board::CellValue::CPU;
+use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome};
+use crate::engine::Engine;
+use crate::evaluator::Evaluator;
+use crate::menace::Menace;
+use crate::network::Network;
+use crate::qlearning::QTable;
+use crate::search::WinLines;
+use crate::utils::Random;
+
+use std::fmt;
+use std::fs;
+use std::rc::Rc;
+
+// Playouts an MCTS agent makes for every move unless told otherwise
+const MCTS_ITERATIONS: u32 = 1000;
+// How much MCTS favours moves it has tried less, the usual square root of two
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// Something that can play a two player game, so different ways of choosing
+/// moves can be played against each other
+pub trait Agent {
+    /// Name used for the agent in results
+    fn name(&self) -> String;
+
+    /// Move for `value` to play on `board`, or `None` when there is none
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell>;
+
+    /// Called with the outcome once a game the agent played in is over, for
+    /// agents that learn from their games
+    fn finish(&mut self, _outcome: Outcome) {}
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AgentError {
+    UnknownAgent(String),
+    BadLevel(String),
+    /// The agent can not play on the board it was asked for
+    Unsupported(String),
+    /// What a learning agent learned could not be read
+    CannotLoad(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AgentError::UnknownAgent(a) => write!(f, "unknown agent {}", a),
+            AgentError::BadLevel(l) => write!(f, "bad agent level {}", l),
+            AgentError::Unsupported(a) => write!(f, "{} can not play this board", a),
+            AgentError::CannotLoad(e) => write!(f, "could not load agent: {}", e),
+        }
+    }
+}
+
+/// The agent described by `spec`, which is one of:
+///
+/// - `random`
+/// - `mcts[:<playouts>]`
+/// - `minimax[:<depth>]`, searched to the end or to a depth
+/// - `evaluator`, the evaluator behind the classic game
+/// - `menace[:<path>]`, the matchbox learner, new or as saved to a file
+/// - `qlearning:<path>`, a Q-table saved to a file
+/// - `network:<path>`, `minimax` with network weights saved to a file
+///
+/// Agents that make random choices start from `seed`.
+pub fn from_spec(spec: &str, config: BoardConfig, seed: u64) -> Result<Box<dyn Agent>, AgentError> {
+    let (kind, level) = match spec.split_once(':') {
+        Some((kind, level)) => (kind, Some(level)),
+        None => (spec, None),
+    };
+    let bad_level = || AgentError::BadLevel(spec.to_string());
+
+    match (kind, level) {
+        ("random", None) => Ok(Box::new(RandomAgent::new(seed))),
+        ("mcts", level) => {
+            let iterations = match level {
+                Some(level) => level.parse().map_err(|_e| bad_level())?,
+                None => MCTS_ITERATIONS,
+            };
+            Ok(Box::new(MctsAgent::new(iterations, seed)))
+        }
+        ("minimax", None) => Ok(Box::new(MinimaxAgent::new(None))),
+        ("minimax", Some(level)) => {
+            let depth = level.parse().map_err(|_e| bad_level())?;
+            Ok(Box::new(MinimaxAgent::new(Some(depth))))
+        }
+        ("evaluator", None) if config == BoardConfig::default() => Ok(Box::new(EvaluatorAgent)),
+        ("evaluator", None) => Err(AgentError::Unsupported(spec.to_string())),
+        ("menace", None) => Menace::new(config, seed)
+            .map(|menace| Box::new(menace) as Box<dyn Agent>)
+            .map_err(|_e| AgentError::Unsupported(spec.to_string())),
+        ("menace", Some(path)) => {
+            let menace = fs::read_to_string(path)
+                .map_err(|e| AgentError::CannotLoad(format!("{}: {}", path, e)))
+                .and_then(|json| {
+                    Menace::from_json(&json).map_err(|e| AgentError::CannotLoad(e.to_string()))
+                })?;
+            if menace.config() != config {
+                return Err(AgentError::Unsupported(spec.to_string()));
+            }
+            Ok(Box::new(menace))
+        }
+        ("qlearning", Some(path)) => {
+            let table = fs::read_to_string(path)
+                .map_err(|e| AgentError::CannotLoad(format!("{}: {}", path, e)))
+                .and_then(|json| {
+                    QTable::from_json(&json).map_err(|e| AgentError::CannotLoad(e.to_string()))
+                })?;
+            if table.config() != config {
+                return Err(AgentError::Unsupported(spec.to_string()));
+            }
+            Ok(Box::new(table))
+        }
+        ("network", Some(path)) => {
+            let network = fs::read(path)
+                .map_err(|e| AgentError::CannotLoad(format!("{}: {}", path, e)))
+                .and_then(|bytes| {
+                    Network::from_bytes(&bytes).map_err(|e| AgentError::CannotLoad(e.to_string()))
+                })?;
+            if network.config() != config {
+                return Err(AgentError::Unsupported(spec.to_string()));
+            }
+            Ok(Box::new(MinimaxAgent::new(None).with_network(network)))
+        }
+        ("random", Some(_)) | ("evaluator", Some(_)) | ("qlearning", None) | ("network", None) => {
+            Err(bad_level())
+        }
+        _ => Err(AgentError::UnknownAgent(spec.to_string())),
+    }
+}
+
+/// Plays any legal move
+pub struct RandomAgent {
+    random: Random,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> RandomAgent {
+        RandomAgent {
+            random: Random::new(seed),
+        }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        let moves = board.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        let cell = moves[self.random.below(moves.len())].clone();
+        Some(Cell { value, ..cell })
+    }
+}
+
+/// Searches with `Engine`, to the end of the game or only a few plies ahead
+/// for a weaker player
+pub struct MinimaxAgent {
+    engine: Engine,
+}
+
+impl MinimaxAgent {
+    pub fn new(depth: Option<u8>) -> MinimaxAgent {
+        let engine = match depth {
+            // Without a clock the same position always gets the same move
+            Some(depth) => Engine {
+                max_depth: depth,
+                time_budget_ms: f64::INFINITY,
+                ..Engine::default()
+            },
+            None => Engine::default(),
+        };
+        MinimaxAgent { engine }
+    }
+
+    /// Searches with the network as its heuristic, see `Engine::network`
+    pub fn with_network(mut self, network: Network) -> MinimaxAgent {
+        self.engine.network = Some(network);
+        self
+    }
+}
+
+impl Agent for MinimaxAgent {
+    fn name(&self) -> String {
+        if self.engine.network.is_some() {
+            "network".to_string()
+        } else if self.engine.max_depth == Engine::default().max_depth {
+            "minimax".to_string()
+        } else {
+            format!("minimax:{}", self.engine.max_depth)
+        }
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        self.engine.analyse(board, value, |_info| {})
+    }
+}
+
+/// The evaluator the classic game plays with. Its search takes the human to
+/// have moved first, so when the agent moves first it searches with
+/// `Engine` to the end of the game instead.
+pub struct EvaluatorAgent;
+
+impl Agent for EvaluatorAgent {
+    fn name(&self) -> String {
+        "evaluator".to_string()
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        if value != CPU {
+            let mut engine = Engine {
+                time_budget_ms: f64::INFINITY,
+                ..Engine::default()
+            };
+            return engine.analyse(board, value, |_info| {});
+        }
+        if board.legal_moves().is_empty() {
+            return None;
+        }
+        Some(Evaluator {}.find_best_move(board.get_cells()))
+    }
+}
+
+/// Monte Carlo tree search: plays many random games from the position,
+/// spending more of them on the moves that have done well so far, and picks
+/// the move it tried most
+pub struct MctsAgent {
+    iterations: u32,
+    random: Random,
+}
+
+struct Node {
+    // The move into this node and who made it
+    index: Option<usize>,
+    player: CellValue,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<usize>,
+    visits: f64,
+    // Points for `player` from the games through this node
+    score: f64,
+    is_finished: bool,
+}
+
+impl MctsAgent {
+    pub fn new(iterations: u32, seed: u64) -> MctsAgent {
+        MctsAgent {
+            iterations: iterations.max(1),
+            random: Random::new(seed),
+        }
+    }
+
+    fn select(&self, tree: &[Node], node: usize) -> usize {
+        let parent_visits = tree[node].visits.ln();
+        let uct = |child: usize| {
+            let child = &tree[child];
+            child.score / child.visits + EXPLORATION * (parent_visits / child.visits).sqrt()
+        };
+        *tree[node]
+            .children
+            .iter()
+            .max_by(|a, b| uct(**a).partial_cmp(&uct(**b)).unwrap())
+            .unwrap()
+    }
+
+    /// Plays random moves until the game ends and returns the winner
+    fn playout(&mut self, search: &mut Search, mut player: CellValue) -> Option<CellValue> {
+        loop {
+            let moves = search.playable();
+            if moves.is_empty() {
+                return None;
+            }
+            let index = moves[self.random.below(moves.len())];
+            if search.play(index, player) {
+                return Some(player);
+            }
+            player = player.opponent();
+        }
+    }
+}
+
+impl Agent for MctsAgent {
+    fn name(&self) -> String {
+        format!("mcts:{}", self.iterations)
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        let start = Search::new(board);
+        let mut tree = vec![Node {
+            index: None,
+            player: value.opponent(),
+            parent: None,
+            children: vec![],
+            untried: start.playable(),
+            visits: 0.0,
+            score: 0.0,
+            is_finished: false,
+        }];
+        if tree[0].untried.is_empty() {
+            return None;
+        }
+
+        for _i in 0..self.iterations {
+            let mut search = start.clone();
+            let mut node = 0;
+
+            while tree[node].untried.is_empty() && !tree[node].is_finished {
+                node = self.select(&tree, node);
+                search.play(tree[node].index.unwrap(), tree[node].player);
+            }
+
+            let mut winner = None;
+            if tree[node].is_finished {
+                winner = Some(tree[node].player).filter(|_p| search.is_won);
+            } else {
+                let untried = &mut tree[node].untried;
+                let index = untried.swap_remove(self.random.below(untried.len()));
+                let player = tree[node].player.opponent();
+                let is_won = search.play(index, player);
+                let untried = if is_won { vec![] } else { search.playable() };
+                let child = tree.len();
+                tree.push(Node {
+                    index: Some(index),
+                    player,
+                    parent: Some(node),
+                    children: vec![],
+                    is_finished: untried.is_empty(),
+                    untried,
+                    visits: 0.0,
+                    score: 0.0,
+                });
+                tree[node].children.push(child);
+                node = child;
+
+                if is_won {
+                    winner = Some(player);
+                } else if !tree[node].is_finished {
+                    winner = self.playout(&mut search, player.opponent());
+                }
+            }
+
+            let mut next = Some(node);
+            while let Some(node) = next {
+                let node = &mut tree[node];
+                node.visits += 1.0;
+                node.score += match winner {
+                    Some(winner) if winner == node.player => 1.0,
+                    Some(_winner) => 0.0,
+                    None => 0.5,
+                };
+                next = node.parent;
+            }
+        }
+
+        let best = tree[0]
+            .children
+            .iter()
+            .max_by(|a, b| tree[**a].visits.partial_cmp(&tree[**b].visits).unwrap())?;
+        let (row, column, layer) = start.config.coordinates(tree[*best].index.unwrap());
+        Some(Cell {
+            row,
+            column,
+            layer,
+            value,
+        })
+    }
+}
+
+/// A board cut down to what a playout needs
+#[derive(Clone)]
+struct Search {
+    config: BoardConfig,
+    values: Vec<CellValue>,
+    lines: Rc<WinLines>,
+    is_won: bool,
+}
+
+impl Search {
+    fn new(board: &Board) -> Search {
+        Search {
+            config: board.config(),
+            values: board.values(),
+            lines: Rc::new(WinLines::new(board)),
+            is_won: false,
+        }
+    }
+
+    fn playable(&self) -> Vec<usize> {
+        (0..self.values.len())
+            .filter(|&i| self.config.is_playable(&self.values, i))
+            .collect()
+    }
+
+    /// Marks a position and returns whether it won the game
+    fn play(&mut self, index: usize, player: CellValue) -> bool {
+        self.values[index] = player;
+        self.is_won = self.lines.is_win_through(&self.values, index);
+        self.is_won
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::agent::{from_spec, Agent, AgentError, EvaluatorAgent, MctsAgent, RandomAgent};
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, BoardConfig, Cell};
+    use crate::menace::Menace;
+    use crate::network::Network;
+
+    fn board_with(moves: &[(u8, u8, bool)]) -> Board {
+        let mut board = Board::default();
+        for &(row, column, is_human) in moves {
+            let value = if is_human { Human } else { CPU };
+            board
+                .set(Cell {
+                    row,
+                    column,
+                    layer: 0,
+                    value,
+                })
+                .unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn test_random_agent__plays_legal_moves() {
+        let mut agent = RandomAgent::new(7);
+        let board = board_with(&[(1, 1, true), (0, 0, false)]);
+
+        let actual = (0..20).all(|_i| {
+            let cell = agent.choose(&board, Human).unwrap();
+            board.clone().set(cell).is_some()
+        });
+
+        assert!(actual);
+    }
+
+    #[test]
+    fn test_mcts_agent__takes_win() {
+        let mut agent = MctsAgent::new(500, 1);
+        let board = board_with(&[(0, 0, false), (2, 0, true), (0, 1, false), (2, 2, true)]);
+
+        let actual = agent
+            .choose(&board, CPU)
+            .map(|c| (c.row, c.column, c.value));
+        let expected = Some((0, 2, CPU));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_mcts_agent__blocks_loss() {
+        let mut agent = MctsAgent::new(2000, 1);
+        let board = board_with(&[(0, 0, true), (1, 1, false), (0, 1, true)]);
+
+        let actual = agent.choose(&board, CPU).map(|c| (c.row, c.column));
+        let expected = Some((0, 2));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_evaluator_agent__when__moving_first() {
+        let board = board_with(&[(0, 0, true), (1, 1, false), (0, 1, true), (2, 2, false)]);
+
+        let actual = EvaluatorAgent.choose(&board, Human);
+        let expected = Some(Cell {
+            row: 0,
+            column: 2,
+            layer: 0,
+            value: Human,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_spec() {
+        let config = BoardConfig::default();
+
+        let actual: Vec<String> = ["random", "mcts:50", "minimax", "minimax:2", "evaluator"]
+            .iter()
+            .map(|s| from_spec(s, config, 1).unwrap().name())
+            .collect();
+        let expected = vec!["random", "mcts:50", "minimax", "minimax:2", "evaluator"];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_spec__when__bad_spec() {
+        let actual = [
+            from_spec("alphazero", BoardConfig::default(), 1).err(),
+            from_spec("mcts:lots", BoardConfig::default(), 1).err(),
+            from_spec("evaluator", BoardConfig::qubic(), 1).err(),
+        ];
+        let expected = [
+            Some(AgentError::UnknownAgent("alphazero".to_string())),
+            Some(AgentError::BadLevel("mcts:lots".to_string())),
+            Some(AgentError::Unsupported("evaluator".to_string())),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_spec__when__menace_saved_to_file() {
+        let path = std::env::temp_dir().join("test_from_spec__menace.json");
+        let menace = Menace::new(BoardConfig::default(), 1).unwrap();
+        std::fs::write(&path, menace.to_json()).unwrap();
+        let spec = format!("menace:{}", path.display());
+
+        let actual = from_spec(&spec, BoardConfig::default(), 1).map(|a| a.name());
+        let expected = Ok("menace".to_string());
+
+        assert_eq!(actual, expected);
+        assert!(from_spec(&spec, BoardConfig::square(4, 4), 1).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_spec__when__network_saved_to_file() {
+        let path = std::env::temp_dir().join("test_from_spec__network.bin");
+        let network = Network::new(BoardConfig::connect_four(), 4, 1).unwrap();
+        std::fs::write(&path, network.to_bytes()).unwrap();
+        let spec = format!("network:{}", path.display());
+
+        let actual = from_spec(&spec, BoardConfig::connect_four(), 1).map(|a| a.name());
+        let expected = Ok("network".to_string());
+
+        assert_eq!(actual, expected);
+        assert!(from_spec(&spec, BoardConfig::default(), 1).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
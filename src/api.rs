@@ -0,0 +1,490 @@
+use crate::board::{BoardConfig, Cell, CellValue, Outcome, Topology};
+use crate::engine::{Engine, SearchInfo};
+use crate::game::{Game, Seat, Variant};
+use crate::notation;
+use crate::record;
+use crate::share;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The longest an analysis can search for, in milliseconds. The server
+/// answers one request at a time, so a long search holds up everyone else.
+pub const MAX_ANALYSIS_MS: f64 = 5000.0;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ApiError {
+    NotFound,
+    MethodNotAllowed,
+    NoSuchGame(u64),
+    BadBody(String),
+    BadGame(String),
+    IllegalMove(String),
+    NotCpuTurn,
+    GameOver,
+    TooManyPlayers,
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::NotFound | ApiError::NoSuchGame(_) => 404,
+            ApiError::MethodNotAllowed => 405,
+            ApiError::NotCpuTurn | ApiError::GameOver => 409,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::NotFound => write!(f, "there is nothing at this path"),
+            ApiError::MethodNotAllowed => write!(f, "the path does not take this method"),
+            ApiError::NoSuchGame(id) => write!(f, "there is no game {}", id),
+            ApiError::BadBody(e) => write!(f, "the request body can not be read: {}", e),
+            ApiError::BadGame(e) => write!(f, "the game can not be made: {}", e),
+            ApiError::IllegalMove(m) => write!(f, "{} can not be played", m),
+            ApiError::NotCpuTurn => write!(f, "it is not the computer's turn"),
+            ApiError::GameOver => write!(f, "the game is over"),
+            ApiError::TooManyPlayers => write!(f, "only games of two players can be analyzed"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Response {
+    pub status: u16,
+    /// JSON, or empty when there is nothing to send back
+    pub body: String,
+}
+
+/// What `POST /games` takes, every field is optional
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct NewGame {
+    /// A name such as `connect-four`, or a board as written by
+    /// `notation::format_config` of at most `board::MAX_CELLS` cells
+    variant: Option<String>,
+    players: Option<u8>,
+    torus: bool,
+    /// A start position in the notation of `notation::parse`
+    position: Option<String>,
+    /// A code from `share::encode` to carry on from
+    share_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewMove {
+    /// A coordinate such as `b2`, or `b2:3` on boards with layers
+    #[serde(rename = "move")]
+    coordinate: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GameView {
+    id: u64,
+    config: BoardConfig,
+    cells: Vec<CellValue>,
+    seats: Vec<Seat>,
+    to_move: CellValue,
+    outcome: Outcome,
+    history: Vec<String>,
+    /// Missing for games with values the notation has no symbol for
+    notation: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Analysis {
+    best_move: String,
+    depth: u8,
+    score: i32,
+    /// Plies to the end when the search has seen it, negative when the
+    /// player to move loses
+    mate: Option<i32>,
+    nodes: u64,
+    pv: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorView {
+    error: String,
+}
+
+/// Games kept in memory and served as JSON. It has no sockets of its own,
+/// so it can be called in-process as well as from behind a server.
+///
+/// - `POST /games` makes a game from a `NewGame` body
+/// - `GET /games/{id}` is the state of a game
+/// - `DELETE /games/{id}` forgets a game
+/// - `POST /games/{id}/moves` plays `{"move": "b2"}` for the player to move
+/// - `POST /games/{id}/cpu-move` has the computer play its move
+/// - `GET /games/{id}/analysis?time=<ms>` is the engine's view of the
+///   position, searched for at most `MAX_ANALYSIS_MS`
+pub struct Api {
+    games: HashMap<u64, Game>,
+    next_id: u64,
+}
+
+impl Default for Api {
+    fn default() -> Self {
+        Api {
+            games: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Api {
+    pub fn handle(&mut self, method: &str, path: &str, body: &str) -> Response {
+        match self.route(method, path, body) {
+            Ok(response) => response,
+            Err(e) => Response {
+                status: e.status(),
+                body: to_json(&ErrorView {
+                    error: e.to_string(),
+                }),
+            },
+        }
+    }
+
+    fn route(&mut self, method: &str, path: &str, body: &str) -> Result<Response, ApiError> {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match segments[..] {
+            ["games"] => match method {
+                "POST" => {
+                    let request = if body.trim().is_empty() {
+                        NewGame::default()
+                    } else {
+                        parse_body(body)?
+                    };
+                    let game = new_game(request)?;
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.games.insert(id, game);
+                    Ok(self.view(id, 201))
+                }
+                _ => Err(ApiError::MethodNotAllowed),
+            },
+            ["games", id] => {
+                let id = self.find(id)?;
+                match method {
+                    "GET" => Ok(self.view(id, 200)),
+                    "DELETE" => {
+                        self.games.remove(&id);
+                        Ok(Response {
+                            status: 204,
+                            body: String::new(),
+                        })
+                    }
+                    _ => Err(ApiError::MethodNotAllowed),
+                }
+            }
+            ["games", id, "moves"] if method == "POST" => {
+                let id = self.find(id)?;
+                let new_move: NewMove = parse_body(body)?;
+                self.play(id, &new_move.coordinate)?;
+                Ok(self.view(id, 200))
+            }
+            ["games", id, "cpu-move"] if method == "POST" => {
+                let id = self.find(id)?;
+                let game = self.games.get_mut(&id).unwrap();
+                if game.outcome() != Outcome::InProgress {
+                    return Err(ApiError::GameOver);
+                }
+                if !game.is_cpu_turn() {
+                    return Err(ApiError::NotCpuTurn);
+                }
+                game.cpu_play();
+                Ok(self.view(id, 200))
+            }
+            ["games", id, "analysis"] if method == "GET" => {
+                let id = self.find(id)?;
+                let analysis = analyze(&self.games[&id], query)?;
+                Ok(Response {
+                    status: 200,
+                    body: to_json(&analysis),
+                })
+            }
+            ["games", _id, "moves" | "cpu-move" | "analysis"] => Err(ApiError::MethodNotAllowed),
+            _ => Err(ApiError::NotFound),
+        }
+    }
+
+    fn find(&self, id: &str) -> Result<u64, ApiError> {
+        let id = id.parse().map_err(|_e| ApiError::NotFound)?;
+        if self.games.contains_key(&id) {
+            Ok(id)
+        } else {
+            Err(ApiError::NoSuchGame(id))
+        }
+    }
+
+    fn play(&mut self, id: u64, coordinate: &str) -> Result<(), ApiError> {
+        let game = self.games.get_mut(&id).unwrap();
+        if game.outcome() != Outcome::InProgress {
+            return Err(ApiError::GameOver);
+        }
+
+        let config = game.board().config();
+        let value = game.current_seat().value;
+        record::parse_coordinate(config, coordinate)
+            .and_then(|c| game.play(Cell { value, ..c }))
+            .map(|_cell| ())
+            .ok_or_else(|| ApiError::IllegalMove(coordinate.to_string()))
+    }
+
+    fn view(&self, id: u64, status: u16) -> Response {
+        let game = &self.games[&id];
+        let config = game.board().config();
+        let view = GameView {
+            id,
+            config,
+            cells: game.board().values(),
+            seats: game.seats().to_vec(),
+            to_move: game.current_seat().value,
+            outcome: game.outcome(),
+            history: game
+                .history()
+                .iter()
+                .map(|c| record::format_coordinate(config, c))
+                .collect(),
+            notation: game.to_notation().ok(),
+        };
+
+        Response {
+            status,
+            body: to_json(&view),
+        }
+    }
+}
+
+fn new_game(request: NewGame) -> Result<Game, ApiError> {
+    let bad_game = |e: &dyn fmt::Display| ApiError::BadGame(e.to_string());
+
+    if let Some(code) = request.share_code {
+        return share::decode(&code).map_err(|e| bad_game(&e));
+    }
+    if let Some(position) = request.position {
+        return Game::from_notation(&position).map_err(|e| bad_game(&e));
+    }
+
+    let variant = request.variant.unwrap_or_else(|| "classic".to_string());
+    let mut game = match (Variant::from_name(&variant), request.players) {
+        (Some(variant), Some(players)) if players > 2 => Game::with_players(variant, players),
+        (Some(variant), _) => Game::with_variant(variant),
+        (None, None) => {
+            let config = notation::parse_config(&variant).map_err(|e| bad_game(&e))?;
+            Game::with_config(config)
+        }
+        (None, Some(_players)) => {
+            let message = "more players need a named variant";
+            return Err(bad_game(&message));
+        }
+    };
+    if request.torus {
+        game.set_topology(Topology::Torus);
+    }
+    Ok(game)
+}
+
+fn analyze(game: &Game, query: &str) -> Result<Analysis, ApiError> {
+    if game.seats().len() != 2 {
+        return Err(ApiError::TooManyPlayers);
+    }
+    if game.outcome() != Outcome::InProgress {
+        return Err(ApiError::GameOver);
+    }
+
+    let mut engine = Engine::default();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        match pair.split_once('=') {
+            Some(("time", ms)) => {
+                let time = ms
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|time| time.is_finite() && *time > 0.0)
+                    .ok_or_else(|| ApiError::BadBody(format!("bad time {}", ms)))?;
+                engine.time_budget_ms = time.min(MAX_ANALYSIS_MS);
+            }
+            _ => return Err(ApiError::BadBody(format!("unknown parameter {}", pair))),
+        }
+    }
+
+    let board = game.board();
+    let config = board.config();
+    let mut last: Option<SearchInfo> = None;
+    let best = engine
+        .analyse(board, game.current_seat().value, |info| {
+            last = Some(info.clone())
+        })
+        .ok_or(ApiError::GameOver)?;
+    let info = last.ok_or(ApiError::GameOver)?;
+
+    let coordinate = |index: usize| {
+        let (row, column, layer) = config.coordinates(index);
+        let cell = Cell {
+            row,
+            column,
+            layer,
+            value: CellValue::Empty,
+        };
+        record::format_coordinate(config, &cell)
+    };
+    Ok(Analysis {
+        best_move: record::format_coordinate(config, &best),
+        depth: info.depth,
+        score: info.score,
+        mate: info.plies_to_win(),
+        nodes: info.nodes,
+        pv: info.pv.iter().map(|&index| coordinate(index)).collect(),
+    })
+}
+
+fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|e| ApiError::BadBody(e.to_string()))
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Views only hold values JSON can write")
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::api::{Api, Response};
+
+    use serde_json::Value;
+
+    fn json(response: &Response) -> Value {
+        serde_json::from_str(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_handle__create_then_get() {
+        let mut api = Api::default();
+
+        let created = api.handle("POST", "/games", r#"{"variant": "connect-four"}"#);
+        let fetched = api.handle("GET", "/games/1", "");
+
+        assert_eq!(created.status, 201);
+        assert_eq!(json(&fetched)["config"]["rows"], 6);
+        assert_eq!(json(&fetched)["toMove"], "Human");
+    }
+
+    #[test]
+    fn test_handle__move_then_cpu_move() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", "");
+
+        let moved = api.handle("POST", "/games/1/moves", r#"{"move": "b2"}"#);
+        let replied = api.handle("POST", "/games/1/cpu-move", "");
+
+        assert_eq!(json(&moved)["history"], serde_json::json!(["b2"]));
+        assert_eq!(json(&replied)["history"].as_array().unwrap().len(), 2);
+        assert_eq!(json(&replied)["toMove"], "Human");
+    }
+
+    #[test]
+    fn test_handle__when__illegal_move() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", "");
+        api.handle("POST", "/games/1/moves", r#"{"move": "b2"}"#);
+
+        let actual = api.handle("POST", "/games/1/cpu-move", "");
+        let again = api.handle("POST", "/games/1/moves", r#"{"move": "b2"}"#);
+
+        assert_eq!(actual.status, 200);
+        assert_eq!(again.status, 400);
+        assert_eq!(json(&again)["error"], "b2 can not be played");
+    }
+
+    #[test]
+    fn test_handle__when__not_cpu_turn() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", "");
+
+        let actual = api.handle("POST", "/games/1/cpu-move", "").status;
+        let expected = 409;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_handle__analysis_finds_win() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", r#"{"position": "xx./oo./... x"}"#);
+
+        let actual = json(&api.handle("GET", "/games/1/analysis?time=100", ""));
+
+        assert_eq!(actual["bestMove"], "c3");
+        assert_eq!(actual["mate"], 1);
+    }
+
+    #[test]
+    fn test_handle__when__analysis_time_is_not_a_number() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", "");
+
+        let actual = api.handle("GET", "/games/1/analysis?time=NaN", "");
+
+        assert_eq!(actual.status, 400);
+        assert_eq!(
+            json(&actual)["error"],
+            "the request body can not be read: bad time NaN"
+        );
+        assert_eq!(
+            api.handle("GET", "/games/1/analysis?time=inf", "").status,
+            400
+        );
+        assert_eq!(
+            api.handle("GET", "/games/1/analysis?time=0", "").status,
+            400
+        );
+    }
+
+    #[test]
+    fn test_handle__when__game_deleted() {
+        let mut api = Api::default();
+        api.handle("POST", "/games", "");
+
+        let deleted = api.handle("DELETE", "/games/1", "");
+        let fetched = api.handle("GET", "/games/1", "");
+
+        assert_eq!(deleted.status, 204);
+        assert_eq!(fetched.status, 404);
+    }
+
+    #[test]
+    fn test_handle__when__board_can_not_be_played() {
+        let mut api = Api::default();
+
+        let zero = api.handle("POST", "/games", r#"{"variant": "3x3:0"}"#);
+        let huge = api.handle("POST", "/games", r#"{"variant": "50x50x50:3"}"#);
+        let fetched = api.handle("GET", "/games/1", "");
+
+        assert_eq!((zero.status, huge.status), (400, 400));
+        assert_eq!(
+            json(&zero)["error"],
+            "the game can not be made: a game can not be played on the board"
+        );
+        assert_eq!(fetched.status, 404);
+    }
+
+    #[test]
+    fn test_handle__when__bad_body() {
+        let mut api = Api::default();
+
+        let actual = api.handle("POST", "/games", "{").status;
+        let expected = 400;
+
+        assert_eq!(actual, expected);
+    }
+}
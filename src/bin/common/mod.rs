@@ -0,0 +1,126 @@
+use impossible_tic_tac_toe::board::{CellValue, Topology};
+use impossible_tic_tac_toe::game::{Game, PlayerKind, Variant};
+use impossible_tic_tac_toe::share;
+
+/// The options the terminal clients start a game with, for their usage text
+pub const OPTIONS: &str = "\
+Options:
+  --variant <name>     classic, qubic, connect-four, 4x4 or 5x5 (default classic)
+  --players <n>        number of players, the computer plays all but the first
+  --torus              lines wrap around the edges of the board
+  --hot-seat           people play every seat, taking turns at the keyboard
+  --watch              the computer plays every seat
+  --position <text>    start from a position such as \"x.o/.x./..o x\"
+  --code <code>        continue a game from a share code
+  --help               show this message
+";
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub variant: Variant,
+    pub players: u8,
+    pub torus: bool,
+    // Who plays every seat, when it is not the first seat against the CPU
+    pub everyone: Option<PlayerKind>,
+    pub position: Option<String>,
+    pub code: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            variant: Variant::Classic,
+            players: 2,
+            torus: false,
+            everyone: None,
+            position: None,
+            code: None,
+        }
+    }
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--variant" => {
+                let name = value()?;
+                options.variant = Variant::from_name(&name)
+                    .ok_or_else(|| format!("there is no variant called {}", name))?;
+            }
+            "--players" => {
+                let players = value()?;
+                options.players = players
+                    .parse()
+                    .ok()
+                    .filter(|&n| (2..=9).contains(&n))
+                    .ok_or_else(|| format!("{} is not a number of players from 2 to 9", players))?;
+            }
+            "--torus" => options.torus = true,
+            "--hot-seat" => options.everyone = Some(PlayerKind::Human),
+            "--watch" => options.everyone = Some(PlayerKind::Cpu),
+            "--position" => options.position = Some(value()?),
+            "--code" => options.code = Some(value()?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    // A position or a code already says whether the board is a torus
+    let has_board = options.position.is_some() || options.code.is_some();
+    if options.torus && has_board {
+        return Err("--torus can not be used with --position or --code".to_string());
+    }
+    Ok(options)
+}
+
+/// The game the options ask for. A game from a code or a position keeps
+/// its own board, a new one is made on the board the options describe.
+pub fn new_game(options: &Options) -> Result<Game, String> {
+    let mut game = if let Some(code) = &options.code {
+        share::decode(code).map_err(|e| e.to_string())?
+    } else if let Some(position) = &options.position {
+        Game::from_notation(position).map_err(|e| e.to_string())?
+    } else {
+        let topology = if options.torus {
+            Topology::Torus
+        } else {
+            Topology::Flat
+        };
+        let config = options.variant.config().with_topology(topology);
+        let kinds: Vec<PlayerKind> = (0..options.players)
+            .map(|seat| match seat {
+                0 => PlayerKind::Human,
+                _ => PlayerKind::Cpu,
+            })
+            .collect();
+        Game::with_kinds(config, &kinds)
+    };
+
+    if let Some(kind) = options.everyone {
+        for seat in 0..game.seats().len() {
+            game.set_player_kind(seat, kind);
+        }
+    }
+    Ok(game)
+}
+
+pub fn symbol(value: CellValue) -> char {
+    match value {
+        CellValue::Empty => '.',
+        CellValue::Human => 'X',
+        CellValue::CPU => 'O',
+        CellValue::Blocked => '#',
+        CellValue::Player(seat) => ['X', 'O', 'A', 'B', 'C', 'D', 'E', 'F', 'G']
+            .get(seat as usize)
+            .copied()
+            .unwrap_or('?'),
+        _ => '?',
+    }
+}
@@ -0,0 +1,27 @@
+use impossible_tic_tac_toe::protocol::Session;
+
+use std::io::{self, BufRead, Write};
+use std::process;
+
+/// Runs the engine protocol of `protocol::Session` over stdin and stdout
+fn main() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut session = Session::default();
+
+    for line in stdin.lock().lines() {
+        let result = line.and_then(|line| {
+            let is_running = session.handle(&line, &mut stdout)?;
+            stdout.flush()?;
+            Ok(is_running)
+        });
+        match result {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use impossible_tic_tac_toe::agent;
+use impossible_tic_tac_toe::board::CellValue::Human;
+use impossible_tic_tac_toe::board::{BoardConfig, Outcome};
+use impossible_tic_tac_toe::game::Variant;
+use impossible_tic_tac_toe::menace::Menace;
+use impossible_tic_tac_toe::tournament;
+
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Train the matchbox learner and save what it learned.
+
+Usage: menace [options]
+
+Options:
+  --variant <name>     classic, 4x4 or 5x5 (default classic)
+  --games <n>          games to train for (default 1000)
+  --against <agent>    an agent as for the tournament, or self (default evaluator)
+  --every <n>          games between progress reports (default 100)
+  --seed <n>           seed for the learner and its opponent (default 1)
+  --load <path>        carry on from what was saved before
+  --save <path>        save what was learned as JSON
+  --help               show this message
+
+Against another agent the learner moves first in every other game. The
+reports count its wins, draws and losses, or in self-play the wins of the
+first and second player.
+";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    variant: Variant,
+    games: u32,
+    against: String,
+    every: u32,
+    seed: u64,
+    load: Option<String>,
+    save: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            variant: Variant::Classic,
+            games: 1000,
+            against: "evaluator".to_string(),
+            every: 100,
+            seed: 1,
+            load: None,
+            save: None,
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--variant" => {
+                let name = value()?;
+                options.variant = Variant::from_name(&name)
+                    .ok_or_else(|| format!("there is no variant called {}", name))?;
+            }
+            "--games" | "--every" | "--seed" => {
+                let number = value()?;
+                let parsed = number
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&n| n > 0 || arg == "--seed")
+                    .ok_or_else(|| format!("{} is not a number for {}", number, arg))?;
+                match arg.as_str() {
+                    "--games" => options.games = parsed as u32,
+                    "--every" => options.every = parsed as u32,
+                    _ => options.seed = parsed,
+                }
+            }
+            "--against" => options.against = value()?,
+            "--load" => options.load = Some(value()?),
+            "--save" => options.save = Some(value()?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn new_menace(options: &Options, config: BoardConfig) -> Result<Menace, String> {
+    let menace = match &options.load {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("could not read {}: {}", path, e))
+            .and_then(|json| Menace::from_json(&json).map_err(|e| e.to_string()))?,
+        None => Menace::new(config, options.seed).map_err(|e| e.to_string())?,
+    };
+    if menace.config() != config {
+        return Err("what was loaded was learned on another board".to_string());
+    }
+    Ok(menace)
+}
+
+/// Trains the learner, passing a line about every batch of games to `report`
+fn train<F: FnMut(String)>(
+    options: &Options,
+    menace: &mut Menace,
+    mut report: F,
+) -> Result<(), String> {
+    let config = menace.config();
+    let mut opponent = match options.against.as_str() {
+        "self" => None,
+        spec => Some(
+            agent::from_spec(spec, config, options.seed.wrapping_add(1))
+                .map_err(|e| e.to_string())?,
+        ),
+    };
+
+    let mut played = 0;
+    while played < options.games {
+        let batch = options.every.min(options.games - played);
+        let (wins, draws, losses) = match opponent.as_mut() {
+            Some(opponent) => {
+                let p = tournament::play_match(config, menace, &mut **opponent, batch);
+                (p.wins, p.draws, p.losses)
+            }
+            None => (0..batch).fold((0, 0, 0), |(first, draws, second), _game| {
+                match menace.play_self() {
+                    Outcome::Winner(Human) => (first + 1, draws, second),
+                    Outcome::Winner(_value) => (first, draws, second + 1),
+                    _ => (first, draws + 1, second),
+                }
+            }),
+        };
+        played += batch;
+        report(format!(
+            "{:>6} games  +{} ={} -{}  {} positions",
+            played,
+            wins,
+            draws,
+            losses,
+            menace.positions()
+        ));
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let config = options.variant.config();
+    let mut menace = new_menace(&options, config).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    if let Err(e) = train(&options, &mut menace, |line| println!("{}", line)) {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+
+    if let Some(path) = &options.save {
+        if let Err(e) = fs::write(path, menace.to_json()) {
+            eprintln!("Could not write {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{new_menace, parse_args, train, Options};
+    use impossible_tic_tac_toe::board::BoardConfig;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let actual = parse_args(&args(&[
+            "--games",
+            "50",
+            "--against",
+            "self",
+            "--save",
+            "m.json",
+        ]));
+        let expected = Ok(Options {
+            games: 50,
+            against: "self".to_string(),
+            save: Some("m.json".to_string()),
+            ..Options::default()
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_args__when__no_games() {
+        let actual = parse_args(&args(&["--games", "0"]));
+        let expected = Err("0 is not a number for --games".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_train__reports_every_batch() {
+        let options = Options {
+            games: 25,
+            every: 10,
+            against: "random".to_string(),
+            ..Options::default()
+        };
+        let mut menace = new_menace(&options, BoardConfig::default()).unwrap();
+        let mut reports = vec![];
+
+        train(&options, &mut menace, |line| reports.push(line)).unwrap();
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports[2].trim_start().starts_with("25 games"));
+    }
+
+    #[test]
+    fn test_train__when__unknown_opponent() {
+        let options = Options {
+            against: "deep-blue".to_string(),
+            ..Options::default()
+        };
+        let mut menace = new_menace(&options, BoardConfig::default()).unwrap();
+
+        let actual = train(&options, &mut menace, |_line| {});
+        let expected = Err("unknown agent deep-blue".to_string());
+
+        assert_eq!(actual, expected);
+    }
+}
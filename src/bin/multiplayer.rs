@@ -0,0 +1,316 @@
+use impossible_tic_tac_toe::lobby::{ClientId, ClientMessage, Lobby, ServerMessage};
+use impossible_tic_tac_toe::utils::now_ms;
+
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+use tungstenite::error::ProtocolError;
+use tungstenite::{Message, WebSocket};
+
+const USAGE: &str = "\
+Play tic-tac-toe between two people over WebSockets.
+
+Usage:
+  multiplayer serve [--port <port>]
+  multiplayer client <url> create [<variant>]
+  multiplayer client <url> join <code>
+  multiplayer client <url> rejoin <code> <token>
+
+The client is for trying the server out: it prints what the server sends
+and sends every line typed in as a move, such as b2.
+";
+
+// How long a connection waits for a message before checking for messages to
+// send it
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// How often rooms no one is connected to are looked for
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+type Outboxes = Arc<Mutex<HashMap<ClientId, Sender<ServerMessage>>>>;
+
+/// Accepts connections until the listener fails, each on its own thread
+fn serve(listener: TcpListener) {
+    let lobby = Arc::new(Mutex::new(Lobby::default()));
+    let outboxes: Outboxes = Arc::new(Mutex::new(HashMap::new()));
+
+    let expiring = Arc::clone(&lobby);
+    thread::spawn(move || loop {
+        thread::sleep(EXPIRY_INTERVAL);
+        lock(&expiring).expire_rooms(now_ms());
+    });
+
+    for stream in listener.incoming().flatten() {
+        let lobby = Arc::clone(&lobby);
+        let outboxes = Arc::clone(&outboxes);
+        thread::spawn(move || {
+            if let Err(e) = connection(stream, &lobby, &outboxes) {
+                eprintln!("Connection closed: {}", e);
+            }
+        });
+    }
+}
+
+fn connection(stream: TcpStream, lobby: &Mutex<Lobby>, outboxes: &Outboxes) -> io::Result<()> {
+    let mut socket = tungstenite::accept(stream).map_err(io::Error::other)?;
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let client = lock(lobby).connect();
+    let (sender, outbox) = mpsc::channel();
+    lock(outboxes).insert(client, sender);
+
+    let result = pump(&mut socket, client, &outbox, lobby, outboxes);
+
+    lock(outboxes).remove(&client);
+    let messages = lock(lobby).disconnect(client);
+    deliver(outboxes, messages);
+    result
+}
+
+/// Passes messages between the socket and the lobby until the socket closes
+fn pump(
+    socket: &mut WebSocket<TcpStream>,
+    client: ClientId,
+    outbox: &Receiver<ServerMessage>,
+    lobby: &Mutex<Lobby>,
+    outboxes: &Outboxes,
+) -> io::Result<()> {
+    loop {
+        for message in outbox.try_iter() {
+            let text = serde_json::to_string(&message).unwrap();
+            socket.send(Message::text(text)).map_err(io::Error::other)?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let messages = lock(lobby).receive(client, &text);
+                deliver(outboxes, messages);
+            }
+            Ok(Message::Close(_frame)) => return Ok(()),
+            Ok(_other) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed)
+            | Err(tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)) => {
+                return Ok(())
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        }
+    }
+}
+
+/// Locks state the connections share. A connection that panicked while
+/// holding the lock only takes itself down, the others carry on.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn deliver(outboxes: &Outboxes, messages: Vec<(ClientId, ServerMessage)>) {
+    let outboxes = lock(outboxes);
+    for (client, message) in messages {
+        if let Some(sender) = outboxes.get(&client) {
+            // A client that has just gone will be told nothing more
+            let _ = sender.send(message);
+        }
+    }
+}
+
+/// A connection to the server that speaks in lobby messages
+struct Client {
+    socket: WebSocket<tungstenite::stream::MaybeTlsStream<TcpStream>>,
+}
+
+impl Client {
+    fn connect(url: &str) -> io::Result<Client> {
+        let (socket, _response) = tungstenite::connect(url).map_err(io::Error::other)?;
+        if let tungstenite::stream::MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        }
+        Ok(Client { socket })
+    }
+
+    fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+        let text = serde_json::to_string(message).unwrap();
+        self.socket
+            .send(Message::text(text))
+            .map_err(io::Error::other)
+    }
+
+    /// The next message from the server, or `None` if none came in time
+    fn try_receive(&mut self) -> io::Result<Option<ServerMessage>> {
+        match self.socket.read() {
+            Ok(Message::Text(text)) => serde_json::from_str(&text)
+                .map(Some)
+                .map_err(io::Error::other),
+            Ok(_other) => Ok(None),
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+fn run_client(url: &str, first: ClientMessage) -> io::Result<()> {
+    let mut client = Client::connect(url)?;
+    client.send(&first)?;
+
+    let (lines, typed) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        if let Some(message) = client.try_receive()? {
+            println!("{}", serde_json::to_string(&message).unwrap());
+        }
+        for line in typed.try_iter() {
+            let coordinate = line.trim().to_string();
+            if !coordinate.is_empty() {
+                client.send(&ClientMessage::Move { coordinate })?;
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args[..] {
+        ["serve"] | ["serve", "--port", _] => {
+            let port = args.get(2).copied().unwrap_or("9001");
+            let address = format!("127.0.0.1:{}", port);
+            TcpListener::bind(&address).map(|listener| {
+                println!("Listening on ws://{}", address);
+                serve(listener)
+            })
+        }
+        ["client", url, "create"] => run_client(url, ClientMessage::Create { variant: None }),
+        ["client", url, "create", variant] => {
+            let variant = Some(variant.to_string());
+            run_client(url, ClientMessage::Create { variant })
+        }
+        ["client", url, "join", code] => {
+            let code = code.to_string();
+            run_client(url, ClientMessage::Join { code })
+        }
+        ["client", url, "rejoin", code, token] => {
+            let (code, token) = (code.to_string(), token.to_string());
+            run_client(url, ClientMessage::Rejoin { code, token })
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{serve, Client};
+    use impossible_tic_tac_toe::board::{CellValue, Outcome};
+    use impossible_tic_tac_toe::lobby::{ClientMessage, ServerMessage};
+
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Instant;
+
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        thread::spawn(move || serve(listener));
+        url
+    }
+
+    /// Waits for the first message the test is looking for, skipping others
+    fn wait_for<F: Fn(&ServerMessage) -> bool>(client: &mut Client, wanted: F) -> ServerMessage {
+        let start = Instant::now();
+        while start.elapsed().as_secs() < 5 {
+            if let Some(message) = client.try_receive().unwrap() {
+                if wanted(&message) {
+                    return message;
+                }
+            }
+        }
+        panic!("no matching message from the server");
+    }
+
+    fn play(client: &mut Client, coordinate: &str) {
+        let coordinate = coordinate.to_string();
+        client.send(&ClientMessage::Move { coordinate }).unwrap();
+    }
+
+    fn history_length(message: &ServerMessage) -> Option<usize> {
+        match message {
+            ServerMessage::State { history, .. } => Some(history.len()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_serve__two_players_with_reconnect() {
+        let url = start_server();
+        let mut first = Client::connect(&url).unwrap();
+        let mut second = Client::connect(&url).unwrap();
+
+        first
+            .send(&ClientMessage::Create { variant: None })
+            .unwrap();
+        let (code, token) =
+            match wait_for(&mut first, |m| matches!(m, ServerMessage::Joined { .. })) {
+                ServerMessage::Joined { code, token, .. } => (code, token),
+                _ => unreachable!(),
+            };
+        second
+            .send(&ClientMessage::Join { code: code.clone() })
+            .unwrap();
+        wait_for(&mut second, |m| history_length(m) == Some(0));
+
+        play(&mut first, "a1");
+        wait_for(&mut second, |m| history_length(m) == Some(1));
+        play(&mut second, "a2");
+        wait_for(&mut first, |m| history_length(m) == Some(2));
+
+        // The first player drops and comes back on a new connection
+        drop(first);
+        wait_for(&mut second, |m| *m == ServerMessage::OpponentLeft);
+        let mut first = Client::connect(&url).unwrap();
+        first.send(&ClientMessage::Rejoin { code, token }).unwrap();
+        wait_for(&mut first, |m| history_length(m) == Some(2));
+
+        play(&mut first, "b1");
+        wait_for(&mut second, |m| history_length(m) == Some(3));
+        play(&mut second, "b2");
+        wait_for(&mut first, |m| history_length(m) == Some(4));
+        play(&mut first, "c1");
+
+        let actual = wait_for(&mut second, |m| history_length(m) == Some(5));
+
+        assert!(matches!(
+            actual,
+            ServerMessage::State {
+                outcome: Outcome::Winner(CellValue::Human),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,303 @@
+use impossible_tic_tac_toe::board::BoardConfig;
+use impossible_tic_tac_toe::engine::Engine;
+use impossible_tic_tac_toe::game::Variant;
+use impossible_tic_tac_toe::network::{self, Network, Sample, MAX_HIDDEN};
+use impossible_tic_tac_toe::utils::{self, Random};
+
+use std::env;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Train a network for the engine to judge positions with, offline, and save
+its weights, which a game in the browser can load with setNetwork.
+
+Usage: network selfplay [options]
+       network train --data <path> --out <path> [options]
+
+selfplay plays the engine against itself and writes a sample for every
+move it made, one JSON object a line. train learns from those samples.
+
+Options:
+  --variant <name>     classic, qubic, connect-four, 4x4 or 5x5 (default connect-four)
+  --games <n>          self-play games (default 200)
+  --depth <n>          plies the engine searches in self-play (default 2)
+  --hidden <n>         hidden units of a new network (default 32)
+  --epochs <n>         times to go through the samples (default 20)
+  --rate <x>           learning rate (default 0.01)
+  --seed <n>           seed for the random moves and weights (default 1)
+  --load <path>        weights to search with in self-play, or to carry on
+                       training from
+  --data <path>        samples to train on
+  --out <path>         where to write the samples or the weights, samples
+                       are printed without it
+  --help               show this message
+";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Command {
+    SelfPlay,
+    Train,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    variant: Variant,
+    games: u32,
+    depth: u8,
+    hidden: usize,
+    epochs: u32,
+    rate: f32,
+    seed: u64,
+    load: Option<String>,
+    data: Option<String>,
+    out: Option<String>,
+}
+
+impl Options {
+    fn new(command: Command) -> Options {
+        Options {
+            command,
+            variant: Variant::ConnectFour,
+            games: 200,
+            depth: 2,
+            hidden: 32,
+            epochs: 20,
+            rate: 0.01,
+            seed: 1,
+            load: None,
+            data: None,
+            out: None,
+        }
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_e| format!("{} is not a value for {}", text, arg))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = match args.first().map(|arg| arg.as_str()) {
+        Some("selfplay") => Options::new(Command::SelfPlay),
+        Some("train") => Options::new(Command::Train),
+        Some(command) => return Err(format!("unknown command {}", command)),
+        None => return Err("a command is needed".to_string()),
+    };
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--variant" => {
+                let name = value?;
+                options.variant = Variant::from_name(&name)
+                    .ok_or_else(|| format!("there is no variant called {}", name))?;
+            }
+            "--games" => options.games = parse(arg, &value?)?,
+            "--depth" => options.depth = parse(arg, &value?)?,
+            "--hidden" => {
+                let hidden = value?;
+                options.hidden = parse(arg, &hidden)
+                    .ok()
+                    .filter(|n| (1..=MAX_HIDDEN).contains(n))
+                    .ok_or_else(|| {
+                        format!(
+                            "{} is not a number of hidden units from 1 to {}",
+                            hidden, MAX_HIDDEN
+                        )
+                    })?;
+            }
+            "--epochs" => options.epochs = parse(arg, &value?)?,
+            "--rate" => options.rate = parse(arg, &value?)?,
+            "--seed" => options.seed = parse(arg, &value?)?,
+            "--load" => options.load = Some(value?),
+            "--data" => options.data = Some(value?),
+            "--out" => options.out = Some(value?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    let is_missing_paths = options.data.is_none() || options.out.is_none();
+    if options.command == Command::Train && is_missing_paths {
+        return Err("train needs --data and --out".to_string());
+    }
+    Ok(options)
+}
+
+fn load_network(path: &str, config: BoardConfig) -> Result<Network, String> {
+    let network = fs::read(path)
+        .map_err(|e| format!("could not read {}: {}", path, e))
+        .and_then(|bytes| Network::from_bytes(&bytes).map_err(|e| e.to_string()))?;
+    if network.config() != config {
+        return Err("the network was trained on another board".to_string());
+    }
+    Ok(network)
+}
+
+/// The samples of the self-play games, one JSON object a line
+fn self_play(options: &Options) -> Result<String, String> {
+    let config = options.variant.config();
+    let network = match &options.load {
+        Some(path) => Some(load_network(path, config)?),
+        None => None,
+    };
+    let engine = Engine {
+        max_depth: options.depth,
+        time_budget_ms: f64::INFINITY,
+        network,
+        ..Engine::default()
+    };
+
+    let samples = network::self_play(config, &engine, options.games, options.seed);
+    Ok(samples
+        .iter()
+        .map(|sample| utils::to_json(sample, false) + "\n")
+        .collect())
+}
+
+/// Trains on the samples in `data`, passing a line about every epoch to
+/// `report`
+fn train<F: FnMut(String)>(
+    options: &Options,
+    data: &str,
+    mut report: F,
+) -> Result<Network, String> {
+    let config = options.variant.config();
+    let samples = data
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            utils::from_json::<Sample>(line).map_err(|e| format!("sample {}: {}", i + 1, e))
+        })
+        .collect::<Result<Vec<Sample>, String>>()?;
+    let mut network = match &options.load {
+        Some(path) => load_network(path, config)?,
+        None => Network::new(config, options.hidden, options.seed).map_err(|e| e.to_string())?,
+    };
+
+    let mut random = Random::new(options.seed);
+    for epoch in 1..=options.epochs {
+        let loss = network
+            .train(&samples, options.rate, &mut random)
+            .map_err(|e| e.to_string())?;
+        report(format!("epoch {:>4}  loss {:.4}", epoch, loss));
+    }
+    Ok(network)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let output = match options.command {
+        Command::SelfPlay => self_play(&options).map(|lines| lines.into_bytes()),
+        Command::Train => {
+            let path = options.data.as_ref().unwrap();
+            fs::read_to_string(path)
+                .map_err(|e| format!("could not read {}: {}", path, e))
+                .and_then(|data| train(&options, &data, |line| println!("{}", line)))
+                .map(|network| network.to_bytes())
+        }
+    };
+    let output = output.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    match &options.out {
+        Some(path) => {
+            if let Err(e) = fs::write(path, output) {
+                eprintln!("Could not write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", String::from_utf8_lossy(&output)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{parse_args, self_play, train, Command, Options};
+    use impossible_tic_tac_toe::game::Variant;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let actual = parse_args(&args(&[
+            "train", "--data", "s.jsonl", "--out", "n.bin", "--hidden", "16",
+        ]));
+        let expected = Ok(Options {
+            hidden: 16,
+            data: Some("s.jsonl".to_string()),
+            out: Some("n.bin".to_string()),
+            ..Options::new(Command::Train)
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_args__when__train_without_data() {
+        let actual = parse_args(&args(&["train", "--out", "n.bin"]));
+        let expected = Err("train needs --data and --out".to_string());
+
+        assert_eq!(actual, expected);
+        assert!(parse_args(&args(&["play"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args__when__too_many_hidden_units() {
+        let actual = parse_args(&args(&["selfplay", "--hidden", "70000"]));
+        let expected = Err("70000 is not a number of hidden units from 1 to 65535".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_train__on_self_play() {
+        let options = Options {
+            variant: Variant::Classic,
+            games: 5,
+            depth: 1,
+            hidden: 8,
+            epochs: 3,
+            ..Options::new(Command::SelfPlay)
+        };
+        let data = self_play(&options).unwrap();
+        let mut reports = vec![];
+
+        let network = train(&options, &data, |line| reports.push(line)).unwrap();
+
+        assert_eq!(reports.len(), 3);
+        assert!(reports[2].starts_with("epoch    3  loss"));
+        assert_eq!(network.config(), Variant::Classic.config());
+    }
+
+    #[test]
+    fn test_train__when__bad_sample() {
+        let options = Options::new(Command::Train);
+
+        let actual = train(&options, "{\"position\":\"...\"}", |_line| {}).err();
+
+        assert!(actual.unwrap().starts_with("sample 1:"));
+    }
+}
@@ -0,0 +1,367 @@
+mod common;
+
+use common::{new_game, parse_args, symbol, OPTIONS};
+use impossible_tic_tac_toe::board::{BoardConfig, Cell, Outcome, Topology};
+use impossible_tic_tac_toe::game::Game;
+use impossible_tic_tac_toe::record::{self, Metadata};
+use impossible_tic_tac_toe::share;
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const USAGE: &str = "\
+Play tic-tac-toe against the computer in the terminal.
+
+Usage: play [options]
+";
+
+const HELP: &str = "\
+Play a move with its coordinate, such as b2, with the column letter first and
+rows counted from the bottom. Boards with layers take the layer after a colon,
+such as b2:3. On the classic board 1 to 9 are the cells as laid out on a
+number pad, and on boards with gravity a column letter is enough.
+
+Commands:
+  help       show this message
+  restart    start the game over
+  record     print the game as a record
+  share      print a code to share the game
+  quit       leave
+";
+
+/// The board with column letters on top and row numbers counting from the
+/// bottom, one grid per layer
+fn render(game: &Game) -> String {
+    let config = game.board().config();
+    let values = game.board().values();
+    let width = config.rows.to_string().len();
+    // Columns past z take two letters or more, as in records
+    let cell_width = record::format_column(config.columns.saturating_sub(1)).len();
+
+    let letters: String = (0..config.columns)
+        .map(|column| {
+            let letters = record::format_column(column);
+            format!(" {:>width$}", letters, width = cell_width)
+        })
+        .collect();
+    let mut text = String::new();
+    for layer in 0..config.layers {
+        if config.layers > 1 {
+            text.push_str(&format!("Layer {}\n", layer + 1));
+        }
+        text.push_str(&format!("{:width$}{}\n", "", letters, width = width));
+        for row in 0..config.rows {
+            text.push_str(&format!("{:>width$}", config.rows - row, width = width));
+            for column in 0..config.columns {
+                let index = config.index(row, column, layer).unwrap();
+                let mark = symbol(values[index]);
+                text.push_str(&format!(" {:>width$}", mark, width = cell_width));
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The cell a player means with what they typed, for the player to move
+fn parse_move(game: &Game, input: &str) -> Option<Cell> {
+    let config = game.board().config();
+    let value = game.current_seat().value;
+    let input = input.trim().to_lowercase();
+
+    let is_classic = BoardConfig {
+        topology: Topology::Flat,
+        ..config
+    } == BoardConfig::default();
+    let numpad_key = input.parse::<u8>().ok().filter(|n| (1..=9).contains(n));
+    if let (true, Some(key)) = (is_classic, numpad_key) {
+        return Some(Cell {
+            row: 2 - (key - 1) / 3,
+            column: (key - 1) % 3,
+            layer: 0,
+            value,
+        });
+    }
+
+    if let (true, Some(column)) = (config.gravity, record::parse_column(&input)) {
+        return game
+            .board()
+            .legal_moves()
+            .into_iter()
+            .find(|c| (c.column as u32, c.layer) == (column, 0))
+            .map(|c| Cell { value, ..c });
+    }
+
+    record::parse_coordinate(config, &input).map(|c| Cell { value, ..c })
+}
+
+fn describe_outcome(game: &Game) -> String {
+    match game.outcome() {
+        Outcome::Draw => "It's a draw.".to_string(),
+        Outcome::Winner(winner) if game.is_human_winner() => {
+            format!("{} wins, well played!", symbol(winner))
+        }
+        Outcome::Winner(winner) => format!("{} wins, the computer takes it.", symbol(winner)),
+        Outcome::InProgress => String::new(),
+    }
+}
+
+/// Shows the final board and the result once the game is over, returning
+/// whether it did
+fn announce_result<W: Write>(game: &Game, output: &mut W) -> io::Result<bool> {
+    if game.outcome() == Outcome::InProgress {
+        return Ok(false);
+    }
+    write!(output, "\n{}", render(game))?;
+    writeln!(output, "{}", describe_outcome(game))?;
+    writeln!(output, "Type restart to play again or quit to leave.")?;
+    Ok(true)
+}
+
+fn run<R: BufRead, W: Write>(mut game: Game, input: R, output: &mut W) -> io::Result<()> {
+    let config = game.board().config();
+    let mut lines = input.lines();
+    let mut needs_render = true;
+
+    loop {
+        if needs_render {
+            write!(output, "\n{}", render(&game))?;
+            needs_render = false;
+        }
+
+        let is_over = game.outcome() != Outcome::InProgress;
+        if !is_over && game.is_cpu_turn() {
+            game.cpu_play();
+            if let Some(cell) = game.history().last() {
+                let coordinate = record::format_coordinate(config, cell);
+                writeln!(output, "{} plays {}", symbol(cell.value), coordinate)?;
+            }
+            needs_render = !announce_result(&game, output)?;
+            continue;
+        }
+
+        if is_over {
+            write!(output, "> ")?;
+        } else {
+            write!(output, "{} to play > ", symbol(game.current_seat().value))?;
+        }
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            "help" => write!(output, "{}", HELP)?,
+            "restart" => {
+                game.restart();
+                needs_render = true;
+            }
+            "record" => match record::format(&game, &Metadata::default()) {
+                Ok(text) => write!(output, "{}", text)?,
+                Err(e) => writeln!(output, "The game can not be written down: {}", e)?,
+            },
+            "share" => match share::encode(&game) {
+                Ok(code) => writeln!(output, "{}", code)?,
+                Err(e) => writeln!(output, "The game can not be shared: {}", e)?,
+            },
+            _ if is_over => writeln!(output, "The game is over.")?,
+            text => match parse_move(&game, text).and_then(|cell| game.play(cell)) {
+                Some(_cell) => needs_render = !announce_result(&game, output)?,
+                None => writeln!(
+                    output,
+                    "{} is not a move you can play, type help for how to play.",
+                    text
+                )?,
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}\n{}", USAGE, OPTIONS);
+        return;
+    }
+
+    let game = parse_args(&args).and_then(|options| new_game(&options));
+    let game = match game {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("{}\n\n{}\n{}", e, USAGE, OPTIONS);
+            process::exit(2);
+        }
+    };
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    if let Err(e) = run(game, stdin.lock(), &mut stdout) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::common::{new_game, parse_args, Options};
+    use super::{parse_move, render, run};
+    use impossible_tic_tac_toe::board::{Cell, CellValue, Topology};
+    use impossible_tic_tac_toe::game::{Game, Variant};
+    use impossible_tic_tac_toe::share;
+
+    use std::io::Cursor;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_args__when__all_options() {
+        let actual = parse_args(&args("--variant 4x4 --players 3 --torus")).unwrap();
+        let expected = Options {
+            variant: Variant::FourByFour,
+            players: 3,
+            torus: true,
+            ..Options::default()
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_args__when__unknown_variant() {
+        let actual = parse_args(&args("--variant chess"));
+
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn test_parse_args__when__torus_with_position() {
+        let actual = parse_args(&args("--torus --position x.o/.x./..o"));
+        let expected = Err("--torus can not be used with --position or --code".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_new_game__when__torus_with_players() {
+        let options = parse_args(&args("--variant 4x4 --players 3 --torus --hot-seat")).unwrap();
+
+        let game = new_game(&options).unwrap();
+
+        assert_eq!(game.board().config().topology, Topology::Torus);
+        assert_eq!(game.seats().len(), 3);
+        assert!(game.seats().iter().all(|seat| !seat.is_cpu()));
+    }
+
+    #[test]
+    fn test_new_game__when__code() {
+        let mut shared = Game::with_variant(Variant::ConnectFour);
+        shared.human_play(5, 3);
+        let code = share::encode(&shared).unwrap();
+        let options = parse_args(&args(&format!("--code {} --watch", code))).unwrap();
+
+        let game = new_game(&options).unwrap();
+
+        assert_eq!(game.board().values(), shared.board().values());
+        assert!(game.is_cpu_turn());
+    }
+
+    #[test]
+    fn test_run__when__watching() {
+        let options = parse_args(&args("--watch")).unwrap();
+        let game = new_game(&options).unwrap();
+        let mut output = vec![];
+
+        run(game, Cursor::new("quit\n"), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("X plays "));
+        assert!(output.contains("It's a draw."));
+    }
+
+    #[test]
+    fn test_parse_move__when__numpad() {
+        let game = Game::new();
+
+        let actual = parse_move(&game, "7");
+        let expected = Some(Cell {
+            row: 0,
+            column: 0,
+            layer: 0,
+            value: CellValue::Human,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_move__when__column_with_gravity() {
+        let game = Game::with_variant(Variant::ConnectFour);
+
+        let actual = parse_move(&game, "c");
+        let expected = Some(Cell {
+            row: 5,
+            column: 2,
+            layer: 0,
+            value: CellValue::Human,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_render__when__classic() {
+        let mut game = Game::new();
+        game.human_play(1, 1);
+
+        let actual = render(&game);
+        let expected = "  a b c\n3 . . .\n2 . X .\n1 . . .\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_render__when__columns_past_z() {
+        let position = format!("xo{}/{} x", ".".repeat(26), ".".repeat(28));
+        let game = Game::from_notation(&position).unwrap();
+
+        let actual = render(&game);
+
+        assert!(actual.starts_with("   a  b"));
+        assert!(actual.contains(" aa ab\n"));
+        assert!(actual.contains("2  X  O  ."));
+    }
+
+    #[test]
+    fn test_parse_move__when__gravity_column_past_z() {
+        let position = format!("{}/{} x 2x28:4g", ".".repeat(28), ".".repeat(28));
+        let game = Game::from_notation(&position).unwrap();
+
+        let actual = parse_move(&game, "ab").map(|c| (c.row, c.column));
+        let expected = Some((1, 27));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_run__plays_against_the_cpu() {
+        let game = new_game(&Options::default()).unwrap();
+        let input = Cursor::new("b2\nz9\nquit\n");
+        let mut output = vec![];
+
+        run(game, input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("O plays "));
+        assert!(output.contains("z9 is not a move you can play"));
+    }
+}
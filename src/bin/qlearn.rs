@@ -0,0 +1,246 @@
+use impossible_tic_tac_toe::agent;
+use impossible_tic_tac_toe::game::Variant;
+use impossible_tic_tac_toe::qlearning::{self, Epoch, QLearner, QTable, Settings};
+
+use std::env;
+use std::fs;
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Train a Q-learning player and save its Q-table, which a game in the browser
+can play as with setPolicy.
+
+Usage: qlearn [options]
+
+Options:
+  --variant <name>         classic, 4x4 or 5x5 (default classic)
+  --epochs <n>             epochs to train for (default 20)
+  --games <n>              games in every epoch (default 500)
+  --against <agent>        an agent as for the tournament, or self (default self)
+  --learning-rate <x>      how far a value moves towards what is learned (default 0.3)
+  --discount <x>           how much a value counts for the move before (default 0.95)
+  --epsilon-start <x>      chance of a random move at the start (default 1)
+  --epsilon-end <x>        chance of a random move at the end (default 0.05)
+  --epsilon-games <n>      games over which the chance falls (default 5000)
+  --win <x>                reward for a win (default 1)
+  --draw <x>               reward for a draw (default 0.5)
+  --loss <x>               reward for a loss (default -1)
+  --step <x>               reward for every move (default 0)
+  --seed <n>               seed for the learner and its opponent (default 1)
+  --load <path>            carry on from a saved Q-table
+  --save <path>            save the Q-table as JSON
+  --curve <path>           write the learning curve there instead of printing it
+  --help                   show this message
+
+The learning curve is CSV with the wins, draws and losses of every epoch,
+counted for the learner, or in self-play for the player who moves first.
+";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    variant: Variant,
+    epochs: u32,
+    games: u32,
+    against: String,
+    settings: Settings,
+    seed: u64,
+    load: Option<String>,
+    save: Option<String>,
+    curve: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            variant: Variant::Classic,
+            epochs: 20,
+            games: 500,
+            against: "self".to_string(),
+            settings: Settings::default(),
+            seed: 1,
+            load: None,
+            save: None,
+            curve: None,
+        }
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, text: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_e| format!("{} is not a value for {}", text, arg))
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("{} needs a value", arg));
+        let settings = &mut options.settings;
+        match arg.as_str() {
+            "--variant" => {
+                let name = value?;
+                options.variant = Variant::from_name(&name)
+                    .ok_or_else(|| format!("there is no variant called {}", name))?;
+            }
+            "--epochs" => options.epochs = parse(arg, &value?)?,
+            "--games" => options.games = parse(arg, &value?)?,
+            "--against" => options.against = value?,
+            "--learning-rate" => settings.learning_rate = parse(arg, &value?)?,
+            "--discount" => settings.discount = parse(arg, &value?)?,
+            "--epsilon-start" => settings.epsilon.start = parse(arg, &value?)?,
+            "--epsilon-end" => settings.epsilon.end = parse(arg, &value?)?,
+            "--epsilon-games" => settings.epsilon.games = parse(arg, &value?)?,
+            "--win" => settings.rewards.win = parse(arg, &value?)?,
+            "--draw" => settings.rewards.draw = parse(arg, &value?)?,
+            "--loss" => settings.rewards.loss = parse(arg, &value?)?,
+            "--step" => settings.rewards.step = parse(arg, &value?)?,
+            "--seed" => options.seed = parse(arg, &value?)?,
+            "--load" => options.load = Some(value?),
+            "--save" => options.save = Some(value?),
+            "--curve" => options.curve = Some(value?),
+            _ => return Err(format!("unknown option {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(QTable, Vec<Epoch>), String> {
+    let config = options.variant.config();
+    let mut learner = match &options.load {
+        Some(path) => {
+            let table = fs::read_to_string(path)
+                .map_err(|e| format!("could not read {}: {}", path, e))
+                .and_then(|json| QTable::from_json(&json).map_err(|e| e.to_string()))?;
+            if table.config() != config {
+                return Err("the Q-table was learned on another board".to_string());
+            }
+            QLearner::with_table(table, options.settings, options.seed)
+        }
+        None => QLearner::new(config, options.settings, options.seed).map_err(|e| e.to_string())?,
+    };
+
+    let epochs = match options.against.as_str() {
+        "self" => learner.train(None, options.epochs, options.games),
+        spec => {
+            let mut opponent = agent::from_spec(spec, config, options.seed.wrapping_add(1))
+                .map_err(|e| e.to_string())?;
+            learner.train(Some(&mut *opponent), options.epochs, options.games)
+        }
+    };
+    Ok((learner.table().clone(), epochs))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let (table, epochs) = run(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let curve = qlearning::curve_csv(&epochs);
+    let mut files = vec![];
+    match &options.curve {
+        Some(path) => files.push((path, curve)),
+        None => print!("{}", curve),
+    }
+    if let Some(path) = &options.save {
+        files.push((path, table.to_json()));
+    }
+    for (path, contents) in files {
+        if let Err(e) = fs::write(path, contents) {
+            eprintln!("Could not write {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{parse_args, run, Options};
+    use impossible_tic_tac_toe::game::Variant;
+    use impossible_tic_tac_toe::qlearning::{Rewards, Settings};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let actual = parse_args(&args(&[
+            "--learning-rate",
+            "0.1",
+            "--step",
+            "-0.01",
+            "--epochs",
+            "3",
+        ]));
+        let expected = Ok(Options {
+            epochs: 3,
+            settings: Settings {
+                learning_rate: 0.1,
+                rewards: Rewards {
+                    step: -0.01,
+                    ..Rewards::default()
+                },
+                ..Settings::default()
+            },
+            ..Options::default()
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_args__when__bad_value() {
+        let actual = parse_args(&args(&["--discount", "lots"]));
+        let expected = Err("lots is not a value for --discount".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_run__against_agent() {
+        let options = Options {
+            epochs: 2,
+            games: 10,
+            against: "random".to_string(),
+            ..Options::default()
+        };
+
+        let (table, epochs) = run(&options).unwrap();
+
+        assert_eq!(epochs.len(), 2);
+        assert_eq!(epochs[1].games, 20);
+        assert!(table.positions() > 0);
+    }
+
+    #[test]
+    fn test_run__when__board_can_not_be_learned() {
+        let options = Options {
+            variant: Variant::ConnectFour,
+            ..Options::default()
+        };
+
+        let actual = run(&options).err();
+        let expected = Some("only flat square boards can be learned".to_string());
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,52 @@
+use impossible_tic_tac_toe::api::{self, Api};
+
+use std::env;
+use std::process;
+use tiny_http::{Header, Response, Server};
+
+const USAGE: &str = "\
+Serve the game API as JSON over HTTP on localhost.
+
+Usage: server [--port <port>]
+";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let port = match args.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+        [] => 8080,
+        ["--port", port] => port.parse().unwrap_or_else(|_e| exit_with_usage()),
+        _ => exit_with_usage(),
+    };
+
+    let address = format!("127.0.0.1:{}", port);
+    let server = Server::http(&address).unwrap_or_else(|e| {
+        eprintln!("Could not listen on {}: {}", address, e);
+        process::exit(1);
+    });
+    println!("Listening on http://{}", address);
+
+    let json = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let mut api = Api::default();
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_size) => api.handle(request.method().as_str(), request.url(), &body),
+            Err(e) => api::Response {
+                status: 400,
+                body: serde_json::json!({ "error": e.to_string() }).to_string(),
+            },
+        };
+
+        let reply = Response::from_string(response.body)
+            .with_status_code(response.status)
+            .with_header(json.clone());
+        if let Err(e) = request.respond(reply) {
+            eprintln!("Could not answer a request: {}", e);
+        }
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprint!("{}", USAGE);
+    process::exit(2);
+}
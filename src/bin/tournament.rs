@@ -0,0 +1,264 @@
+use impossible_tic_tac_toe::agent::{self, Agent};
+use impossible_tic_tac_toe::game::Variant;
+use impossible_tic_tac_toe::tournament::{self, Tournament};
+
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Play computer players against each other and rate them.
+
+Usage: tournament [options] [<agent> ...]
+
+Every agent plays every other agent, moving first in half of the games.
+Without any, random, mcts, minimax:2 and minimax play. Agents are:
+
+  random                   plays any legal move
+  mcts[:<playouts>]        Monte Carlo tree search (default 1000 playouts)
+  minimax[:<depth>]        alpha-beta search, to the end without a depth
+  evaluator                the classic game's player, on the classic board
+  menace[:<path>]          the matchbox learner, new or as saved by menace
+  qlearning:<path>         a Q-table as saved by qlearn
+  network:<path>           minimax with weights as saved by network train
+
+Options:
+  --variant <name>     classic, qubic, connect-four, 4x4 or 5x5 (default classic)
+  --games <n>          games for every two agents (default 20)
+  --seed <n>           seed for the agents that play randomly (default 1)
+  --json <path>        write the results as JSON
+  --csv <path>         write the result of every match as CSV
+  --help               show this message
+";
+
+const DEFAULT_AGENTS: [&str; 4] = ["random", "mcts", "minimax:2", "minimax"];
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    variant: Variant,
+    games: u32,
+    seed: u64,
+    json: Option<String>,
+    csv: Option<String>,
+    agents: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            variant: Variant::Classic,
+            games: 20,
+            seed: 1,
+            json: None,
+            csv: None,
+            agents: DEFAULT_AGENTS.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut agents = vec![];
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--variant" => {
+                let name = value()?;
+                options.variant = Variant::from_name(&name)
+                    .ok_or_else(|| format!("there is no variant called {}", name))?;
+            }
+            "--games" => {
+                let games = value()?;
+                options.games = games
+                    .parse()
+                    .map_err(|_e| format!("{} is not a number of games", games))?;
+            }
+            "--seed" => {
+                let seed = value()?;
+                options.seed = seed
+                    .parse()
+                    .map_err(|_e| format!("{} is not a seed", seed))?;
+            }
+            "--json" => options.json = Some(value()?),
+            "--csv" => options.csv = Some(value()?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => agents.push(arg.clone()),
+        }
+    }
+
+    if !agents.is_empty() {
+        options.agents = agents;
+    }
+    if options.agents.len() < 2 {
+        return Err("a tournament needs at least two agents".to_string());
+    }
+    Ok(options)
+}
+
+fn render(tournament: &Tournament) -> String {
+    let width = tournament
+        .standings
+        .iter()
+        .map(|s| s.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Agent".len());
+
+    let mut text = String::new();
+    for p in &tournament.pairings {
+        text += &format!(
+            "{:<width$}  vs  {:<width$}  +{} ={} -{}\n",
+            p.first,
+            p.second,
+            p.wins,
+            p.draws,
+            p.losses,
+            width = width
+        );
+    }
+
+    text += &format!(
+        "\n{:<width$}  {:>5} {:>5} {:>5} {:>6}\n",
+        "Agent",
+        "Won",
+        "Drawn",
+        "Lost",
+        "Elo",
+        width = width
+    );
+    for s in &tournament.standings {
+        text += &format!(
+            "{:<width$}  {:>5} {:>5} {:>5} {:>6.0}\n",
+            s.name,
+            s.wins,
+            s.draws,
+            s.losses,
+            s.elo,
+            width = width
+        );
+    }
+    text
+}
+
+fn run(options: &Options) -> Result<Tournament, String> {
+    let config = options.variant.config();
+    let mut agents: Vec<Box<dyn Agent>> = vec![];
+    for (i, spec) in options.agents.iter().enumerate() {
+        let seed = options.seed.wrapping_add(i as u64);
+        agents.push(agent::from_spec(spec, config, seed).map_err(|e| e.to_string())?);
+    }
+
+    Ok(tournament::round_robin(config, &mut agents, options.games))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let options = parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    let tournament = run(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    print!("{}", render(&tournament));
+
+    let files = [
+        (&options.json, tournament.to_json()),
+        (&options.csv, tournament.to_csv()),
+    ];
+    for (path, contents) in files.iter() {
+        if let Some(path) = path {
+            if let Err(e) = fs::write(path, contents) {
+                eprintln!("Could not write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{parse_args, render, run, Options};
+    use impossible_tic_tac_toe::game::Variant;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let actual = parse_args(&args(&[
+            "--games",
+            "4",
+            "--csv",
+            "out.csv",
+            "random",
+            "evaluator",
+        ]));
+        let expected = Ok(Options {
+            games: 4,
+            csv: Some("out.csv".to_string()),
+            agents: vec!["random".to_string(), "evaluator".to_string()],
+            ..Options::default()
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_args__when__one_agent() {
+        let actual = parse_args(&args(&["minimax"]));
+        let expected = Err("a tournament needs at least two agents".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_run__when__agent_can_not_play_variant() {
+        let options = Options {
+            variant: Variant::ConnectFour,
+            agents: vec!["random".to_string(), "evaluator".to_string()],
+            ..Options::default()
+        };
+
+        let actual = run(&options).err();
+        let expected = Some("evaluator can not play this board".to_string());
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_render() {
+        let options = Options {
+            games: 2,
+            agents: vec!["evaluator".to_string(), "minimax".to_string()],
+            ..Options::default()
+        };
+
+        let actual = render(&run(&options).unwrap());
+        let expected = "\
+evaluator  vs  minimax    +0 =2 -0
+
+Agent        Won Drawn  Lost    Elo
+evaluator      0     2     0      0
+minimax        0     2     0      0
+";
+
+        assert_eq!(actual, expected);
+    }
+}
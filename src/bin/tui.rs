@@ -0,0 +1,435 @@
+mod common;
+
+use common::{new_game, parse_args, symbol, OPTIONS};
+use impossible_tic_tac_toe::board::{Cell, CellValue, Outcome};
+use impossible_tic_tac_toe::game::Game;
+use impossible_tic_tac_toe::record;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::env;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "\
+Play tic-tac-toe against the computer in a full-screen terminal.
+
+Usage: tui [options]
+";
+
+const KEYS: &str = "arrows/hjkl move  [ ] layer  enter play  u undo  ? hint  r restart  q quit";
+
+// Columns each cell takes up on screen
+const CELL_WIDTH: u16 = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    LayerUp,
+    LayerDown,
+    Play,
+    Undo,
+    ToggleHint,
+    Restart,
+    Quit,
+}
+
+fn action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Some(Action::Up),
+        KeyCode::Down | KeyCode::Char('j') => Some(Action::Down),
+        KeyCode::Left | KeyCode::Char('h') => Some(Action::Left),
+        KeyCode::Right | KeyCode::Char('l') => Some(Action::Right),
+        KeyCode::PageUp | KeyCode::Char('[') => Some(Action::LayerUp),
+        KeyCode::PageDown | KeyCode::Char(']') => Some(Action::LayerDown),
+        KeyCode::Enter | KeyCode::Char(' ') => Some(Action::Play),
+        KeyCode::Char('u') => Some(Action::Undo),
+        KeyCode::Char('?') => Some(Action::ToggleHint),
+        KeyCode::Char('r') => Some(Action::Restart),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+struct App {
+    game: Game,
+    // Row, column and layer of the cell under the cursor
+    cursor: (u8, u8, u8),
+    show_hint: bool,
+    hint: Option<Cell>,
+    message: String,
+}
+
+impl App {
+    fn new(game: Game) -> App {
+        App {
+            game,
+            cursor: (0, 0, 0),
+            show_hint: false,
+            hint: None,
+            message: String::new(),
+        }
+    }
+
+    fn is_cpu_turn(&self) -> bool {
+        self.game.outcome() == Outcome::InProgress && self.game.is_cpu_turn()
+    }
+
+    fn cpu_play(&mut self) {
+        self.game.cpu_play();
+        self.refresh_hint();
+    }
+
+    /// Carries out a key press, returning false once the player wants to quit
+    fn apply(&mut self, action: Action) -> bool {
+        let config = self.game.board().config();
+        let (row, column, layer) = self.cursor;
+        self.message.clear();
+
+        match action {
+            Action::Up => self.cursor.0 = row.saturating_sub(1),
+            Action::Down => self.cursor.0 = (row + 1).min(config.rows - 1),
+            Action::Left => self.cursor.1 = column.saturating_sub(1),
+            Action::Right => self.cursor.1 = (column + 1).min(config.columns - 1),
+            Action::LayerUp => self.cursor.2 = layer.saturating_sub(1),
+            Action::LayerDown => self.cursor.2 = (layer + 1).min(config.layers - 1),
+            Action::Play => self.play(),
+            Action::Undo => self.undo(),
+            Action::ToggleHint => {
+                self.show_hint = !self.show_hint;
+                self.refresh_hint();
+            }
+            Action::Restart => {
+                self.game.restart();
+                self.refresh_hint();
+            }
+            Action::Quit => return false,
+        }
+        true
+    }
+
+    fn play(&mut self) {
+        if self.game.outcome() != Outcome::InProgress {
+            self.message = "The game is over, press r to play again".to_string();
+            return;
+        }
+
+        let (row, column, layer) = self.cursor;
+        let value = self.game.current_seat().value;
+        let cell = if self.game.board().config().gravity {
+            self.game
+                .board()
+                .legal_moves()
+                .into_iter()
+                .find(|c| (c.column, c.layer) == (column, layer))
+        } else {
+            Some(Cell {
+                row,
+                column,
+                layer,
+                value,
+            })
+        };
+
+        match cell.and_then(|c| self.game.play(Cell { value, ..c })) {
+            Some(_cell) => self.refresh_hint(),
+            None => self.message = "You can not play there".to_string(),
+        }
+    }
+
+    /// Takes back the computer's replies as well, so a person is to move again
+    fn undo(&mut self) {
+        if self.game.history().is_empty() {
+            self.message = "There is nothing to undo".to_string();
+            return;
+        }
+        while self.game.undo().is_some() {
+            if !self.game.current_seat().is_cpu() {
+                break;
+            }
+        }
+        self.refresh_hint();
+    }
+
+    fn refresh_hint(&mut self) {
+        self.hint = if self.show_hint && !self.is_cpu_turn() {
+            self.game.hint()
+        } else {
+            None
+        };
+    }
+
+    fn status(&self) -> String {
+        let seat = self.game.current_seat();
+        match self.game.outcome() {
+            Outcome::InProgress if seat.is_cpu() => "The computer is thinking...".to_string(),
+            Outcome::InProgress => format!("{} to play", symbol(seat.value)),
+            Outcome::Draw => "It's a draw".to_string(),
+            Outcome::Winner(winner) if self.game.is_human_winner() => {
+                format!("{} wins, well played!", symbol(winner))
+            }
+            Outcome::Winner(winner) => format!("{} wins, the computer takes it", symbol(winner)),
+        }
+    }
+}
+
+/// Draws the board with the cursor, the winning line and the hint marked, and
+/// the moves so far in a pane on the right
+fn draw<W: Write>(app: &App, out: &mut W) -> io::Result<()> {
+    let board = app.game.board();
+    let config = board.config();
+    let values = board.values();
+    let winning_line = board.winning_line().unwrap_or(&[]);
+    let hint_index = app
+        .hint
+        .as_ref()
+        .and_then(|c| config.index(c.row, c.column, c.layer));
+
+    queue!(
+        out,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print("Impossible tic-tac-toe")
+    )?;
+
+    let mut y = 2;
+    for layer in 0..config.layers {
+        if config.layers > 1 {
+            queue!(out, MoveTo(0, y), Print(format!("Layer {}", layer + 1)))?;
+            y += 1;
+        }
+        for column in 0..config.columns {
+            let letters = record::format_column(column);
+            let x = 3 + column as u16 * CELL_WIDTH;
+            queue!(out, MoveTo(x + 1, y), Print(letters))?;
+        }
+        y += 1;
+
+        for row in 0..config.rows {
+            queue!(
+                out,
+                MoveTo(0, y),
+                Print(format!("{:>2}", config.rows - row))
+            )?;
+            for column in 0..config.columns {
+                let index = config.index(row, column, layer).unwrap();
+                let is_cursor = app.cursor == (row, column, layer);
+                let is_hint = hint_index == Some(index);
+                let mark = if is_hint && values[index] == CellValue::Empty {
+                    '+'
+                } else {
+                    symbol(values[index])
+                };
+
+                queue!(out, MoveTo(3 + column as u16 * CELL_WIDTH, y))?;
+                if winning_line.contains(&index) {
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::Green),
+                        SetAttribute(Attribute::Bold)
+                    )?;
+                } else if is_hint {
+                    queue!(out, SetForegroundColor(Color::Yellow))?;
+                }
+                if is_cursor {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                queue!(
+                    out,
+                    Print(format!(" {} ", mark)),
+                    SetAttribute(Attribute::Reset),
+                    ResetColor
+                )?;
+            }
+            y += 1;
+        }
+        y += 1;
+    }
+
+    queue!(out, MoveTo(0, y), Print(app.status()))?;
+    if !app.message.is_empty() {
+        queue!(out, MoveTo(0, y + 1), Print(&app.message))?;
+    }
+    queue!(out, MoveTo(0, y + 3), Print(KEYS))?;
+
+    let pane_x = 6 + config.columns as u16 * CELL_WIDTH;
+    queue!(out, MoveTo(pane_x, 2), Print("Moves"))?;
+    let (_columns, screen_rows) = terminal::size().unwrap_or((80, 24));
+    let shown = screen_rows.saturating_sub(4).max(1) as usize;
+    let history = app.game.history();
+    let first = history.len().saturating_sub(shown);
+    for (i, cell) in history.iter().enumerate().skip(first) {
+        let line = format!(
+            "{:>3}. {} {}",
+            i + 1,
+            symbol(cell.value),
+            record::format_coordinate(config, cell)
+        );
+        queue!(out, MoveTo(pane_x, 3 + (i - first) as u16), Print(line))?;
+    }
+
+    out.flush()
+}
+
+fn run<W: Write>(mut app: App, out: &mut W) -> io::Result<()> {
+    loop {
+        draw(&app, out)?;
+        if app.is_cpu_turn() {
+            app.cpu_play();
+            continue;
+        }
+
+        if let Event::Key(key) = event::read()? {
+            let action = action(key).filter(|_a| key.kind == KeyEventKind::Press);
+            if let Some(action) = action {
+                if !app.apply(action) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}\n{}", USAGE, OPTIONS);
+        return;
+    }
+    let game = match parse_args(&args).and_then(|options| new_game(&options)) {
+        Ok(game) => game,
+        Err(e) => {
+            eprintln!("{}\n\n{}\n{}", e, USAGE, OPTIONS);
+            process::exit(2);
+        }
+    };
+
+    let mut stdout = io::stdout();
+    let result = terminal::enable_raw_mode()
+        .and_then(|_| execute!(stdout, EnterAlternateScreen, Hide))
+        .and_then(|_| run(App::new(game), &mut stdout));
+    // Hand the terminal back even when drawing failed
+    let _ = execute!(stdout, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use super::{action, draw, Action, App};
+    use impossible_tic_tac_toe::board::CellValue::{Human, CPU};
+    use impossible_tic_tac_toe::game::{Game, Variant};
+
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn app_after(actions: &[Action]) -> App {
+        let mut app = App::new(Game::new());
+        for &action in actions {
+            app.apply(action);
+        }
+        app
+    }
+
+    #[test]
+    fn test_action__when__vim_keys() {
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        let actual: Vec<Option<Action>> = "hjklx".chars().map(|c| action(key(c))).collect();
+        let expected = vec![
+            Some(Action::Left),
+            Some(Action::Down),
+            Some(Action::Up),
+            Some(Action::Right),
+            None,
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_apply__cursor_stays_on_board() {
+        let app = app_after(&[
+            Action::Up,
+            Action::Left,
+            Action::Right,
+            Action::Right,
+            Action::Right,
+        ]);
+
+        let actual = app.cursor;
+        let expected = (0, 2, 0);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_apply__play_on_cursor() {
+        let app = app_after(&[Action::Down, Action::Right, Action::Play]);
+
+        let actual = app.game.board().values()[4];
+        let expected = Human;
+
+        assert_eq!(actual, expected);
+        assert!(app.is_cpu_turn());
+    }
+
+    #[test]
+    fn test_apply__when__gravity_drops_in_column() {
+        let mut app = App::new(Game::with_variant(Variant::ConnectFour));
+        app.apply(Action::Right);
+        app.apply(Action::Play);
+
+        let actual = app.game.history().last().map(|c| (c.row, c.column));
+        let expected = Some((5, 1));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_apply__undo_takes_back_cpu_reply() {
+        let mut app = app_after(&[Action::Play]);
+        app.cpu_play();
+        assert_eq!(app.game.history().last().map(|c| c.value), Some(CPU));
+
+        app.apply(Action::Undo);
+
+        assert!(app.game.history().is_empty());
+        assert!(!app.is_cpu_turn());
+    }
+
+    #[test]
+    fn test_apply__hint_follows_position() {
+        let mut app = App::new(Game::from_notation("xx./oo./... x").unwrap());
+        app.apply(Action::ToggleHint);
+
+        let actual = app.hint.as_ref().map(|c| (c.row, c.column));
+        let expected = Some((0, 2));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_draw__lists_moves() {
+        let mut app = app_after(&[Action::Play]);
+        app.cpu_play();
+        let mut screen = vec![];
+
+        draw(&app, &mut screen).unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+
+        assert!(screen.contains("1. X a3"));
+        assert!(screen.contains("2. O "));
+    }
+}
@@ -1,16 +1,107 @@
-use crate::board::CellValue::{Empty, CPU};
+use crate::board::CellValue::{Empty, Human, CPU};
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+// The most cells a board may have, so that a position or message from
+// elsewhere can not ask for a huge board
+pub const MAX_CELLS: usize = 1024;
+
+/// A symbol on the board. In most variants the symbol tells who played it,
+/// but in some either player may play either symbol.
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Mark {
+    X,
+    O,
+}
 
-#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+impl Mark {
+    pub fn other(self) -> Mark {
+        match self {
+            Mark::X => Mark::O,
+            Mark::O => Mark::X,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum CellValue {
-    Empty = 0,
-    CPU = 1,
-    Human = 2,
+    Empty,
+    CPU,
+    Human,
+    /// A symbol that does not belong to any player
+    Mark(Mark),
+    Number(u8),
+    /// A seat in games with more than two players
+    Player(u8),
+    /// A cell no one can play in
+    Blocked,
+}
+
+impl CellValue {
+    pub fn opponent(self) -> CellValue {
+        match self {
+            CPU => Human,
+            Human => CPU,
+            Empty => Empty,
+            CellValue::Mark(mark) => CellValue::Mark(mark.other()),
+            CellValue::Number(number) => CellValue::Number(number),
+            // There is no single opponent with more than two players
+            CellValue::Player(seat) => CellValue::Player(seat),
+            CellValue::Blocked => CellValue::Blocked,
+        }
+    }
+
+    /// Value of a cell in the compact form used from JavaScript: 0 is empty,
+    /// 1 is the CPU, 2 is the human and 3 is blocked
+    pub fn from_code(code: u8) -> Option<CellValue> {
+        match code {
+            0 => Some(Empty),
+            1 => Some(CPU),
+            2 => Some(Human),
+            3 => Some(CellValue::Blocked),
+            _ => None,
+        }
+    }
+}
+
+/// Why a position can not be played from
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PositionError {
+    WrongSize,
+    UnsupportedValue,
+    PieceCounts,
+    MultipleWinners,
+    Floating,
+    Unreachable,
+    BadConfig,
 }
 
-#[derive(Serialize, Debug, Clone)]
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            PositionError::WrongSize => "the position does not fit the board",
+            PositionError::UnsupportedValue => "the position has a value that can not be used",
+            PositionError::PieceCounts => {
+                "the human has to have as many marks as the CPU or one more"
+            }
+            PositionError::MultipleWinners => "both players have a line",
+            PositionError::Floating => "a mark is floating above an empty cell",
+            PositionError::Unreachable => "the position can not come up in a game",
+            PositionError::BadConfig => "a game can not be played on the board",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Cell {
     pub row: u8,
     pub column: u8,
+    pub layer: u8,
     pub value: CellValue,
 }
 
@@ -26,28 +117,351 @@ impl Cell {
 
 impl PartialEq for Cell {
     fn eq(&self, other: &Self) -> bool {
-        (self.row, self.column, self.value) == (other.row, other.column, other.value)
+        (self.row, self.column, self.layer, self.value)
+            == (other.row, other.column, other.layer, other.value)
     }
 }
 
+/// How lines run at the edges of a board
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Topology {
+    /// Lines stop at the edges
+    Flat,
+    /// Lines that leave one edge of a layer come back in on the opposite edge
+    Torus,
+}
+
+/// Dimensions of a board and how many marks in a row are needed to win.
+/// A board with more than one layer is played in three dimensions. With
+/// gravity marks fall to the lowest empty row of their column.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct BoardConfig {
+    pub rows: u8,
+    pub columns: u8,
+    pub layers: u8,
+    pub win_length: u8,
+    pub gravity: bool,
+    pub topology: Topology,
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        BoardConfig {
+            rows: 3,
+            columns: 3,
+            layers: 1,
+            win_length: 3,
+            gravity: false,
+            topology: Topology::Flat,
+        }
+    }
+}
+
+impl BoardConfig {
+    /// 4x4x4 cube where four in a row wins, also known as Qubic
+    pub fn qubic() -> Self {
+        BoardConfig {
+            rows: 4,
+            columns: 4,
+            layers: 4,
+            win_length: 4,
+            gravity: false,
+            topology: Topology::Flat,
+        }
+    }
+
+    /// Square board for games with more than two players
+    pub fn square(size: u8, win_length: u8) -> Self {
+        BoardConfig {
+            rows: size,
+            columns: size,
+            layers: 1,
+            win_length,
+            gravity: false,
+            topology: Topology::Flat,
+        }
+    }
+
+    /// 7 columns and 6 rows with gravity where four in a row wins
+    pub fn connect_four() -> Self {
+        BoardConfig {
+            rows: 6,
+            columns: 7,
+            layers: 1,
+            win_length: 4,
+            gravity: true,
+            topology: Topology::Flat,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.rows as usize * self.columns as usize * self.layers as usize
+    }
+
+    /// Whether a game can be played on the board: it has cells but no more
+    /// than `MAX_CELLS`, and a line of `win_length` fits along its longest
+    /// side
+    pub fn is_valid(&self) -> bool {
+        let longest_side = self.rows.max(self.columns).max(self.layers);
+        self.size() > 0
+            && self.size() <= MAX_CELLS
+            && self.win_length > 0
+            && self.win_length <= longest_side
+    }
+
+    /// Position of a cell in the list of cells, which is ordered by layer,
+    /// then row, then column
+    pub fn index(&self, row: u8, column: u8, layer: u8) -> Option<usize> {
+        if row >= self.rows || column >= self.columns || layer >= self.layers {
+            return None;
+        }
+
+        let (row, column, layer) = (row as usize, column as usize, layer as usize);
+        Some((layer * self.rows as usize + row) * self.columns as usize + column)
+    }
+
+    pub fn coordinates(&self, index: usize) -> (u8, u8, u8) {
+        let columns = self.columns as usize;
+        let rows = self.rows as usize;
+        let column = index % columns;
+        let row = (index / columns) % rows;
+        let layer = index / (columns * rows);
+        (row as u8, column as u8, layer as u8)
+    }
+
+    /// Whether a mark can be put at `index`. With gravity a mark can only be
+    /// put on the bottom row or on top of another mark.
+    pub fn is_playable(&self, values: &[CellValue], index: usize) -> bool {
+        if values[index] != Empty {
+            return false;
+        }
+        if !self.gravity {
+            return true;
+        }
+
+        let (row, _column, _layer) = self.coordinates(index);
+        let is_bottom_row = row + 1 == self.rows;
+        is_bottom_row || values[index + self.columns as usize] != Empty
+    }
+
+    pub fn with_topology(self, topology: Topology) -> Self {
+        BoardConfig { topology, ..self }
+    }
+
+    /// Every line of `win_length` cells, as indexes into the list of cells.
+    /// On a torus a line is only listed once however many cells it can be
+    /// started from, and lines that would cross a cell twice are left out.
+    pub fn win_lines(&self) -> Vec<Vec<usize>> {
+        // Only keep one of each pair of opposite directions
+        let directions: Vec<(i16, i16, i16)> = (-1..=1)
+            .flat_map(|l| (-1..=1).flat_map(move |r| (-1..=1).map(move |c| (l, r, c))))
+            .filter(|&(l, r, c)| (l, r, c) > (0, 0, 0))
+            .collect();
+
+        if self.win_length == 0 {
+            return vec![];
+        }
+
+        let length = self.win_length as i16;
+        let mut lines = vec![];
+        let mut seen = HashSet::new();
+        for layer in 0..self.layers as i16 {
+            for row in 0..self.rows as i16 {
+                for column in 0..self.columns as i16 {
+                    for (dl, dr, dc) in directions.iter() {
+                        let line: Option<Vec<usize>> = (0..length)
+                            .map(|step| {
+                                self.index_of(
+                                    row + dr * step,
+                                    column + dc * step,
+                                    layer + dl * step,
+                                )
+                            })
+                            .collect();
+                        if let Some(line) = line {
+                            let mut cells = line.clone();
+                            cells.sort_unstable();
+                            cells.dedup();
+                            if cells.len() == line.len() && seen.insert(cells) {
+                                lines.push(line);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        lines
+    }
+
+    fn index_of(&self, row: i16, column: i16, layer: i16) -> Option<usize> {
+        let (row, column) = match self.topology {
+            Topology::Flat => (row, column),
+            Topology::Torus => (
+                row.rem_euclid(self.rows as i16),
+                column.rem_euclid(self.columns as i16),
+            ),
+        };
+        if row < 0 || column < 0 || layer < 0 || row > 255 || column > 255 || layer > 255 {
+            return None;
+        }
+        self.index(row as u8, column as u8, layer as u8)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    InProgress,
+    Draw,
+    Winner(CellValue),
+}
+
+/// A mark is either put on an empty cell, or taken off one cell and put on
+/// another in variants where the players run out of marks
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum Move {
+    Place(Cell),
+    Step { from: Cell, to: Cell },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(into = "BoardState", try_from = "BoardState")]
 pub struct Board {
     cells: Vec<Cell>,
+    config: BoardConfig,
+    lines: Vec<Vec<usize>>,
+}
+
+/// What is saved of a board, the lines are worked out again from the config
+#[derive(Serialize, Deserialize)]
+struct BoardState {
+    config: BoardConfig,
+    cells: Vec<Cell>,
+}
+
+impl From<Board> for BoardState {
+    fn from(board: Board) -> Self {
+        BoardState {
+            config: board.config,
+            cells: board.cells,
+        }
+    }
+}
+
+impl TryFrom<BoardState> for Board {
+    type Error = PositionError;
+
+    fn try_from(state: BoardState) -> Result<Self, Self::Error> {
+        let config = state.config;
+        if !config.is_valid() {
+            return Err(PositionError::BadConfig);
+        }
+        let is_in_order = state.cells.len() == config.size()
+            && state
+                .cells
+                .iter()
+                .enumerate()
+                .all(|(i, c)| config.coordinates(i) == (c.row, c.column, c.layer));
+        if !is_in_order {
+            return Err(PositionError::WrongSize);
+        }
+
+        let mut board = Board::new(config);
+        board.set_all(state.cells);
+        Ok(board)
+    }
 }
 
 impl Default for Board {
     fn default() -> Self {
+        Board::new(BoardConfig::default())
+    }
+}
+
+impl Board {
+    pub fn new(config: BoardConfig) -> Self {
         let mut cells = vec![];
-        for row in 0..=2 {
-            for column in 0..=2 {
-                cells.push(Board::empty_cell(row, column))
+        for layer in 0..config.layers {
+            for row in 0..config.rows {
+                for column in 0..config.columns {
+                    cells.push(Board::empty_cell(row, column, layer))
+                }
             }
         }
 
-        Board { cells }
+        Board {
+            cells,
+            config,
+            lines: config.win_lines(),
+        }
+    }
+
+    /// A board with the given value in every position, in the order of
+    /// `BoardConfig::index`. The human is taken to have moved first, and the
+    /// position has to be one that a game between the two could reach.
+    pub fn from_position(
+        config: BoardConfig,
+        values: Vec<CellValue>,
+    ) -> Result<Board, PositionError> {
+        if values.len() != config.size() {
+            return Err(PositionError::WrongSize);
+        }
+        let is_supported = |v: &CellValue| matches!(v, Empty | Human | CPU | CellValue::Blocked);
+        if !values.iter().all(is_supported) {
+            return Err(PositionError::UnsupportedValue);
+        }
+
+        let count = |value| values.iter().filter(|v| **v == value).count();
+        let (humans, cpus) = (count(Human), count(CPU));
+        if humans != cpus && humans != cpus + 1 {
+            return Err(PositionError::PieceCounts);
+        }
+
+        let has_support = |i: usize| {
+            let (row, _column, _layer) = config.coordinates(i);
+            row + 1 == config.rows || values[i + config.columns as usize] != Empty
+        };
+        if config.gravity && !(0..values.len()).all(|i| values[i] == Empty || has_support(i)) {
+            return Err(PositionError::Floating);
+        }
+
+        let mut board = Board::new(config);
+        for (i, value) in values.iter().enumerate() {
+            let (row, column, layer) = config.coordinates(i);
+            board.cells[i] = Cell {
+                row,
+                column,
+                layer,
+                value: *value,
+            };
+        }
+
+        let winners: HashSet<CellValue> = board
+            .winning_lines()
+            .map(|(winner, _line)| winner)
+            .collect();
+        if winners.len() > 1 {
+            return Err(PositionError::MultipleWinners);
+        }
+        if let Some(&winner) = winners.iter().next() {
+            // The winner made the last move, and every line they have goes
+            // through the mark of that move
+            let moved_last = if humans > cpus { Human } else { CPU };
+            let last_move_is_shared = (0..values.len()).any(|i| {
+                values[i] == winner
+                    && board
+                        .winning_lines()
+                        .all(|(_winner, line)| line.contains(&i))
+            });
+            if winner != moved_last || !last_move_is_shared {
+                return Err(PositionError::Unreachable);
+            }
+        }
+
+        Ok(board)
     }
-}
 
-impl Board {
     pub fn clear(&mut self) {
         let cleared_cells = self
             .cells
@@ -65,87 +479,103 @@ impl Board {
         self.cells.clone()
     }
 
-    pub fn is_cpu_winner(&self) -> bool {
-        // Check row wins
-        for row in 0..=2 {
-            let cpu_row_cells = self
-                .cells
-                .clone()
-                .into_iter()
-                .filter(|c| (c.row, c.value) == (row, CPU))
-                .collect::<Vec<Cell>>();
-
-            let cpu_won_row = cpu_row_cells.len() == 3;
-            if cpu_won_row {
-                return true;
-            }
-        }
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
 
-        // Check column wins
-        for column in 0..=2 {
-            let cpu_column_cells = self
-                .cells
-                .clone()
-                .into_iter()
-                .filter(|c| (c.column, c.value) == (column, CPU))
-                .collect::<Vec<Cell>>();
-
-            let cpu_won_column = cpu_column_cells.len() == 3;
-            if cpu_won_column {
-                return true;
+    pub fn win_lines(&self) -> &[Vec<usize>] {
+        &self.lines
+    }
+
+    /// Value of every position on the board, in the order of
+    /// `BoardConfig::index`
+    pub fn values(&self) -> Vec<CellValue> {
+        let mut values = vec![Empty; self.config.size()];
+        for c in self.cells.iter() {
+            if let Some(index) = self.config.index(c.row, c.column, c.layer) {
+                values[index] = c.value;
             }
         }
+        values
+    }
 
-        // Check diagonal wins
-        let diagonal_win: Vec<Cell> = vec![(0, 0), (1, 1), (2, 2)]
-            .into_iter()
-            .map(|(row, column)| Cell {
-                row,
-                column,
-                value: CPU,
-            })
-            .collect();
+    pub fn winner(&self) -> Option<CellValue> {
+        self.winning_lines().next().map(|(winner, _line)| winner)
+    }
 
-        let cpu_diagonal_win: Vec<Cell> = self
-            .cells
-            .clone()
-            .into_iter()
-            .filter(|c| diagonal_win.contains(c))
-            .collect();
+    /// Indexes of the cells in a completed line, if there is one
+    pub fn winning_line(&self) -> Option<&[usize]> {
+        self.winning_lines()
+            .next()
+            .map(|(_winner, line)| line.as_slice())
+    }
 
-        let cpu_won_diagonal = cpu_diagonal_win.len() == 3;
-        if cpu_won_diagonal {
-            return true;
+    fn winning_lines(&self) -> impl Iterator<Item = (CellValue, &Vec<usize>)> {
+        let values = self.values();
+        self.lines.iter().filter_map(move |line| {
+            let first = values[line[0]];
+            let is_mark = first != Empty && first != CellValue::Blocked;
+            let is_complete_line = is_mark && line.iter().all(|&i| values[i] == first);
+            is_complete_line.then_some((first, line))
+        })
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        if let Some(winner) = self.winner() {
+            return Outcome::Winner(winner);
         }
 
-        let diagonal_win_2: Vec<Cell> = vec![(0, 2), (1, 1), (2, 0)]
-            .into_iter()
-            .map(|(row, column)| Cell {
-                row,
-                column,
-                value: CPU,
+        if Board::is_moves_left(self.get_cells()) {
+            Outcome::InProgress
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    /// Empty cells a mark can be put in under the rules of the board
+    pub fn legal_moves(&self) -> Vec<Cell> {
+        let values = self.values();
+        (0..values.len())
+            .filter(|&i| self.config.is_playable(&values, i))
+            .map(|i| {
+                let (row, column, layer) = self.config.coordinates(i);
+                Board::empty_cell(row, column, layer)
             })
-            .collect();
+            .collect()
+    }
 
-        let cpu_diagonal_win_2: Vec<Cell> = self
-            .cells
-            .clone()
+    /// Puts a mark in the lowest empty row of a column, on a board with
+    /// gravity. Returns `None` on a board without gravity or when the column
+    /// is full.
+    pub fn drop_in_column(&mut self, column: u8, value: CellValue) -> Option<Cell> {
+        if !self.config.gravity {
+            return None;
+        }
+        let cell = self
+            .legal_moves()
             .into_iter()
-            .filter(|c| diagonal_win_2.contains(c))
-            .collect();
+            .find(|c| (c.column, c.layer) == (column, 0))
+            .map(|c| Cell { value, ..c })?;
 
-        let cpu_won_diagonal_2 = cpu_diagonal_win_2.len() == 3;
-        if cpu_won_diagonal_2 {
-            return true;
-        }
+        self.set(cell.clone()).map(|_cells| cell)
+    }
 
-        false
+    pub fn is_cpu_winner(&self) -> bool {
+        self.winner() == Some(CPU)
+    }
+
+    pub fn is_human_winner(&self) -> bool {
+        self.winner() == Some(Human)
     }
 
     pub fn get_cell(cells: Vec<Cell>, row: u8, column: u8) -> Option<Cell> {
+        Board::get_cell_in_layer(cells, row, column, 0)
+    }
+
+    pub fn get_cell_in_layer(cells: Vec<Cell>, row: u8, column: u8, layer: u8) -> Option<Cell> {
         if let Some(index) = cells
             .iter()
-            .position(|c| (c.row, c.column) == (row, column))
+            .position(|c| (c.row, c.column, c.layer) == (row, column, layer))
         {
             return Some(cells.clone().get(index).unwrap().to_owned());
         }
@@ -161,6 +591,7 @@ impl Board {
         self.set(Cell {
             row,
             column,
+            layer: 0,
             value: CellValue::Human,
         })
     }
@@ -169,6 +600,7 @@ impl Board {
         self.set(Cell {
             row,
             column,
+            layer: 0,
             value: CellValue::CPU,
         })
     }
@@ -176,7 +608,7 @@ impl Board {
     pub fn set_on(mut cells: Vec<Cell>, cell: Cell) -> Option<Vec<Cell>> {
         if let Some(index) = cells
             .iter()
-            .position(|c| (c.row, c.column) == (cell.row, cell.column))
+            .position(|c| (c.row, c.column, c.layer) == (cell.row, cell.column, cell.layer))
         {
             let old_cell = cells.get(index).unwrap();
             if old_cell.is_empty() {
@@ -188,11 +620,60 @@ impl Board {
         None
     }
 
+    pub fn apply_move(&mut self, mv: Move) -> Option<Vec<Cell>> {
+        match mv {
+            Move::Place(cell) => self.set(cell),
+            Move::Step { from, to } => {
+                let is_own_mark = from.value != Empty
+                    && from.value == to.value
+                    && Board::get_cell_in_layer(
+                        self.get_cells(),
+                        from.row,
+                        from.column,
+                        from.layer,
+                    ) == Some(from.clone());
+                if !is_own_mark {
+                    return None;
+                }
+
+                let lifted = Board::empty_cell(from.row, from.column, from.layer);
+                let cells = self
+                    .cells
+                    .clone()
+                    .into_iter()
+                    .map(|c| if c == from { lifted.clone() } else { c })
+                    .collect();
+
+                let maybe_set = Board::set_on(cells, to);
+                if let Some(cells) = maybe_set.clone() {
+                    self.set_all(cells)
+                }
+                maybe_set
+            }
+        }
+    }
+
     pub fn is_moves_left(cells: Vec<Cell>) -> bool {
         cells.into_iter().any(|c| c.value == Empty)
     }
 
-    fn set(&mut self, cell: Cell) -> Option<Vec<Cell>> {
+    /// Takes the mark off a cell and returns it
+    pub fn remove(&mut self, row: u8, column: u8, layer: u8) -> Option<Cell> {
+        let index = self.config.index(row, column, layer)?;
+        let empty = Board::empty_cell(row, column, layer);
+        let removed = std::mem::replace(&mut self.cells[index], empty);
+        removed.is_not_empty().then_some(removed)
+    }
+
+    pub fn set(&mut self, cell: Cell) -> Option<Vec<Cell>> {
+        let is_legal = self
+            .legal_moves()
+            .iter()
+            .any(|c| (c.row, c.column, c.layer) == (cell.row, cell.column, cell.layer));
+        if !is_legal {
+            return None;
+        }
+
         let maybe_set = Board::set_on(self.cells.clone(), cell);
         // Replace if available
         if let Some(cells) = maybe_set.clone() {
@@ -201,10 +682,11 @@ impl Board {
         maybe_set
     }
 
-    fn empty_cell(row: u8, column: u8) -> Cell {
+    fn empty_cell(row: u8, column: u8, layer: u8) -> Cell {
         Cell {
             row,
             column,
+            layer,
             value: Empty,
         }
     }
@@ -214,8 +696,10 @@ impl Board {
 mod tests {
     #![allow(non_snake_case)]
 
-    use crate::board::CellValue::{Empty, Human};
-    use crate::board::{Board, Cell, CellValue};
+    use crate::board::CellValue::{Empty, Human, CPU};
+    use crate::board::{
+        Board, BoardConfig, Cell, CellValue, Mark, Move, Outcome, PositionError, Topology,
+    };
 
     #[test]
     fn test_new_board__creates_cells() {
@@ -237,6 +721,7 @@ mod tests {
         let expected = Some(Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: Empty,
         });
 
@@ -259,6 +744,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
@@ -276,6 +762,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
@@ -284,6 +771,7 @@ mod tests {
         cells.push(Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: Empty,
         });
 
@@ -303,6 +791,7 @@ mod tests {
         let cells: Vec<Cell> = vec![Cell {
             row: 100,
             column: 100,
+            layer: 0,
             value: CellValue::Human,
         }];
         board.set_all(cells);
@@ -320,6 +809,7 @@ mod tests {
         let before_set_cell = Some(Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Empty,
         });
         assert_eq!(before_set, before_set_cell);
@@ -327,6 +817,7 @@ mod tests {
         let human_cell = Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Human,
         };
         let set_result = board.set(human_cell);
@@ -338,6 +829,7 @@ mod tests {
         let expected = Some(Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Human,
         });
 
@@ -350,6 +842,7 @@ mod tests {
         let cell = Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Human,
         };
         let set_result = board.set(cell);
@@ -359,6 +852,7 @@ mod tests {
         let cell_after_set_expected = Some(Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Human,
         });
         assert_eq!(cell_after_set, cell_after_set_expected);
@@ -366,6 +860,7 @@ mod tests {
         let cell2 = Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CellValue::Human,
         };
         let actual = board.set(cell2);
@@ -380,6 +875,7 @@ mod tests {
         let cell = Cell {
             row: 100,
             column: 100,
+            layer: 0,
             value: CellValue::Human,
         };
 
@@ -440,4 +936,457 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_win_lines__when__classic() {
+        let actual = BoardConfig::default().win_lines().len();
+        let expected = 8;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_win_lines__when__win_length_zero() {
+        let config = BoardConfig::square(3, 0);
+
+        assert!(config.win_lines().is_empty());
+        assert!(!config.is_valid());
+        assert_eq!(Board::new(config).outcome(), Outcome::InProgress);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let actual = [
+            BoardConfig::default(),
+            BoardConfig::qubic(),
+            BoardConfig::square(3, 4),
+            BoardConfig::square(0, 1),
+            BoardConfig::square(40, 5),
+        ]
+        .iter()
+        .map(|config| config.is_valid())
+        .collect::<Vec<bool>>();
+        let expected = vec![true, true, false, false, false];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_win_lines__when__qubic() {
+        let actual = BoardConfig::qubic().win_lines().len();
+        let expected = 76;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_win_lines__when__torus() {
+        let config = BoardConfig::square(4, 4).with_topology(Topology::Torus);
+
+        let actual = config.win_lines().len();
+        let expected = 16;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_win_lines__when__torus_shorter_than_board() {
+        let config = BoardConfig::square(5, 3).with_topology(Topology::Torus);
+
+        let actual = config.win_lines().len();
+        let expected = 100;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__torus_diagonal_wraps() {
+        let mut board = Board::new(BoardConfig::square(5, 3).with_topology(Topology::Torus));
+        for (row, column) in [(3, 4), (4, 0), (0, 1)] {
+            board.set_human(row, column);
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::Winner(Human);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__flat_diagonal_would_wrap() {
+        let mut board = Board::new(BoardConfig::square(5, 3));
+        for (row, column) in [(3, 4), (4, 0), (0, 1)] {
+            board.set_human(row, column);
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::InProgress;
+
+        assert_eq!(actual, expected);
+    }
+
+    fn position(codes: &[u8]) -> Vec<CellValue> {
+        codes
+            .iter()
+            .map(|&code| CellValue::from_code(code).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_from_position__when__valid_with_blocked_cells() {
+        let values = position(&[2, 1, 0, 3, 2, 0, 0, 0, 3]);
+
+        let board = Board::from_position(BoardConfig::default(), values.clone()).unwrap();
+
+        assert_eq!(board.values(), values);
+        assert_eq!(board.legal_moves().len(), 4);
+    }
+
+    #[test]
+    fn test_from_position__when__wrong_size() {
+        let actual = Board::from_position(BoardConfig::default(), position(&[0, 0, 0])).err();
+        let expected = Some(PositionError::WrongSize);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_position__when__cpu_has_more_marks() {
+        let values = position(&[1, 1, 0, 0, 2, 0, 0, 0, 0]);
+
+        let actual = Board::from_position(BoardConfig::default(), values).err();
+        let expected = Some(PositionError::PieceCounts);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_position__when__both_players_have_a_line() {
+        let values = position(&[2, 2, 2, 1, 1, 1, 0, 0, 0]);
+
+        let actual = Board::from_position(BoardConfig::default(), values).err();
+        let expected = Some(PositionError::MultipleWinners);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_position__when__winner_did_not_move_last() {
+        let values = position(&[2, 2, 2, 1, 1, 0, 1, 0, 0]);
+
+        let actual = Board::from_position(BoardConfig::default(), values).err();
+        let expected = Some(PositionError::Unreachable);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_position__when__two_separate_lines() {
+        let config = BoardConfig::square(4, 3);
+        let values = position(&[2, 2, 2, 1, 1, 0, 1, 0, 2, 2, 2, 1, 1, 0, 0, 0]);
+
+        let actual = Board::from_position(config, values).err();
+        let expected = Some(PositionError::Unreachable);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_position__when__floating_mark() {
+        let mut values = vec![Empty; BoardConfig::connect_four().size()];
+        values[0] = Human;
+
+        let actual = Board::from_position(BoardConfig::connect_four(), values).err();
+        let expected = Some(PositionError::Floating);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_winner__when__line_of_blocked_cells() {
+        let values = position(&[3, 3, 3, 0, 0, 0, 0, 0, 0]);
+        let board = Board::from_position(BoardConfig::default(), values).unwrap();
+
+        let actual = board.winner();
+        let expected = None;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_winning_line__when__diagonal() {
+        let values = position(&[2, 1, 1, 0, 2, 0, 0, 0, 2]);
+        let board = Board::from_position(BoardConfig::default(), values).unwrap();
+
+        let actual = board.winning_line();
+        let expected = Some(&[0, 4, 8][..]);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_coordinates__is_inverse_of_index() {
+        let config = BoardConfig::qubic();
+
+        let actual = config.coordinates(config.index(2, 3, 1).unwrap());
+        let expected = (2, 3, 1);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__qubic_space_diagonal_win() {
+        let mut board = Board::new(BoardConfig::qubic());
+        for i in 0..=3 {
+            board.set(Cell {
+                row: i,
+                column: 3 - i,
+                layer: i,
+                value: Human,
+            });
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::Winner(Human);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__draw() {
+        let mut board = Board::default();
+        for (row, column) in [(0, 0), (0, 2), (1, 0), (1, 2), (2, 1)] {
+            board.set_cpu(row, column);
+        }
+        for (row, column) in [(0, 1), (1, 1), (2, 0), (2, 2)] {
+            board.set_human(row, column);
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::Draw;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__in_progress() {
+        let mut board = Board::default();
+        board.set_cpu(1, 1);
+
+        let actual = board.outcome();
+        let expected = Outcome::InProgress;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__third_player_wins() {
+        let mut board = Board::new(BoardConfig::square(4, 3));
+        for (row, column, seat) in [
+            (0, 0, 0),
+            (0, 1, 1),
+            (1, 1, 2),
+            (1, 0, 0),
+            (2, 2, 2),
+            (3, 3, 2),
+        ] {
+            board.set(Cell {
+                row,
+                column,
+                layer: 0,
+                value: CellValue::Player(seat),
+            });
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::Winner(CellValue::Player(2));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_legal_moves__when__gravity() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        board.drop_in_column(3, Human);
+
+        let actual: Vec<(u8, u8)> = board
+            .legal_moves()
+            .into_iter()
+            .map(|c| (c.row, c.column))
+            .collect();
+        let expected = vec![(4, 3), (5, 0), (5, 1), (5, 2), (5, 4), (5, 5), (5, 6)];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_set__when__gravity_and_floating() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        let cell = Cell {
+            row: 0,
+            column: 0,
+            layer: 0,
+            value: Human,
+        };
+
+        let actual = board.set(cell);
+        let expected = None;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_drop_in_column__stacks_marks() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        board.drop_in_column(2, Human);
+
+        let actual = board.drop_in_column(2, CPU);
+        let expected = Some(Cell {
+            row: 4,
+            column: 2,
+            layer: 0,
+            value: CPU,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove__frees_cell() {
+        let mut board = Board::new(BoardConfig::default());
+        board.set_human(1, 1);
+
+        let removed = board.remove(1, 1, 0);
+
+        assert_eq!(removed.map(|c| c.value), Some(Human));
+        assert_eq!(board.remove(1, 1, 0), None);
+        assert!(board.set_cpu(1, 1).is_some());
+    }
+
+    #[test]
+    fn test_drop_in_column__when__column_full() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        for _ in 0..6 {
+            board.drop_in_column(0, Human);
+        }
+
+        let actual = board.drop_in_column(0, Human);
+        let expected = None;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_drop_in_column__when__no_gravity() {
+        let mut board = Board::default();
+
+        let actual = board.drop_in_column(1, Human);
+        let expected = None;
+
+        assert_eq!(actual, expected);
+        assert!(board.get_cells().iter().all(|c| c.value == Empty));
+    }
+
+    #[test]
+    fn test_outcome__when__connect_four_row_win() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        for column in 1..=4 {
+            board.drop_in_column(column, CPU);
+        }
+
+        let actual = board.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_apply_move__when__step() {
+        let mut board = Board::default();
+        board.set_cpu(0, 0);
+        let mv = Move::Step {
+            from: Cell {
+                row: 0,
+                column: 0,
+                layer: 0,
+                value: CPU,
+            },
+            to: Cell {
+                row: 1,
+                column: 1,
+                layer: 0,
+                value: CPU,
+            },
+        };
+
+        let result = board.apply_move(mv);
+        assert!(result.is_some());
+
+        assert_eq!(
+            Board::get_cell(board.get_cells(), 0, 0).unwrap().value,
+            Empty
+        );
+        assert_eq!(Board::get_cell(board.get_cells(), 1, 1).unwrap().value, CPU);
+    }
+
+    #[test]
+    fn test_apply_move__when__step_from_opponent_mark() {
+        let mut board = Board::default();
+        board.set_human(0, 0);
+        let mv = Move::Step {
+            from: Cell {
+                row: 0,
+                column: 0,
+                layer: 0,
+                value: CPU,
+            },
+            to: Cell {
+                row: 1,
+                column: 1,
+                layer: 0,
+                value: CPU,
+            },
+        };
+
+        let actual = board.apply_move(mv);
+        let expected = None;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_deserialize__is_inverse_of_serialize() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        board.drop_in_column(3, Human);
+        board.drop_in_column(3, CellValue::Mark(Mark::O));
+
+        let json = serde_json::to_string(&board).unwrap();
+        let restored: Board = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.config(), board.config());
+        assert_eq!(restored.get_cells(), board.get_cells());
+        assert_eq!(restored.win_lines(), board.win_lines());
+    }
+
+    #[test]
+    fn test_deserialize__when__cells_do_not_fit_config() {
+        let mut json = serde_json::to_value(Board::default()).unwrap();
+        json["cells"].as_array_mut().unwrap().pop();
+
+        let actual = serde_json::from_value::<Board>(json).is_err();
+
+        assert!(actual);
+    }
+
+    #[test]
+    fn test_deserialize__when__win_length_zero() {
+        let mut json = serde_json::to_value(Board::default()).unwrap();
+        json["config"]["win_length"] = 0.into();
+
+        let actual = serde_json::from_value::<Board>(json).err().unwrap();
+
+        assert!(actual
+            .to_string()
+            .contains("a game can not be played on the board"));
+    }
 }
@@ -0,0 +1,512 @@
+use crate::board::CellValue::Empty;
+use crate::board::{Board, BoardConfig, Cell, CellValue};
+use crate::network::Network;
+use crate::search::{self, line_weight, Clock, WinLines};
+use crate::utils::now_ms;
+
+const WIN_SCORE: i32 = 1_000_000;
+
+// Score of a position the network is sure is won, well below a seen win
+const NETWORK_SCORE: f32 = 10_000.0;
+// Priority of a move the network is sure of, below winning and blocking
+const NETWORK_PRIORITY: f32 = 1_000.0;
+
+/// Plays boards that are too large for `Evaluator` to search to the end,
+/// such as Qubic. It searches one ply deeper at a time and plays the best
+/// move of the deepest search that finished within the time budget.
+#[derive(Clone)]
+pub struct Engine {
+    pub time_budget_ms: f64,
+    pub max_depth: u8,
+    /// Searched positions after which the search stops
+    pub max_nodes: u64,
+    /// Scores positions and orders moves instead of counting lines, on the
+    /// board it was trained for
+    pub network: Option<Network>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            time_budget_ms: 500.0,
+            max_depth: 64,
+            max_nodes: u64::MAX,
+            network: None,
+        }
+    }
+}
+
+/// What a search had found after finishing a depth
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchInfo {
+    pub depth: u8,
+    /// Score for the player to move, a million less the plies to the end
+    /// once the search has seen a win
+    pub score: i32,
+    pub nodes: u64,
+    pub time_ms: f64,
+    /// The best line of play found, as board indexes starting with the move
+    /// to make
+    pub pv: Vec<usize>,
+}
+
+impl SearchInfo {
+    /// Plies until the end of a game the search has seen to the end, positive
+    /// when the player to move wins
+    pub fn plies_to_win(&self) -> Option<i32> {
+        if self.score.abs() < WIN_SCORE - u8::MAX as i32 {
+            return None;
+        }
+        let plies = WIN_SCORE - self.score.abs();
+        Some(if self.score > 0 { plies } else { -plies })
+    }
+}
+
+impl Engine {
+    pub fn find_best_move(&mut self, board: &Board, value: CellValue) -> Cell {
+        self.analyse(board, value, |_info| {})
+            .expect("There are no moves left to play")
+    }
+
+    /// Searches like `find_best_move`, passing what it found to `report`
+    /// after every depth it finishes, and returns `None` when no move can be
+    /// played
+    pub fn analyse<F: FnMut(&SearchInfo)>(
+        &mut self,
+        board: &Board,
+        value: CellValue,
+        report: F,
+    ) -> Option<Cell> {
+        let deadline = now_ms() + self.time_budget_ms;
+        let network = self
+            .network
+            .as_ref()
+            .filter(|network| network.config() == board.config());
+        let mut search = Search::new(board, deadline, self.max_nodes, network.cloned());
+
+        let index = search.iterate(value, self.max_depth, report)?;
+        let (row, column, layer) = board.config().coordinates(index);
+
+        Some(Cell {
+            row,
+            column,
+            layer,
+            value,
+        })
+    }
+}
+
+struct Search {
+    config: BoardConfig,
+    lines: WinLines,
+    values: Vec<CellValue>,
+    clock: Clock,
+    // The best line found from each ply of the current path
+    pv: Vec<Vec<usize>>,
+    network: Option<Network>,
+}
+
+impl Search {
+    fn new(board: &Board, deadline: f64, max_nodes: u64, network: Option<Network>) -> Self {
+        Search {
+            config: board.config(),
+            lines: WinLines::new(board),
+            values: board.values(),
+            clock: Clock::new(deadline, max_nodes),
+            pv: vec![],
+            network,
+        }
+    }
+
+    /// Returns the best move found before running out of depth, time or
+    /// positions to search
+    fn iterate<F: FnMut(&SearchInfo)>(
+        &mut self,
+        side: CellValue,
+        max_depth: u8,
+        mut report: F,
+    ) -> Option<usize> {
+        let start = now_ms();
+        let mut moves = self.ordered_moves(side);
+        let mut best = *moves.first()?;
+        let plies_left = search::plies_left(&self.values);
+
+        for depth in 1..=max_depth.max(1) {
+            let mut alpha = -WIN_SCORE - 1;
+            let mut best_at_depth = best;
+            let mut pv = vec![best];
+
+            for &mv in moves.iter() {
+                self.values[mv] = side;
+                let score =
+                    -self.negamax(depth - 1, 1, -WIN_SCORE - 1, -alpha, side.opponent(), mv);
+                self.values[mv] = Empty;
+
+                if self.clock.stopped {
+                    return Some(best);
+                }
+                if score > alpha {
+                    alpha = score;
+                    best_at_depth = mv;
+                    pv = self.line_from(mv, 1);
+                }
+            }
+
+            best = best_at_depth;
+            report(&SearchInfo {
+                depth,
+                score: alpha,
+                nodes: self.clock.nodes,
+                time_ms: now_ms() - start,
+                pv,
+            });
+
+            // Search the best move first on the next iteration
+            moves.retain(|&mv| mv != best);
+            moves.insert(0, best);
+
+            let is_decided = alpha.abs() >= WIN_SCORE - max_depth as i32;
+            let is_fully_searched = depth as usize >= plies_left;
+            if is_decided || is_fully_searched {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+
+    fn negamax(
+        &mut self,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        side: CellValue,
+        last: usize,
+    ) -> i32 {
+        if self.pv.len() <= ply as usize + 1 {
+            self.pv.resize(ply as usize + 2, vec![]);
+        }
+        self.pv[ply as usize].clear();
+
+        // The previous move was made by the opponent of `side`
+        if self.lines.is_win_through(&self.values, last) {
+            return -(WIN_SCORE - ply as i32);
+        }
+        if self.clock.tick() {
+            return 0;
+        }
+
+        if depth == 0 {
+            let is_full = !(0..self.values.len()).any(|i| self.config.is_playable(&self.values, i));
+            return if is_full { 0 } else { self.evaluate(side) };
+        }
+        let moves = self.ordered_moves(side);
+        if moves.is_empty() {
+            return 0;
+        }
+
+        for mv in moves {
+            self.values[mv] = side;
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, side.opponent(), mv);
+            self.values[mv] = Empty;
+
+            if score > alpha {
+                alpha = score;
+                self.pv[ply as usize] = self.line_from(mv, ply as usize + 1);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        alpha
+    }
+
+    /// `mv` followed by the best line found after it
+    fn line_from(&self, mv: usize, ply: usize) -> Vec<usize> {
+        let mut line = vec![mv];
+        line.extend_from_slice(&self.pv[ply]);
+        line
+    }
+
+    /// Playable cells, the most promising first. Winning moves come before
+    /// blocking moves, which come before everything else.
+    fn ordered_moves(&self, side: CellValue) -> Vec<usize> {
+        let policy = self
+            .network
+            .as_ref()
+            .map(|network| network.policy(&self.values, side));
+        let mut scored: Vec<(i32, usize)> = (0..self.values.len())
+            .filter(|&i| self.config.is_playable(&self.values, i))
+            .map(|i| {
+                let priority = match (self.move_priority(i, side), &policy) {
+                    (priority, Some(policy)) if priority < WIN_SCORE / 2 => {
+                        (policy[i] * NETWORK_PRIORITY) as i32
+                    }
+                    (priority, _) => priority,
+                };
+                (priority, i)
+            })
+            .collect();
+
+        scored.sort_by(|(score, _i), (score2, _i2)| score2.cmp(score));
+        scored.into_iter().map(|(_score, i)| i).collect()
+    }
+
+    fn move_priority(&self, index: usize, side: CellValue) -> i32 {
+        let mut priority = 0;
+        for &line in self.lines.through[index].iter() {
+            let (own, other) = self.lines.count_line(&self.values, line, side);
+            let length = self.lines.lines[line].len();
+
+            if own == length - 1 {
+                priority += WIN_SCORE;
+            } else if other == length - 1 {
+                priority += WIN_SCORE / 2;
+            } else if other == 0 {
+                priority += line_weight(own) + 1;
+            } else if own == 0 {
+                priority += line_weight(other);
+            }
+        }
+        priority
+    }
+
+    /// Score of the position for `side`, counting every line that only one
+    /// of the players can still complete, or as the network sees it
+    fn evaluate(&self, side: CellValue) -> i32 {
+        if let Some(network) = &self.network {
+            return (network.value(&self.values, side) * NETWORK_SCORE) as i32;
+        }
+        (0..self.lines.lines.len())
+            .map(
+                |line| match self.lines.count_line(&self.values, line, side) {
+                    (own, 0) => line_weight(own),
+                    (0, other) => -line_weight(other),
+                    _ => 0,
+                },
+            )
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, BoardConfig, Cell, CellValue};
+    use crate::engine::Engine;
+    use crate::network::Network;
+
+    fn qubic_with(cells: Vec<(u8, u8, u8, CellValue)>) -> Board {
+        let mut board = Board::new(BoardConfig::qubic());
+        for (layer, row, column, value) in cells {
+            board.set(Cell {
+                row,
+                column,
+                layer,
+                value,
+            });
+        }
+        board
+    }
+
+    #[test]
+    fn test_find_best_move__when__win_available() {
+        let mut engine = Engine {
+            time_budget_ms: 200.0,
+            max_depth: 4,
+            ..Engine::default()
+        };
+        let board = qubic_with(vec![
+            (0, 0, 0, CPU),
+            (1, 1, 1, CPU),
+            (2, 2, 2, CPU),
+            (0, 0, 1, Human),
+            (0, 0, 2, Human),
+            (0, 0, 3, Human),
+        ]);
+
+        let actual = engine.find_best_move(&board, CPU);
+        let expected = Cell {
+            row: 3,
+            column: 3,
+            layer: 3,
+            value: CPU,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__loss_at_risk() {
+        let mut engine = Engine {
+            time_budget_ms: 200.0,
+            max_depth: 4,
+            ..Engine::default()
+        };
+        let board = qubic_with(vec![
+            (3, 0, 0, Human),
+            (3, 1, 0, Human),
+            (3, 2, 0, Human),
+            (0, 0, 0, CPU),
+            (1, 1, 1, CPU),
+        ]);
+
+        let actual = engine.find_best_move(&board, CPU);
+        let expected = Cell {
+            row: 3,
+            column: 0,
+            layer: 3,
+            value: CPU,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__classic_board() {
+        let mut engine = Engine::default();
+        let mut board = Board::default();
+        board.set_human(0, 0);
+        board.set_human(0, 1);
+        board.set_cpu(1, 1);
+
+        let actual = engine.find_best_move(&board, CPU);
+        let expected = Cell {
+            row: 0,
+            column: 2,
+            layer: 0,
+            value: CPU,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__connect_four_loss_at_risk() {
+        let mut engine = Engine {
+            time_budget_ms: 200.0,
+            max_depth: 4,
+            ..Engine::default()
+        };
+        let mut board = Board::new(BoardConfig::connect_four());
+        for _ in 0..3 {
+            board.drop_in_column(5, Human);
+        }
+        board.drop_in_column(0, CPU);
+        board.drop_in_column(6, CPU);
+
+        let actual = engine.find_best_move(&board, CPU);
+        let expected = Cell {
+            row: 2,
+            column: 5,
+            layer: 0,
+            value: CPU,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_analyse__reports_each_depth_with_pv() {
+        let mut engine = Engine::default();
+        let mut board = Board::default();
+        board.set_human(0, 0);
+        board.set_human(0, 1);
+        board.set_cpu(1, 1);
+        let mut infos = vec![];
+
+        let best = engine.analyse(&board, CPU, |info| infos.push(info.clone()));
+
+        let depths: Vec<u8> = infos.iter().map(|info| info.depth).collect();
+        assert_eq!(depths, (1..=depths.len() as u8).collect::<Vec<u8>>());
+        let last = infos.last().unwrap();
+        assert_eq!(last.pv[0], 2);
+        assert_eq!(best.map(|c| (c.row, c.column)), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_analyse__when__win_in_one() {
+        let mut engine = Engine::default();
+        let mut board = Board::default();
+        board.set_cpu(0, 0);
+        board.set_cpu(0, 1);
+        board.set_human(1, 0);
+        board.set_human(1, 1);
+        board.set_human(2, 2);
+        let mut last = None;
+
+        engine.analyse(&board, CPU, |info| last = Some(info.clone()));
+
+        let actual = last.and_then(|info| info.plies_to_win());
+        let expected = Some(1);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_analyse__when__gravity_searches_past_columns() {
+        let mut engine = Engine {
+            time_budget_ms: f64::INFINITY,
+            max_depth: 8,
+            ..Engine::default()
+        };
+        let config = BoardConfig {
+            rows: 4,
+            columns: 3,
+            gravity: true,
+            ..BoardConfig::square(4, 4)
+        };
+        let mut depth = 0;
+
+        engine.analyse(&Board::new(config), Human, |info| depth = info.depth);
+
+        assert_eq!(depth, 8);
+    }
+
+    #[test]
+    fn test_analyse__when__node_limit() {
+        let mut engine = Engine {
+            max_nodes: 500,
+            ..Engine::default()
+        };
+        let board = Board::new(BoardConfig::qubic());
+        let mut nodes = 0;
+
+        let best = engine.analyse(&board, Human, |info| nodes = info.nodes);
+
+        assert!(best.is_some());
+        assert!(nodes < 500);
+    }
+
+    #[test]
+    fn test_find_best_move__when__network_and_loss_at_risk() {
+        // Even an untrained network can not talk the search out of a block
+        let config = BoardConfig::connect_four();
+        let mut engine = Engine {
+            time_budget_ms: 200.0,
+            max_depth: 4,
+            network: Some(Network::new(config, 8, 1).unwrap()),
+            ..Engine::default()
+        };
+        let mut board = Board::new(config);
+        for _ in 0..3 {
+            board.drop_in_column(5, Human);
+        }
+        board.drop_in_column(0, CPU);
+        board.drop_in_column(6, CPU);
+
+        let actual = engine.find_best_move(&board, CPU);
+        let expected = Cell {
+            row: 2,
+            column: 5,
+            layer: 0,
+            value: CPU,
+        };
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::board::CellValue::{Empty, Human, CPU};
 use crate::board::{Board, Cell, CellValue};
 
+#[derive(Clone)]
 pub struct Evaluator {}
 
 impl Evaluator {
@@ -9,11 +10,14 @@ impl Evaluator {
             .clone()
             .into_iter()
             .any(|c| (c.row, c.column, c.value) == (1, 1, Empty));
+        // Games started from a custom position may need a different reply
+        let is_opening = cells.iter().filter(|c| c.is_not_empty()).count() <= 1;
 
-        if is_middle_cell_available {
+        if is_opening && is_middle_cell_available {
             return Cell {
                 row: 1,
                 column: 1,
+                layer: 0,
                 value: CPU,
             };
         }
@@ -22,6 +26,7 @@ impl Evaluator {
             return Cell {
                 row: 0,
                 column: 2,
+                layer: 0,
                 value: CPU,
             };
         }
@@ -37,6 +42,7 @@ impl Evaluator {
                     Cell {
                         row: c.row,
                         column: c.column,
+                        layer: c.layer,
                         value: CellValue::Human,
                     },
                 )
@@ -53,6 +59,7 @@ impl Evaluator {
             .unwrap()
     }
 
+    #[allow(clippy::only_used_in_recursion)]
     fn minimax(&mut self, cells: Vec<Cell>, depth: u8) -> i8 {
         let score = self.evaluate(cells.clone());
 
@@ -79,12 +86,13 @@ impl Evaluator {
                     Cell {
                         row: c.row,
                         column: c.column,
+                        layer: c.layer,
                         value: CellValue::CPU,
                     },
                 )
                 .unwrap();
 
-                self.minimax(cells, (depth + 1) as u8)
+                self.minimax(cells, depth + 1)
             })
             .min_by(|score, score2| score2.cmp(score))
             .unwrap()
@@ -153,17 +161,14 @@ impl Evaluator {
         }
 
         // Check for wins in diagonals
-        let first_diagonal: Vec<(u8, u8)> =
-            vec![(0 as u8, 0 as u8), (1 as u8, 1 as u8), (2 as u8, 2 as u8)];
-        let second_diagonal: Vec<(u8, u8)> =
-            vec![(0 as u8, 2 as u8), (1 as u8, 1 as u8), (2 as u8, 0 as u8)];
+        let first_diagonal: Vec<(u8, u8)> = vec![(0, 0), (1, 1), (2, 2)];
+        let second_diagonal: Vec<(u8, u8)> = vec![(0, 2), (1, 1), (2, 0)];
         let diagonals = vec![first_diagonal, second_diagonal];
 
         let human_did_win = diagonals
             .clone()
             .into_iter()
-            .map(|diagonals| self.eval_diagonal_win(cells.clone(), diagonals, Human))
-            .any(|res| res);
+            .any(|diagonals| self.eval_diagonal_win(cells.clone(), diagonals, Human));
 
         if human_did_win {
             return 10;
@@ -171,8 +176,7 @@ impl Evaluator {
 
         let cpu_did_win = diagonals
             .into_iter()
-            .map(|diagonals| self.eval_diagonal_win(cells.clone(), diagonals, CPU))
-            .any(|res| res);
+            .any(|diagonals| self.eval_diagonal_win(cells.clone(), diagonals, CPU));
 
         if cpu_did_win {
             return -10;
@@ -213,16 +217,19 @@ impl Evaluator {
             Cell {
                 row: 0,
                 column: 0,
+                layer: 0,
                 value: CellValue::CPU,
             },
             Cell {
                 row: 1,
                 column: 1,
+                layer: 0,
                 value: CellValue::Human,
             },
             Cell {
                 row: 2,
                 column: 2,
+                layer: 0,
                 value: CellValue::Human,
             },
         ];
@@ -247,11 +254,11 @@ mod tests {
     #[test]
     fn test_eval_diagonal_win__when__there_is_a_win() {
         let evaluator = Evaluator {};
-        let first_diagonal: Vec<(u8, u8)> =
-            vec![(0 as u8, 0 as u8), (1 as u8, 1 as u8), (2 as u8, 2 as u8)];
+        let first_diagonal: Vec<(u8, u8)> = vec![(0, 0), (1, 1), (2, 2)];
         let human_win_cell_1 = Cell {
             row: 0,
             column: 0,
+            layer: 0,
             value: Human,
         };
         let mut human_win_cell_2 = human_win_cell_1.clone();
@@ -276,6 +283,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
@@ -294,6 +302,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -302,11 +311,12 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
 
-        let x = cells.into_iter().chain(win_cells.into_iter()).collect();
+        let x = cells.into_iter().chain(win_cells).collect();
 
         let actual = evaluator.evaluate(x);
         let expected = -10;
@@ -322,6 +332,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
@@ -340,6 +351,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
@@ -358,6 +370,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -366,10 +379,11 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
-        cells.extend(win_cells.into_iter());
+        cells.extend(win_cells);
 
         let actual = evaluator.evaluate(cells);
         let expected = 10;
@@ -385,6 +399,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -393,10 +408,11 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
-        cells.extend(win_cells.into_iter());
+        cells.extend(win_cells);
 
         let actual = evaluator.evaluate(cells);
         let expected = 10;
@@ -412,6 +428,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -420,10 +437,11 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
-        cells.extend(win_cells.into_iter());
+        cells.extend(win_cells);
 
         let actual = evaluator.evaluate(cells);
         let expected = -10;
@@ -439,6 +457,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -447,10 +466,11 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
-        cells.extend(win_cells.into_iter());
+        cells.extend(win_cells);
 
         let actual = evaluator.evaluate(cells);
         let expected = -10;
@@ -466,6 +486,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Empty,
             })
             .collect();
@@ -484,6 +505,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
@@ -502,6 +524,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
@@ -520,6 +543,7 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: CPU,
             })
             .collect();
@@ -529,11 +553,12 @@ mod tests {
             .map(|(row, column)| Cell {
                 row,
                 column,
+                layer: 0,
                 value: Human,
             })
             .collect();
 
-        cpu_cells.extend(human_cells.into_iter());
+        cpu_cells.extend(human_cells);
 
         let actual = evaluator.minimax(cpu_cells, 0);
         let expected = 0;
@@ -556,13 +581,19 @@ mod tests {
             (2, 2, Empty),
         ]
         .into_iter()
-        .map(|(row, column, value)| Cell { row, column, value })
+        .map(|(row, column, value)| Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        })
         .collect();
 
         let actual = evaluator.find_best_move(cells);
         let expected = Cell {
             row: 1,
             column: 1,
+            layer: 0,
             value: CPU,
         };
 
@@ -584,13 +615,19 @@ mod tests {
             (2, 2, Empty),
         ]
         .into_iter()
-        .map(|(row, column, value)| Cell { row, column, value })
+        .map(|(row, column, value)| Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        })
         .collect();
 
         let actual = evaluator.find_best_move(cells);
         let expected = Cell {
             row: 2,
             column: 2,
+            layer: 0,
             value: CPU,
         };
 
@@ -612,13 +649,19 @@ mod tests {
             (2, 2, Empty),
         ]
         .into_iter()
-        .map(|(row, column, value)| Cell { row, column, value })
+        .map(|(row, column, value)| Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        })
         .collect();
 
         let actual = evaluator.find_best_move(cells);
         let expected = Cell {
             row: 0,
             column: 2,
+            layer: 0,
             value: CPU,
         };
 
@@ -640,13 +683,19 @@ mod tests {
             (2, 2, Empty),
         ]
         .into_iter()
-        .map(|(row, column, value)| Cell { row, column, value })
+        .map(|(row, column, value)| Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        })
         .collect();
 
         let actual = evaluator.find_best_move(cells);
         let expected = Cell {
             row: 2,
             column: 1,
+            layer: 0,
             value: CPU,
         };
 
@@ -668,13 +717,19 @@ mod tests {
             (2, 2, Human),
         ]
         .into_iter()
-        .map(|(row, column, value)| Cell { row, column, value })
+        .map(|(row, column, value)| Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        })
         .collect();
 
         let actual = evaluator.find_best_move(cells);
         let expected = Cell {
             row: 0,
             column: 2,
+            layer: 0,
             value: CPU,
         };
 
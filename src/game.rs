@@ -1,13 +1,360 @@
-use crate::board::Board;
+use crate::board::CellValue::{Human, CPU};
+use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome, PositionError, Topology};
+use crate::engine::Engine;
 use crate::evaluator::Evaluator;
+use crate::network::{Network, NetworkError};
+use crate::notation::{self, NotationError};
+use crate::paranoid::ParanoidEngine;
+use crate::qlearning::{QLearningError, QTable};
+use crate::record::{self, Metadata};
+use crate::share;
 
+use serde::Serialize;
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
 
 #[wasm_bindgen]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Variant {
+    Classic,
+    Qubic,
+    ConnectFour,
+    /// Three in a row on a 4x4 board, meant for three players
+    FourByFour,
+    /// Four in a row on a 5x5 board, meant for three or four players
+    FiveByFive,
+}
+
+impl Variant {
+    /// The variant with a name such as `connect-four`, as typed by players
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name {
+            "classic" => Some(Variant::Classic),
+            "qubic" => Some(Variant::Qubic),
+            "connect-four" => Some(Variant::ConnectFour),
+            "4x4" => Some(Variant::FourByFour),
+            "5x5" => Some(Variant::FiveByFive),
+            _ => None,
+        }
+    }
+
+    pub fn config(self) -> BoardConfig {
+        match self {
+            Variant::Classic => BoardConfig::default(),
+            Variant::Qubic => BoardConfig::qubic(),
+            Variant::ConnectFour => BoardConfig::connect_four(),
+            Variant::FourByFour => BoardConfig::square(4, 3),
+            Variant::FiveByFive => BoardConfig::square(5, 4),
+        }
+    }
+}
+
+/// Who makes the moves for a seat
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PlayerKind {
+    /// Someone at the device, playing through `humanPlay`
+    Human,
+    /// The computer, playing through `cpuPlay`
+    Cpu,
+}
+
+/// A player in the order of play, with the value of their marks
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(try_from = "SavedSeat")]
+pub struct Seat {
+    pub value: CellValue,
+    pub kind: PlayerKind,
+}
+
+/// A seat as any version has saved it. Saves from before player kinds only
+/// say whether the computer plays the seat.
+#[derive(Deserialize)]
+struct SavedSeat {
+    value: CellValue,
+    kind: Option<PlayerKind>,
+    is_cpu: Option<bool>,
+}
+
+impl TryFrom<SavedSeat> for Seat {
+    type Error = &'static str;
+
+    fn try_from(saved: SavedSeat) -> Result<Self, Self::Error> {
+        let kind = match (saved.kind, saved.is_cpu) {
+            (Some(kind), _) => kind,
+            (None, Some(true)) => PlayerKind::Cpu,
+            (None, Some(false)) => PlayerKind::Human,
+            (None, None) => return Err("a seat needs a kind"),
+        };
+        Ok(Seat {
+            value: saved.value,
+            kind,
+        })
+    }
+}
+
+impl Seat {
+    pub fn is_cpu(&self) -> bool {
+        self.kind == PlayerKind::Cpu
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(into = "GameState", try_from = "GameState")]
 pub struct Game {
     board: Board,
     evaluator: Evaluator,
+    engine: Engine,
+    paranoid_engine: ParanoidEngine,
+    // A learned player the computer plays as instead of searching
+    policy: Option<QTable>,
+    seats: Vec<Seat>,
+    turn: usize,
+    // Marks played since the start position, oldest first
+    history: Vec<Cell>,
+}
+
+/// What is saved of a game, the engines start over with their defaults and
+/// a learned player has to be given again
+#[derive(Serialize, Deserialize)]
+struct GameState {
+    board: Board,
+    seats: Vec<Seat>,
+    turn: usize,
+    history: Vec<Cell>,
+}
+
+impl From<Game> for GameState {
+    fn from(game: Game) -> Self {
+        GameState {
+            board: game.board,
+            seats: game.seats,
+            turn: game.turn,
+            history: game.history,
+        }
+    }
+}
+
+impl TryFrom<GameState> for Game {
+    type Error = PositionError;
+
+    /// Checks that the player to move is one of the seats and that every
+    /// move in the history is on the board
+    fn try_from(state: GameState) -> Result<Self, Self::Error> {
+        if state.seats.len() < 2 || state.turn >= state.seats.len() {
+            return Err(PositionError::Unreachable);
+        }
+        let cells = state.board.get_cells();
+        let is_on_board = |c: &Cell| {
+            Board::get_cell_in_layer(cells.clone(), c.row, c.column, c.layer).as_ref() == Some(c)
+        };
+        if !state.history.iter().all(is_on_board) {
+            return Err(PositionError::Unreachable);
+        }
+
+        let mut game = Game::with_seats(state.board.config(), state.seats);
+        game.board = state.board;
+        game.turn = state.turn;
+        game.history = state.history;
+        Ok(game)
+    }
+}
+
+impl Game {
+    /// A game between the human, who plays first, and the CPU on any board
+    pub fn with_config(config: BoardConfig) -> Game {
+        Game::with_kinds(config, &[PlayerKind::Human, PlayerKind::Cpu])
+    }
+
+    /// A game with a seat for every kind of player, in the order of play.
+    /// Two seats play with the marks of the human and the CPU whoever has
+    /// them, more seats play with `CellValue::Player`.
+    pub fn with_kinds(config: BoardConfig, kinds: &[PlayerKind]) -> Game {
+        let seats = kinds
+            .iter()
+            .enumerate()
+            .map(|(seat, &kind)| {
+                let value = match (kinds.len(), seat) {
+                    (2, 0) => Human,
+                    (2, _) => CPU,
+                    _ => CellValue::Player(seat as u8),
+                };
+                Seat { value, kind }
+            })
+            .collect();
+        Game::with_seats(config, seats)
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn seats(&self) -> &[Seat] {
+        &self.seats
+    }
+
+    pub fn current_seat(&self) -> Seat {
+        self.seats[self.turn]
+    }
+
+    pub fn history(&self) -> &[Cell] {
+        &self.history
+    }
+
+    /// The board before the first move in the history, and the player who
+    /// made that move
+    pub fn start_position(&self) -> (Board, CellValue) {
+        let mut values = self.board.values();
+        let config = self.board.config();
+        for c in self.history.iter() {
+            if let Some(index) = config.index(c.row, c.column, c.layer) {
+                values[index] = CellValue::Empty;
+            }
+        }
+
+        let cells = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let (row, column, layer) = config.coordinates(index);
+                Cell {
+                    row,
+                    column,
+                    layer,
+                    value,
+                }
+            })
+            .collect();
+        let mut board = Board::new(config);
+        board.set_all(cells);
+
+        (board, self.seats[self.start_turn()].value)
+    }
+
+    // The seat that made the first move in the history
+    fn start_turn(&self) -> usize {
+        let seats = self.seats.len();
+        (self.turn + seats - self.history.len() % seats) % seats
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.board.outcome()
+    }
+
+    /// Puts a mark for the player whose turn it is and passes the turn on
+    pub fn play(&mut self, cell: Cell) -> Option<Cell> {
+        let is_own_mark = cell.value == self.current_seat().value;
+        if !is_own_mark || self.outcome() != Outcome::InProgress {
+            return None;
+        }
+
+        self.board.set(cell.clone())?;
+        self.turn = (self.turn + 1) % self.seats.len();
+        self.history.push(cell.clone());
+        Some(cell)
+    }
+
+    /// Takes back the last move and gives the turn back to whoever made it
+    pub fn undo(&mut self) -> Option<Cell> {
+        let cell = self.history.pop()?;
+        self.board.remove(cell.row, cell.column, cell.layer);
+        self.turn = (self.turn + self.seats.len() - 1) % self.seats.len();
+        Some(cell)
+    }
+
+    /// The move the computer would make for the player whose turn it is
+    pub fn hint(&mut self) -> Option<Cell> {
+        (self.outcome() == Outcome::InProgress).then(|| self.best_move())
+    }
+
+    /// A game against the CPU from a position, with whoever has fewer marks
+    /// to move
+    pub fn with_position(variant: Variant, values: Vec<CellValue>) -> Result<Game, PositionError> {
+        let board = Board::from_position(variant.config(), values)?;
+        Ok(Game::with_board(board))
+    }
+
+    /// A game against the CPU on a board from `Board::from_position`
+    pub fn with_board(board: Board) -> Game {
+        let count = |value| board.values().into_iter().filter(|v| *v == value).count();
+
+        let mut game = Game::with_config(board.config());
+        game.turn = if count(Human) > count(CPU) { 1 } else { 0 };
+        game.board = board;
+        game
+    }
+
+    /// A game against the CPU from a position in the notation of
+    /// `notation::parse`
+    pub fn from_notation(text: &str) -> Result<Game, NotationError> {
+        let (board, _to_move) = notation::parse(text)?;
+        Ok(Game::with_board(board))
+    }
+
+    /// Lets the computer play as a learned player, such as one trained with
+    /// `QLearner`, instead of searching
+    pub fn set_policy(&mut self, table: QTable) -> Result<(), QLearningError> {
+        if table.config() != self.board.config() {
+            return Err(QLearningError::WrongBoard);
+        }
+        self.policy = Some(table);
+        Ok(())
+    }
+
+    /// Gives the computer's search a trained network to judge positions
+    /// with. The evaluator plays the CPU seat of the classic board by
+    /// itself, so the network only helps the search everywhere else.
+    pub fn set_network(&mut self, network: Network) -> Result<(), NetworkError> {
+        if network.config() != self.board.config() {
+            return Err(NetworkError::WrongBoard);
+        }
+        self.engine.network = Some(network);
+        Ok(())
+    }
+
+    pub fn to_notation(&self) -> Result<String, NotationError> {
+        notation::format(&self.board, self.current_seat().value)
+    }
+
+    fn best_move(&mut self) -> Cell {
+        let seat = self.current_seat();
+        let values: Vec<CellValue> = self.seats.iter().map(|seat| seat.value).collect();
+        let policy_move = self
+            .policy
+            .as_ref()
+            .and_then(|policy| policy.best_move(&self.board, seat.value));
+        if matches!(seat.value, CellValue::Player(_)) {
+            self.paranoid_engine
+                .find_best_move(&self.board, &values, self.turn)
+        } else if let Some(cell) = policy_move {
+            cell
+        } else if seat.value == CPU && self.board.config() == BoardConfig::default() {
+            // The evaluator can only play the classic board as the CPU, but
+            // it never loses
+            self.evaluator.find_best_move(self.board.get_cells())
+        } else {
+            self.engine.find_best_move(&self.board, seat.value)
+        }
+    }
+
+    fn human_cell(&self, row: u8, column: u8, layer: u8) -> Option<Cell> {
+        let seat = self.current_seat();
+        (!seat.is_cpu()).then_some(Cell {
+            row,
+            column,
+            layer,
+            value: seat.value,
+        })
+    }
+
+    fn is_winner_cpu(&self, is_cpu: bool) -> bool {
+        self.board
+            .winner()
+            .and_then(|winner| self.seats.iter().find(|seat| seat.value == winner))
+            .is_some_and(|seat| seat.is_cpu() == is_cpu)
+    }
 }
 
 #[wasm_bindgen]
@@ -15,20 +362,234 @@ impl Game {
     #[wasm_bindgen(constructor)]
     #[allow(clippy::new_without_default)]
     pub fn new() -> Game {
+        Game::with_variant(Variant::Classic)
+    }
+
+    #[wasm_bindgen(js_name = withVariant)]
+    pub fn with_variant(variant: Variant) -> Game {
+        Game::with_config(variant.config())
+    }
+
+    /// A game where the human plays first and the computer plays every
+    /// other seat
+    #[wasm_bindgen(js_name = withPlayers)]
+    pub fn with_players(variant: Variant, players: u8) -> Game {
+        let seats = (0..players.max(2))
+            .map(|seat| Seat {
+                value: CellValue::Player(seat),
+                kind: if seat == 0 {
+                    PlayerKind::Human
+                } else {
+                    PlayerKind::Cpu
+                },
+            })
+            .collect();
+        Game::with_seats(variant.config(), seats)
+    }
+
+    /// A game for two people taking turns on one device
+    #[wasm_bindgen(js_name = hotSeat)]
+    pub fn hot_seat(variant: Variant) -> Game {
+        Game::with_kinds(variant.config(), &[PlayerKind::Human, PlayerKind::Human])
+    }
+
+    /// A game the computer plays against itself, to watch
+    #[wasm_bindgen(js_name = cpuVersusCpu)]
+    pub fn cpu_versus_cpu(variant: Variant) -> Game {
+        Game::with_kinds(variant.config(), &[PlayerKind::Cpu, PlayerKind::Cpu])
+    }
+
+    fn with_seats(config: BoardConfig, seats: Vec<Seat>) -> Game {
         Game {
-            board: Board::default(),
+            board: Board::new(config),
             evaluator: Evaluator {},
+            engine: Engine::default(),
+            paranoid_engine: ParanoidEngine::default(),
+            policy: None,
+            seats,
+            turn: 0,
+            history: vec![],
         }
     }
 
+    /// A game against the CPU from a position given as one code per cell,
+    /// see `CellValue::from_code`
+    #[wasm_bindgen(js_name = fromPosition)]
+    pub fn from_position(variant: Variant, codes: Vec<u8>) -> Result<Game, JsValue> {
+        let values: Option<Vec<CellValue>> = codes.into_iter().map(CellValue::from_code).collect();
+        values
+            .ok_or(PositionError::UnsupportedValue)
+            .and_then(|values| Game::with_position(variant, values))
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = fromNotation)]
+    pub fn from_notation_js(text: &str) -> Result<Game, JsValue> {
+        Game::from_notation(text).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = toNotation)]
+    pub fn to_notation_js(&self) -> Result<String, JsValue> {
+        self.to_notation()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// The game and its moves so far as a record, see `record::format`
+    #[wasm_bindgen(js_name = toRecord)]
+    pub fn to_record(
+        &self,
+        human: String,
+        cpu: String,
+        level: String,
+        date: String,
+    ) -> Result<String, JsValue> {
+        let metadata = Metadata {
+            human,
+            cpu,
+            level,
+            date,
+        };
+        record::format(self, &metadata).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// A game with every move of a record played, ready to be stepped
+    /// through with `getHistory`
+    #[wasm_bindgen(js_name = fromRecord)]
+    pub fn from_record(text: &str) -> Result<Game, JsValue> {
+        record::parse(text)
+            .map(|(game, _metadata)| game)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// A code for the game that can be put in a link, see `share::encode`
+    #[wasm_bindgen(js_name = toShareCode)]
+    pub fn to_share_code(&self) -> Result<String, JsValue> {
+        share::encode(self).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = fromShareCode)]
+    pub fn from_share_code(code: &str) -> Result<Game, JsValue> {
+        share::decode(code).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = getHistory)]
+    pub fn get_history(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.history).unwrap()
+    }
+
+    /// The whole game as a plain object, so `JSON.stringify` can save it
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        self.serialize(&serializer).unwrap()
+    }
+
+    /// A game saved with `toJSON`
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(value: JsValue) -> Result<Game, JsValue> {
+        serde_wasm_bindgen::from_value(value).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
     #[wasm_bindgen(js_name = getCells)]
     pub fn get_cells(&self) -> JsValue {
-        JsValue::from_serde(&self.board.get_cells()).unwrap()
+        serde_wasm_bindgen::to_value(&self.board.get_cells()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getConfig)]
+    pub fn get_config(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.config()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getLegalMoves)]
+    pub fn get_legal_moves(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.legal_moves()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getSeats)]
+    pub fn get_seats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.seats).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getCurrentPlayer)]
+    pub fn get_current_player(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.current_seat().value).unwrap()
+    }
+
+    /// The seat to move, counting from 0 in the order of play
+    #[wasm_bindgen(js_name = getTurn)]
+    pub fn get_turn(&self) -> usize {
+        self.turn
+    }
+
+    #[wasm_bindgen(js_name = getCurrentPlayerKind)]
+    pub fn get_current_player_kind(&self) -> PlayerKind {
+        self.current_seat().kind
+    }
+
+    /// The seat that has a line, if any
+    #[wasm_bindgen(js_name = getWinnerSeat)]
+    pub fn get_winner_seat(&self) -> Option<usize> {
+        let winner = self.board.winner()?;
+        self.seats.iter().position(|seat| seat.value == winner)
+    }
+
+    /// Lets the computer play as a learned player from a Q-table saved as
+    /// JSON, see `set_policy`
+    #[wasm_bindgen(js_name = setPolicy)]
+    pub fn set_policy_js(&mut self, json: &str) -> Result<(), JsValue> {
+        QTable::from_json(json)
+            .and_then(|table| self.set_policy(table))
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Goes back to searching for the computer's moves
+    #[wasm_bindgen(js_name = clearPolicy)]
+    pub fn clear_policy(&mut self) {
+        self.policy = None;
+    }
+
+    /// Loads network weights saved with `Network::to_bytes`, see
+    /// `set_network`
+    #[wasm_bindgen(js_name = setNetwork)]
+    pub fn set_network_js(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        Network::from_bytes(bytes)
+            .and_then(|network| self.set_network(network))
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Goes back to counting lines to judge positions
+    #[wasm_bindgen(js_name = clearNetwork)]
+    pub fn clear_network(&mut self) {
+        self.engine.network = None;
+    }
+
+    /// Hands a seat to a person or to the computer, in the middle of a game
+    /// as well
+    #[wasm_bindgen(js_name = setPlayerKind)]
+    pub fn set_player_kind(&mut self, seat: usize, kind: PlayerKind) {
+        if let Some(seat) = self.seats.get_mut(seat) {
+            seat.kind = kind;
+        }
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = isCpuTurn)]
+    pub fn is_cpu_turn(&self) -> bool {
+        self.current_seat().is_cpu()
     }
 
     #[wasm_bindgen(js_name = isCpuWinner)]
     pub fn is_cpu_winner(&self) -> bool {
-        self.board.is_cpu_winner()
+        self.is_winner_cpu(true)
+    }
+
+    #[wasm_bindgen(js_name = isHumanWinner)]
+    pub fn is_human_winner(&self) -> bool {
+        self.is_winner_cpu(false)
     }
 
     #[wasm_bindgen(js_name = hasEmptyCells)]
@@ -38,16 +599,421 @@ impl Game {
 
     #[wasm_bindgen(js_name = humanPlay)]
     pub fn human_play(&mut self, row: u8, column: u8) {
-        self.board.set_human(row, column);
+        self.human_play_in_layer(row, column, 0);
+    }
+
+    #[wasm_bindgen(js_name = humanPlayInLayer)]
+    pub fn human_play_in_layer(&mut self, row: u8, column: u8, layer: u8) {
+        if let Some(cell) = self.human_cell(row, column, layer) {
+            self.play(cell);
+        }
     }
 
+    /// Plays in the lowest empty row of a column when the board has gravity
+    #[wasm_bindgen(js_name = humanDrop)]
+    pub fn human_drop(&mut self, column: u8) {
+        let cell = self
+            .board
+            .legal_moves()
+            .into_iter()
+            .find(|c| (c.column, c.layer) == (column, 0))
+            .and_then(|c| self.human_cell(c.row, c.column, c.layer));
+        if let Some(cell) = cell {
+            self.play(cell);
+        }
+    }
+
+    /// Plays for the current seat if the computer has it
     #[wasm_bindgen(js_name = cpuPlay)]
     pub fn cpu_play(&mut self) {
-        let best_move = self.evaluator.find_best_move(self.board.get_cells());
-        self.board.set_cpu(best_move.row, best_move.column);
+        let seat = self.current_seat();
+        if !seat.is_cpu() || self.outcome() != Outcome::InProgress {
+            return;
+        }
+
+        let best_move = self.best_move();
+        self.play(best_move);
+    }
+
+    /// Starts over on the same board with lines that run the given way
+    /// across its edges
+    #[wasm_bindgen(js_name = setTopology)]
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.board = Board::new(self.board.config().with_topology(topology));
+        self.turn = 0;
+        self.history.clear();
     }
 
+    /// Goes back to the start position, with its blocked cells and the
+    /// marks it was set up with
     pub fn restart(&mut self) {
-        self.board.clear();
+        let (board, _first) = self.start_position();
+        self.turn = self.start_turn();
+        self.board = board;
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{BoardConfig, Cell, CellValue, Outcome, Topology};
+    use crate::game::{Game, PlayerKind, Variant};
+    use crate::network::{Network, NetworkError};
+    use crate::qlearning::{QLearningError, QTable};
+
+    #[test]
+    fn test_cpu_play__when__policy_is_set() {
+        // Learned to answer the centre with an edge, which the evaluator
+        // would never play
+        let json = r#"{"config":{"rows":3,"columns":3,"layers":1,"win_length":3,
+            "gravity":false,"topology":"Flat"},
+            "values":{"....o....":[0,1,0,0,0,0,0,0,0]}}"#;
+        let mut game = Game::new();
+        game.set_policy_js(json).unwrap();
+        game.human_play(1, 1);
+
+        game.cpu_play();
+        let actual = game.history().last().cloned();
+        let expected = Some(Cell {
+            row: 0,
+            column: 1,
+            layer: 0,
+            value: CPU,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_cpu_play__when__policy_has_not_learned_position() {
+        // Only knows how to answer the centre, so a corner is left to the
+        // evaluator, which takes the centre
+        let json = r#"{"config":{"rows":3,"columns":3,"layers":1,"win_length":3,
+            "gravity":false,"topology":"Flat"},
+            "values":{"....o....":[0,1,0,0,0,0,0,0,0]}}"#;
+        let mut game = Game::new();
+        game.set_policy_js(json).unwrap();
+        game.human_play(0, 0);
+
+        game.cpu_play();
+        let actual = game.history().last().cloned();
+        let expected = Some(Cell {
+            row: 1,
+            column: 1,
+            layer: 0,
+            value: CPU,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_set_policy__when__other_board() {
+        let table = QTable::new(BoardConfig::square(4, 4)).unwrap();
+
+        let actual = Game::new().set_policy(table);
+        let expected = Err(QLearningError::WrongBoard);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_cpu_play__when__network_is_set() {
+        let mut game = Game::with_variant(Variant::ConnectFour);
+        let network = Network::new(BoardConfig::connect_four(), 8, 1).unwrap();
+        game.set_network_js(&network.to_bytes()).unwrap();
+        game.engine.time_budget_ms = 50.0;
+        game.human_play(5, 3);
+
+        game.cpu_play();
+        let actual = game.history().len();
+        let expected = 2;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_set_network__when__other_board() {
+        let network = Network::new(BoardConfig::connect_four(), 8, 1).unwrap();
+
+        let actual = Game::new().set_network(network);
+        let expected = Err(NetworkError::WrongBoard);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_cpu_play__when__humans_turn() {
+        let mut game = Game::new();
+        game.cpu_play();
+
+        assert!(game.board().values().iter().all(|v| *v != CPU));
+        assert_eq!(game.current_seat().value, Human);
+    }
+
+    #[test]
+    fn test_human_play__passes_turn_to_cpu() {
+        let mut game = Game::new();
+        game.human_play(1, 1);
+        game.human_play(0, 0);
+
+        assert_eq!(game.current_seat().value, CPU);
+        assert_eq!(
+            game.board()
+                .values()
+                .iter()
+                .filter(|v| **v == Human)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_turn_rotation__when__three_players() {
+        let mut game = Game::with_players(Variant::FourByFour, 3);
+        game.paranoid_engine.time_budget_ms = 50.0;
+
+        game.human_play(0, 0);
+        assert_eq!(game.current_seat().value, CellValue::Player(1));
+        game.cpu_play();
+        assert_eq!(game.current_seat().value, CellValue::Player(2));
+        game.cpu_play();
+        assert!(!game.is_cpu_turn());
+
+        let actual: Vec<usize> = (0..3)
+            .map(|seat| {
+                let value = CellValue::Player(seat);
+                game.board()
+                    .values()
+                    .iter()
+                    .filter(|v| **v == value)
+                    .count()
+            })
+            .collect();
+        let expected = vec![1, 1, 1];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_is_cpu_winner__when__other_cpu_seat_wins() {
+        let mut game = Game::with_players(Variant::FourByFour, 3);
+        for (row, column) in [
+            (3, 3),
+            (0, 0),
+            (1, 0),
+            (3, 2),
+            (0, 1),
+            (1, 1),
+            (2, 3),
+            (0, 2),
+        ] {
+            let value = game.current_seat().value;
+            game.play(Cell {
+                row,
+                column,
+                layer: 0,
+                value,
+            })
+            .unwrap();
+        }
+
+        assert_eq!(game.outcome(), Outcome::Winner(CellValue::Player(1)));
+        assert!(game.is_cpu_winner());
+        assert!(!game.is_human_winner());
+    }
+
+    #[test]
+    fn test_undo__gives_turn_back() {
+        let mut game = Game::new();
+        game.human_play(1, 1);
+        game.cpu_play();
+
+        let undone = game.undo();
+
+        assert_eq!(undone.map(|c| c.value), Some(CPU));
+        assert_eq!(game.current_seat().value, CPU);
+        assert_eq!(game.history().len(), 1);
+        assert_eq!(
+            game.board().values().iter().filter(|v| **v == CPU).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_undo__when__no_moves() {
+        let mut game = Game::new();
+
+        let actual = game.undo();
+        let expected = None;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_hint__when__human_can_win() {
+        let mut game = Game::from_notation("xx./oo./... x").unwrap();
+
+        let actual = game.hint();
+        let expected = Some(Cell {
+            row: 0,
+            column: 2,
+            layer: 0,
+            value: Human,
+        });
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_hot_seat__both_seats_play_by_hand() {
+        let mut game = Game::hot_seat(Variant::Classic);
+        game.human_play(1, 1);
+        game.cpu_play();
+        game.human_play(0, 0);
+
+        assert_eq!(game.board().values()[0], CPU);
+        assert_eq!(game.board().values()[4], Human);
+        assert_eq!(game.get_turn(), 0);
+        assert_eq!(game.get_current_player_kind(), PlayerKind::Human);
+    }
+
+    #[test]
+    fn test_cpu_versus_cpu__plays_to_a_draw() {
+        let mut game = Game::cpu_versus_cpu(Variant::Classic);
+        while game.outcome() == Outcome::InProgress {
+            game.cpu_play();
+        }
+
+        let actual = game.outcome();
+        let expected = Outcome::Draw;
+
+        assert_eq!(actual, expected);
+        assert_eq!(game.get_winner_seat(), None);
+    }
+
+    #[test]
+    fn test_set_player_kind__hands_seat_to_cpu() {
+        let mut game = Game::hot_seat(Variant::Classic);
+        game.human_play(0, 0);
+        game.set_player_kind(1, PlayerKind::Cpu);
+
+        assert!(game.is_cpu_turn());
+        game.cpu_play();
+        assert_eq!(game.history().len(), 2);
+    }
+
+    #[test]
+    fn test_get_winner_seat__when__second_seat_wins() {
+        let mut game = Game::hot_seat(Variant::Classic);
+        for (row, column) in [(0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (1, 2)] {
+            let value = game.current_seat().value;
+            game.play(Cell {
+                row,
+                column,
+                layer: 0,
+                value,
+            })
+            .unwrap();
+        }
+
+        let actual = game.get_winner_seat();
+        let expected = Some(1);
+
+        assert_eq!(actual, expected);
+        assert!(game.is_human_winner());
+    }
+
+    #[test]
+    fn test_with_position__when__cpu_has_to_block() {
+        let values = [2, 2, 0, 0, 0, 1, 0, 0, 0]
+            .iter()
+            .map(|&code| CellValue::from_code(code).unwrap())
+            .collect();
+        let mut game = Game::with_position(Variant::Classic, values).unwrap();
+        assert!(game.is_cpu_turn());
+
+        game.cpu_play();
+
+        let actual = game.board().values()[2];
+        let expected = CPU;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_restart__when__started_from_position() {
+        let values: Vec<CellValue> = [3, 2, 0, 0, 1, 0, 0, 0, 0]
+            .iter()
+            .map(|&code| CellValue::from_code(code).unwrap())
+            .collect();
+        let mut game = Game::with_position(Variant::Classic, values.clone()).unwrap();
+        let hint = game.hint().unwrap();
+        game.play(hint);
+        game.cpu_play();
+
+        game.restart();
+
+        assert_eq!(game.board().values(), values);
+        assert!(!game.is_cpu_turn());
+        assert!(game.history().is_empty());
+    }
+
+    #[test]
+    fn test_from_notation__keeps_player_to_move() {
+        let text = "x.o/.x./..o x";
+        let game = Game::from_notation(text).unwrap();
+
+        assert_eq!(game.current_seat().value, Human);
+        assert_eq!(game.to_notation(), Ok(text.to_string()));
+    }
+
+    #[test]
+    fn test_deserialize__is_inverse_of_serialize() {
+        let mut game = Game::with_players(Variant::FourByFour, 3);
+        game.set_topology(Topology::Torus);
+        game.human_play(0, 0);
+        game.paranoid_engine.time_budget_ms = 50.0;
+        game.cpu_play();
+
+        let json = serde_json::to_string(&game).unwrap();
+        let restored: Game = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.board().config(), game.board().config());
+        assert_eq!(restored.board().values(), game.board().values());
+        assert_eq!(restored.seats(), game.seats());
+        assert_eq!(restored.history(), game.history());
+        assert_eq!(restored.current_seat(), game.current_seat());
+    }
+
+    #[test]
+    fn test_deserialize__when__saved_before_player_kinds() {
+        let mut game = Game::new();
+        game.human_play(1, 1);
+        let mut json = serde_json::to_value(&game).unwrap();
+        for seat in json["seats"].as_array_mut().unwrap() {
+            let is_cpu = seat["kind"] == "Cpu";
+            let seat = seat.as_object_mut().unwrap();
+            seat.remove("kind");
+            seat.insert("is_cpu".to_string(), is_cpu.into());
+        }
+
+        let restored: Game = serde_json::from_value(json).unwrap();
+
+        assert_eq!(restored.seats(), game.seats());
+        assert!(restored.is_cpu_turn());
+    }
+
+    #[test]
+    fn test_deserialize__when__turn_out_of_range() {
+        let mut json = serde_json::to_value(Game::new()).unwrap();
+        json["turn"] = serde_json::json!(2);
+
+        let actual = serde_json::from_value::<Game>(json).is_err();
+
+        assert!(actual);
     }
 }
@@ -1,10 +1,33 @@
 #[macro_use]
 extern crate serde_derive;
 
+pub mod agent;
+#[cfg(feature = "server")]
+pub mod api;
 pub mod board;
+pub mod engine;
 pub mod evaluator;
 pub mod game;
+#[cfg(feature = "multiplayer")]
+pub mod lobby;
+pub mod menace;
+pub mod morris;
+pub mod network;
+pub mod notakto;
+pub mod notation;
+pub mod numerical;
+pub mod order_chaos;
+pub mod paranoid;
+pub mod protocol;
+pub mod qlearning;
+pub mod quantum;
+pub mod record;
+pub mod search;
+pub mod share;
+pub mod symmetry;
+pub mod tournament;
 pub mod utils;
+pub mod wild;
 
 use wasm_bindgen::prelude::*;
 
@@ -0,0 +1,687 @@
+use crate::board::{BoardConfig, Cell, CellValue, Outcome};
+use crate::game::{Game, Variant};
+use crate::notation;
+use crate::record;
+use crate::utils::now_ms;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// A connection to the lobby, handed out by `Lobby::connect`
+pub type ClientId = u64;
+
+// Letters and digits that can not be mistaken for each other when read out
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTVWXYZ23456789";
+const CODE_LENGTH: usize = 5;
+const TOKEN_LENGTH: usize = 16;
+
+/// How long a room is kept once no one is connected to it, for the players
+/// to come back to
+pub const ROOM_EXPIRY_MS: f64 = 10.0 * 60.0 * 1000.0;
+
+/// What a browser sends, as JSON with a `type` field
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ClientMessage {
+    /// Opens a room and takes its first seat. The variant is a name such as
+    /// `connect-four` or a board such as `6x7:4g` of at most
+    /// `board::MAX_CELLS` cells, classic when left out.
+    Create {
+        variant: Option<String>,
+    },
+    Join {
+        code: String,
+    },
+    /// Takes a seat back after losing the connection, with the token the
+    /// seat was given when it was first taken
+    Rejoin {
+        code: String,
+        token: String,
+    },
+    Move {
+        #[serde(rename = "move")]
+        coordinate: String,
+    },
+}
+
+/// What the lobby sends back, as JSON with a `type` field
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ServerMessage {
+    /// The seat taken, counting from 0 in the order of play, and the token
+    /// to take it back with
+    Joined {
+        code: String,
+        seat: usize,
+        token: String,
+    },
+    /// The whole game, sent whenever it changes and to anyone who takes a
+    /// seat, so a client never has to keep track of moves itself
+    State {
+        config: BoardConfig,
+        cells: Vec<CellValue>,
+        /// The seat to move
+        turn: usize,
+        outcome: Outcome,
+        history: Vec<String>,
+        /// Which seats have someone connected
+        connected: Vec<bool>,
+    },
+    OpponentLeft,
+    OpponentJoined,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LobbyError {
+    BadMessage(String),
+    BadVariant(String),
+    AlreadyInRoom,
+    NotInRoom,
+    NoSuchRoom(String),
+    RoomFull,
+    BadToken,
+    WaitingForOpponent,
+    NotYourTurn,
+    GameOver,
+    IllegalMove(String),
+    NoRandomness,
+}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LobbyError::BadMessage(e) => write!(f, "the message can not be read: {}", e),
+            LobbyError::BadVariant(v) => write!(f, "{} is not a variant or a board to play on", v),
+            LobbyError::AlreadyInRoom => write!(f, "you are already in a room"),
+            LobbyError::NotInRoom => write!(f, "you are not in a room"),
+            LobbyError::NoSuchRoom(code) => write!(f, "there is no room {}", code),
+            LobbyError::RoomFull => write!(f, "the room is full"),
+            LobbyError::BadToken => write!(f, "the token does not match a seat in the room"),
+            LobbyError::WaitingForOpponent => write!(f, "the other player has not joined yet"),
+            LobbyError::NotYourTurn => write!(f, "it is not your turn"),
+            LobbyError::GameOver => write!(f, "the game is over"),
+            LobbyError::IllegalMove(m) => write!(f, "{} can not be played", m),
+            LobbyError::NoRandomness => write!(f, "the server can not make a code right now"),
+        }
+    }
+}
+
+struct SeatHolder {
+    token: String,
+    client: Option<ClientId>,
+}
+
+struct Room {
+    game: Game,
+    seats: Vec<Option<SeatHolder>>,
+    // When the last client left, while no one is connected
+    empty_since: Option<f64>,
+}
+
+impl Room {
+    fn clients(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.seats
+            .iter()
+            .filter_map(|seat| seat.as_ref().and_then(|s| s.client))
+    }
+
+    fn state(&self) -> ServerMessage {
+        let config = self.game.board().config();
+        let to_move = self.game.current_seat().value;
+        ServerMessage::State {
+            config,
+            cells: self.game.board().values(),
+            turn: self
+                .game
+                .seats()
+                .iter()
+                .position(|seat| seat.value == to_move)
+                .unwrap(),
+            outcome: self.game.outcome(),
+            history: self
+                .game
+                .history()
+                .iter()
+                .map(|c| record::format_coordinate(config, c))
+                .collect(),
+            connected: self
+                .seats
+                .iter()
+                .map(|seat| seat.as_ref().is_some_and(|s| s.client.is_some()))
+                .collect(),
+        }
+    }
+}
+
+/// Rooms where two people play each other, with the server checking every
+/// move. It only deals in messages, so any transport can carry them: pass
+/// what a client sends to `receive` and deliver what comes back to the
+/// clients it names.
+pub struct Lobby {
+    rooms: HashMap<String, Room>,
+    // The room code and seat of every client that has one
+    clients: HashMap<ClientId, (String, usize)>,
+    next_client: ClientId,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby {
+            rooms: HashMap::new(),
+            clients: HashMap::new(),
+            next_client: 1,
+        }
+    }
+}
+
+impl Lobby {
+    pub fn connect(&mut self) -> ClientId {
+        let client = self.next_client;
+        self.next_client += 1;
+        client
+    }
+
+    /// Handles a message from a client and returns the messages to send out
+    pub fn receive(&mut self, client: ClientId, text: &str) -> Vec<(ClientId, ServerMessage)> {
+        let result = serde_json::from_str(text)
+            .map_err(|e| LobbyError::BadMessage(e.to_string()))
+            .and_then(|message| match message {
+                ClientMessage::Create { variant } => self.create(client, variant),
+                ClientMessage::Join { code } => self.join(client, &code),
+                ClientMessage::Rejoin { code, token } => self.rejoin(client, &code, &token),
+                ClientMessage::Move { coordinate } => self.play(client, &coordinate),
+            });
+
+        match result {
+            Ok(messages) => messages,
+            Err(e) => vec![(
+                client,
+                ServerMessage::Error {
+                    message: e.to_string(),
+                },
+            )],
+        }
+    }
+
+    /// Frees the seat of a client whose connection is gone, keeping it for
+    /// them to rejoin until the game is over or `expire_rooms` removes it
+    pub fn disconnect(&mut self, client: ClientId) -> Vec<(ClientId, ServerMessage)> {
+        let (code, seat) = match self.clients.remove(&client) {
+            Some(place) => place,
+            None => return vec![],
+        };
+        let room = self.rooms.get_mut(&code).unwrap();
+        if let Some(holder) = room.seats[seat].as_mut() {
+            holder.client = None;
+        }
+
+        let others: Vec<ClientId> = room.clients().collect();
+        if others.is_empty() && room.game.outcome() != Outcome::InProgress {
+            self.rooms.remove(&code);
+        } else if others.is_empty() {
+            room.empty_since = Some(now_ms());
+        }
+        others
+            .into_iter()
+            .map(|other| (other, ServerMessage::OpponentLeft))
+            .collect()
+    }
+
+    /// Removes the rooms no one has been connected to for `ROOM_EXPIRY_MS`
+    /// by `now`, in milliseconds since the Unix epoch
+    pub fn expire_rooms(&mut self, now: f64) {
+        self.rooms.retain(|_code, room| {
+            room.empty_since
+                .is_none_or(|since| now - since < ROOM_EXPIRY_MS)
+        });
+    }
+
+    fn create(
+        &mut self,
+        client: ClientId,
+        variant: Option<String>,
+    ) -> Result<Vec<(ClientId, ServerMessage)>, LobbyError> {
+        if self.clients.contains_key(&client) {
+            return Err(LobbyError::AlreadyInRoom);
+        }
+        let variant = variant.unwrap_or_else(|| "classic".to_string());
+        let config = match Variant::from_name(&variant) {
+            Some(variant) => variant.config(),
+            None => notation::parse_config(&variant)
+                .map_err(|_e| LobbyError::BadVariant(variant.clone()))?,
+        };
+
+        let code = loop {
+            let code = random_text(CODE_ALPHABET, CODE_LENGTH)?;
+            if !self.rooms.contains_key(&code) {
+                break code;
+            }
+        };
+        let room = Room {
+            game: Game::with_config(config),
+            seats: vec![None, None],
+            empty_since: None,
+        };
+        self.rooms.insert(code.clone(), room);
+        self.take_seat(client, &code, 0)
+    }
+
+    fn join(
+        &mut self,
+        client: ClientId,
+        code: &str,
+    ) -> Result<Vec<(ClientId, ServerMessage)>, LobbyError> {
+        if self.clients.contains_key(&client) {
+            return Err(LobbyError::AlreadyInRoom);
+        }
+        let code = code.trim().to_uppercase();
+        let room = self
+            .rooms
+            .get(&code)
+            .ok_or_else(|| LobbyError::NoSuchRoom(code.clone()))?;
+        let seat = room
+            .seats
+            .iter()
+            .position(|seat| seat.is_none())
+            .ok_or(LobbyError::RoomFull)?;
+
+        self.take_seat(client, &code, seat)
+    }
+
+    fn take_seat(
+        &mut self,
+        client: ClientId,
+        code: &str,
+        seat: usize,
+    ) -> Result<Vec<(ClientId, ServerMessage)>, LobbyError> {
+        let token = random_text(CODE_ALPHABET, TOKEN_LENGTH)?;
+        let room = self.rooms.get_mut(code).unwrap();
+        room.seats[seat] = Some(SeatHolder {
+            token: token.clone(),
+            client: Some(client),
+        });
+        room.empty_since = None;
+        self.clients.insert(client, (code.to_string(), seat));
+
+        let joined = ServerMessage::Joined {
+            code: code.to_string(),
+            seat,
+            token,
+        };
+        Ok(self.announce_arrival(client, code, joined))
+    }
+
+    fn rejoin(
+        &mut self,
+        client: ClientId,
+        code: &str,
+        token: &str,
+    ) -> Result<Vec<(ClientId, ServerMessage)>, LobbyError> {
+        if self.clients.contains_key(&client) {
+            return Err(LobbyError::AlreadyInRoom);
+        }
+        let code = code.trim().to_uppercase();
+        let room = self
+            .rooms
+            .get_mut(&code)
+            .ok_or_else(|| LobbyError::NoSuchRoom(code.clone()))?;
+        let seat = room
+            .seats
+            .iter()
+            .position(|seat| seat.as_ref().is_some_and(|s| s.token == token))
+            .ok_or(LobbyError::BadToken)?;
+
+        // A connection that is still open for the seat is replaced
+        room.empty_since = None;
+        let holder = room.seats[seat].as_mut().unwrap();
+        if let Some(old) = holder.client.replace(client) {
+            self.clients.remove(&old);
+        }
+        self.clients.insert(client, (code.clone(), seat));
+
+        let joined = ServerMessage::Joined {
+            code: code.clone(),
+            seat,
+            token: token.to_string(),
+        };
+        Ok(self.announce_arrival(client, &code, joined))
+    }
+
+    /// Tells a client which seat they have, the others that they are there,
+    /// and everyone the state of the game
+    fn announce_arrival(
+        &self,
+        client: ClientId,
+        code: &str,
+        joined: ServerMessage,
+    ) -> Vec<(ClientId, ServerMessage)> {
+        let room = &self.rooms[code];
+        let state = room.state();
+
+        let mut messages = vec![(client, joined)];
+        for other in room.clients().filter(|other| *other != client) {
+            messages.push((other, ServerMessage::OpponentJoined));
+        }
+        messages.extend(room.clients().map(|c| (c, state.clone())));
+        messages
+    }
+
+    fn play(
+        &mut self,
+        client: ClientId,
+        coordinate: &str,
+    ) -> Result<Vec<(ClientId, ServerMessage)>, LobbyError> {
+        let (code, seat) = self.clients.get(&client).ok_or(LobbyError::NotInRoom)?;
+        let room = self.rooms.get_mut(code).unwrap();
+        if room.seats.iter().any(|seat| seat.is_none()) {
+            return Err(LobbyError::WaitingForOpponent);
+        }
+        if room.game.outcome() != Outcome::InProgress {
+            return Err(LobbyError::GameOver);
+        }
+        let value = room.game.seats()[*seat].value;
+        if room.game.current_seat().value != value {
+            return Err(LobbyError::NotYourTurn);
+        }
+
+        let config = room.game.board().config();
+        let cell = match record::parse_coordinate(config, coordinate) {
+            // On boards with gravity a column letter is enough
+            None if config.gravity => column_drop(&room.game, coordinate),
+            cell => cell,
+        };
+        cell.and_then(|c| room.game.play(Cell { value, ..c }))
+            .ok_or_else(|| LobbyError::IllegalMove(coordinate.to_string()))?;
+
+        let state = room.state();
+        Ok(room.clients().map(|c| (c, state.clone())).collect())
+    }
+}
+
+/// Letters drawn from the operating system's randomness, so that no one can
+/// work out the token of another seat from the codes and tokens they see
+fn random_text(alphabet: &[u8], length: usize) -> Result<String, LobbyError> {
+    // Bytes past the last whole run of the alphabet are drawn again, so that
+    // every letter is as likely
+    let limit = 256 - 256 % alphabet.len();
+    let mut text = String::with_capacity(length);
+    let mut bytes = [0u8; 32];
+    while text.len() < length {
+        getrandom::getrandom(&mut bytes).map_err(|_e| LobbyError::NoRandomness)?;
+        for &byte in bytes.iter().filter(|&&byte| (byte as usize) < limit) {
+            if text.len() < length {
+                text.push(alphabet[byte as usize % alphabet.len()] as char);
+            }
+        }
+    }
+    Ok(text)
+}
+
+fn column_drop(game: &Game, text: &str) -> Option<Cell> {
+    let letter = text.trim().to_lowercase().bytes().next()?;
+    let column = letter.checked_sub(b'a')?;
+    game.board()
+        .legal_moves()
+        .into_iter()
+        .find(|c| (c.column, c.layer) == (column, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::Outcome;
+    use crate::lobby::{
+        random_text, ClientId, Lobby, ServerMessage, CODE_ALPHABET, ROOM_EXPIRY_MS, TOKEN_LENGTH,
+    };
+    use crate::utils::now_ms;
+
+    fn joined(messages: &[(ClientId, ServerMessage)]) -> (String, String) {
+        match &messages[0].1 {
+            ServerMessage::Joined { code, token, .. } => (code.clone(), token.clone()),
+            message => panic!("expected to join, got {:?}", message),
+        }
+    }
+
+    /// A room with two players in it, and the code and token of each seat
+    fn full_room() -> (Lobby, ClientId, ClientId, String, String) {
+        let mut lobby = Lobby::default();
+        let (first, second) = (lobby.connect(), lobby.connect());
+        let (code, first_token) = joined(&lobby.receive(first, r#"{"type": "create"}"#));
+        let join = format!(r#"{{"type": "join", "code": "{}"}}"#, code.to_lowercase());
+        lobby.receive(second, &join);
+        (lobby, first, second, code, first_token)
+    }
+
+    fn play(
+        lobby: &mut Lobby,
+        client: ClientId,
+        coordinate: &str,
+    ) -> Vec<(ClientId, ServerMessage)> {
+        let text = format!(r#"{{"type": "move", "move": "{}"}}"#, coordinate);
+        lobby.receive(client, &text)
+    }
+
+    #[test]
+    fn test_receive__join_sends_state_to_both() {
+        let mut lobby = Lobby::default();
+        let (first, second) = (lobby.connect(), lobby.connect());
+        let (code, _token) = joined(&lobby.receive(first, r#"{"type": "create"}"#));
+
+        let join = format!(r#"{{"type": "join", "code": "{}"}}"#, code);
+        let messages = lobby.receive(second, &join);
+
+        let actual: Vec<ClientId> = messages
+            .iter()
+            .filter(|(_client, message)| matches!(message, ServerMessage::State { .. }))
+            .map(|(client, _message)| *client)
+            .collect();
+        let expected = vec![first, second];
+
+        assert_eq!(actual, expected);
+        assert_eq!(messages[1], (first, ServerMessage::OpponentJoined));
+    }
+
+    #[test]
+    fn test_receive__move_is_broadcast() {
+        let (mut lobby, first, second, _code, _token) = full_room();
+
+        let messages = play(&mut lobby, first, "b2");
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|(_client, message)| matches!(
+            message,
+            ServerMessage::State { turn: 1, history, .. } if history == &["b2"]
+        )));
+        assert_eq!(messages[1].0, second);
+    }
+
+    #[test]
+    fn test_receive__when__not_your_turn() {
+        let (mut lobby, _first, second, _code, _token) = full_room();
+
+        let actual = play(&mut lobby, second, "b2");
+        let expected = vec![(
+            second,
+            ServerMessage::Error {
+                message: "it is not your turn".to_string(),
+            },
+        )];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_receive__when__cell_taken() {
+        let (mut lobby, first, second, _code, _token) = full_room();
+        play(&mut lobby, first, "b2");
+
+        let actual = play(&mut lobby, second, "b2");
+
+        assert!(matches!(actual[..], [(_, ServerMessage::Error { .. })]));
+    }
+
+    #[test]
+    fn test_receive__when__waiting_for_opponent() {
+        let mut lobby = Lobby::default();
+        let first = lobby.connect();
+        lobby.receive(first, r#"{"type": "create", "variant": "connect-four"}"#);
+
+        let actual = play(&mut lobby, first, "d");
+
+        assert!(matches!(actual[..], [(_, ServerMessage::Error { .. })]));
+    }
+
+    #[test]
+    fn test_receive__when__board_can_not_be_played() {
+        let mut lobby = Lobby::default();
+        let first = lobby.connect();
+
+        let actual: Vec<Vec<(ClientId, ServerMessage)>> = ["3x3:0", "50x50x50:3"]
+            .iter()
+            .map(|variant| {
+                let create = format!(r#"{{"type": "create", "variant": "{}"}}"#, variant);
+                lobby.receive(first, &create)
+            })
+            .collect();
+        let expected = vec![
+            vec![(
+                first,
+                ServerMessage::Error {
+                    message: "3x3:0 is not a variant or a board to play on".to_string(),
+                },
+            )],
+            vec![(
+                first,
+                ServerMessage::Error {
+                    message: "50x50x50:3 is not a variant or a board to play on".to_string(),
+                },
+            )],
+        ];
+
+        assert_eq!(actual, expected);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    #[test]
+    fn test_random_text__when__drawn_twice() {
+        let first = random_text(CODE_ALPHABET, TOKEN_LENGTH).unwrap();
+        let second = random_text(CODE_ALPHABET, TOKEN_LENGTH).unwrap();
+
+        assert_eq!(first.len(), TOKEN_LENGTH);
+        assert!(first.bytes().all(|letter| CODE_ALPHABET.contains(&letter)));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_receive__when__room_full() {
+        let (mut lobby, _first, _second, code, _token) = full_room();
+        let third = lobby.connect();
+
+        let join = format!(r#"{{"type": "join", "code": "{}"}}"#, code);
+        let actual = lobby.receive(third, &join);
+        let expected = vec![(
+            third,
+            ServerMessage::Error {
+                message: "the room is full".to_string(),
+            },
+        )];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_rejoin__resyncs_state() {
+        let (mut lobby, first, second, code, token) = full_room();
+        play(&mut lobby, first, "b2");
+
+        let left = lobby.disconnect(first);
+        let returned = lobby.connect();
+        let rejoin = format!(
+            r#"{{"type": "rejoin", "code": "{}", "token": "{}"}}"#,
+            code, token
+        );
+        let messages = lobby.receive(returned, &rejoin);
+
+        assert_eq!(left, vec![(second, ServerMessage::OpponentLeft)]);
+        assert!(messages.contains(&(second, ServerMessage::OpponentJoined)));
+        assert!(messages.iter().any(|(client, message)| *client == returned
+            && matches!(message, ServerMessage::State { history, .. } if history == &["b2"])));
+        assert_eq!(play(&mut lobby, second, "a1").len(), 2);
+    }
+
+    #[test]
+    fn test_rejoin__when__bad_token() {
+        let (mut lobby, first, _second, code, _token) = full_room();
+        lobby.disconnect(first);
+        let returned = lobby.connect();
+
+        let rejoin = format!(r#"{{"type": "rejoin", "code": "{}", "token": "x"}}"#, code);
+        let actual = lobby.receive(returned, &rejoin);
+
+        assert!(matches!(actual[..], [(_, ServerMessage::Error { .. })]));
+    }
+
+    #[test]
+    fn test_expire_rooms__when__everyone_left() {
+        let (mut lobby, first, second, code, token) = full_room();
+        play(&mut lobby, first, "b2");
+        lobby.disconnect(first);
+        lobby.disconnect(second);
+
+        lobby.expire_rooms(now_ms());
+        let is_kept = lobby.rooms.contains_key(&code);
+        lobby.expire_rooms(now_ms() + ROOM_EXPIRY_MS);
+
+        let returned = lobby.connect();
+        let rejoin = format!(
+            r#"{{"type": "rejoin", "code": "{}", "token": "{}"}}"#,
+            code, token
+        );
+        let actual = lobby.receive(returned, &rejoin);
+        let expected = vec![(
+            returned,
+            ServerMessage::Error {
+                message: format!("there is no room {}", code),
+            },
+        )];
+
+        assert!(is_kept);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_expire_rooms__when__someone_connected() {
+        let (mut lobby, _first, second, code, _token) = full_room();
+        lobby.disconnect(second);
+
+        lobby.expire_rooms(now_ms() + ROOM_EXPIRY_MS);
+
+        assert!(lobby.rooms.contains_key(&code));
+    }
+
+    #[test]
+    fn test_receive__game_to_the_end() {
+        let (mut lobby, first, second, _code, _token) = full_room();
+        for (client, coordinate) in [(first, "a1"), (second, "a2"), (first, "b1"), (second, "b2")] {
+            play(&mut lobby, client, coordinate);
+        }
+
+        let messages = play(&mut lobby, first, "c1");
+
+        assert!(matches!(
+            &messages[0].1,
+            ServerMessage::State {
+                outcome: Outcome::Winner(_),
+                ..
+            }
+        ));
+        assert!(matches!(
+            play(&mut lobby, second, "c2")[..],
+            [(_, ServerMessage::Error { .. })]
+        ));
+    }
+}
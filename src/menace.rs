@@ -0,0 +1,321 @@
+use crate::agent::Agent;
+use crate::board::CellValue::Human;
+use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome};
+use crate::symmetry;
+use crate::utils::{self, Random};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Beads added to a move after a game, by how the game ended
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Rewards {
+    pub win: i32,
+    pub draw: i32,
+    pub loss: i32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            win: 3,
+            draw: 1,
+            loss: -1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MenaceError {
+    /// Only square boards without gravity have the symmetries it relies on
+    UnsupportedBoard,
+    BadState(String),
+}
+
+impl fmt::Display for MenaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MenaceError::UnsupportedBoard => write!(f, "only flat square boards can be learned"),
+            MenaceError::BadState(e) => write!(f, "bad learned state: {}", e),
+        }
+    }
+}
+
+/// The matchbox learner: a box of beads for every position it has had to
+/// move in, one colour of bead for every move. It draws a bead to pick its
+/// move, and after the game adds beads to the moves that led to a win and
+/// takes them away from the moves that led to a loss, so it loses less the
+/// more it plays.
+///
+/// Positions are stored once for all their rotations and reflections, with
+/// the marks of the player to move as `x`, so the same boxes serve both
+/// seats.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Menace {
+    config: BoardConfig,
+    rewards: Rewards,
+    /// Beads for every position of the board in its canonical form, by
+    /// position
+    boxes: BTreeMap<String, Vec<u32>>,
+    random: Random,
+    // The box, bead and seat of every move of the game being played
+    #[serde(skip)]
+    moves: Vec<(String, usize, CellValue)>,
+}
+
+impl Menace {
+    pub fn new(config: BoardConfig, seed: u64) -> Result<Menace, MenaceError> {
+        if !symmetry::has_symmetries(config) {
+            return Err(MenaceError::UnsupportedBoard);
+        }
+
+        Ok(Menace {
+            config,
+            rewards: Rewards::default(),
+            boxes: BTreeMap::new(),
+            random: Random::new(seed),
+            moves: vec![],
+        })
+    }
+
+    pub fn with_rewards(mut self, rewards: Rewards) -> Menace {
+        self.rewards = rewards;
+        self
+    }
+
+    pub fn from_json(json: &str) -> Result<Menace, MenaceError> {
+        let menace: Menace = utils::from_json(json).map_err(MenaceError::BadState)?;
+        // Check the board can be learned, as `new` does
+        Menace::new(menace.config, 1)?;
+
+        // Every box needs a bead to draw, and only beads for empty cells
+        let size = menace.config.size();
+        if let Some((key, _beads)) = menace.boxes.iter().find(|(k, beads)| {
+            let cells: Vec<char> = k.chars().collect();
+            cells.len() != size
+                || beads.len() != size
+                || !cells.iter().all(|&c| ".xo".contains(c))
+                || cells
+                    .iter()
+                    .zip(beads.iter())
+                    .any(|(&c, &n)| c != '.' && n > 0)
+                || beads.iter().all(|&n| n == 0)
+        }) {
+            return Err(MenaceError::BadState(format!("bad box {}", key)));
+        }
+        Ok(menace)
+    }
+
+    pub fn to_json(&self) -> String {
+        utils::to_json(self, false)
+    }
+
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
+
+    /// Positions it has a box for
+    pub fn positions(&self) -> usize {
+        self.boxes.len()
+    }
+
+    /// Plays a game against itself and learns from both sides of it
+    pub fn play_self(&mut self) -> Outcome {
+        let mut board = Board::new(self.config);
+        let mut value = Human;
+        while board.outcome() == Outcome::InProgress {
+            let cell = self.choose(&board, value).unwrap();
+            board.set(cell).unwrap();
+            value = value.opponent();
+        }
+
+        let outcome = board.outcome();
+        self.finish(outcome);
+        outcome
+    }
+
+    /// Beads a new box starts with for every move, fewer later in the game
+    /// so that later moves are learned faster
+    fn initial_beads(&self, key: &str) -> u32 {
+        let marks = key.chars().filter(|&c| c != '.').count() as u32;
+        4u32.saturating_sub(marks / 2).max(1)
+    }
+}
+
+impl Agent for Menace {
+    fn name(&self) -> String {
+        "menace".to_string()
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        if board.config() != self.config || board.outcome() != Outcome::InProgress {
+            return None;
+        }
+
+        let (key, orientation) = symmetry::canonical(self.config, &board.values(), value);
+        let initial = self.initial_beads(&key);
+        let beads = self.boxes.entry(key.clone()).or_insert_with(|| {
+            key.chars()
+                .map(|c| if c == '.' { initial } else { 0 })
+                .collect()
+        });
+
+        let total: u32 = beads.iter().sum();
+        let mut draw = self.random.below(total as usize) as u32;
+        let bead = beads
+            .iter()
+            .position(|&count| {
+                let is_drawn = draw < count;
+                draw = draw.saturating_sub(count);
+                is_drawn
+            })
+            .unwrap();
+        self.moves.push((key, bead, value));
+
+        let index = symmetry::untransform(self.config, orientation, bead);
+        let (row, column, layer) = self.config.coordinates(index);
+        Some(Cell {
+            row,
+            column,
+            layer,
+            value,
+        })
+    }
+
+    /// Adds and takes away beads for the moves of the game. A move always
+    /// keeps one bead, so no box ever runs out.
+    fn finish(&mut self, outcome: Outcome) {
+        for (key, bead, value) in self.moves.drain(..) {
+            let reward = match outcome {
+                Outcome::Winner(winner) if winner == value => self.rewards.win,
+                Outcome::Winner(_winner) => self.rewards.loss,
+                _ => self.rewards.draw,
+            };
+            if let Some(beads) = self.boxes.get_mut(&key) {
+                beads[bead] = (beads[bead] as i32 + reward).max(1) as u32;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::agent::{Agent, RandomAgent};
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome};
+    use crate::menace::{Menace, MenaceError};
+    use crate::tournament::play_match;
+
+    fn cell(row: u8, column: u8, value: CellValue) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_new__when__board_has_gravity() {
+        let actual = Menace::new(BoardConfig::connect_four(), 1).err();
+        let expected = Some(MenaceError::UnsupportedBoard);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_choose__shares_box_between_symmetries() {
+        let mut menace = Menace::new(BoardConfig::default(), 1).unwrap();
+        let corners = [(0, 0), (0, 2), (2, 0), (2, 2)];
+
+        for &(row, column) in &corners {
+            let mut board = Board::default();
+            board.set(cell(row, column, Human)).unwrap();
+            menace.choose(&board, CPU).unwrap();
+        }
+        let actual = menace.positions();
+        let expected = 1;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_choose__plays_legal_move_on_rotated_board() {
+        let mut menace = Menace::new(BoardConfig::default(), 5).unwrap();
+        let mut board = Board::default();
+        board.set(cell(0, 2, Human)).unwrap();
+        board.set(cell(1, 1, CPU)).unwrap();
+        board.set(cell(2, 0, Human)).unwrap();
+
+        let actual = (0..20).all(|_i| {
+            let chosen = menace.choose(&board, CPU).unwrap();
+            board.clone().set(chosen).is_some()
+        });
+
+        assert!(actual);
+    }
+
+    #[test]
+    fn test_finish__rewards_winning_moves() {
+        let mut menace = Menace::new(BoardConfig::default(), 1).unwrap();
+        let board = Board::default();
+        menace.choose(&board, Human).unwrap();
+        menace.finish(Outcome::Winner(Human));
+
+        let actual: u32 = menace.boxes.values().next().unwrap().iter().sum();
+        let expected = 9 * 4 + 3;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_to_json__round_trip() {
+        let mut menace = Menace::new(BoardConfig::default(), 1).unwrap();
+        for _game in 0..10 {
+            menace.play_self();
+        }
+
+        let restored = Menace::from_json(&menace.to_json()).unwrap();
+
+        assert_eq!(restored.boxes, menace.boxes);
+        assert!(Menace::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_from_json__when__bad_box() {
+        let boxes = [
+            ("", vec![]),
+            (".........", vec![0; 9]),
+            (".........", vec![1; 8]),
+            ("........?", vec![1; 9]),
+            ("x........", vec![1; 9]),
+        ];
+
+        for (key, beads) in boxes.iter() {
+            let mut menace = Menace::new(BoardConfig::default(), 1).unwrap();
+            menace.boxes.insert(key.to_string(), beads.clone());
+
+            let actual = Menace::from_json(&menace.to_json()).err();
+            let expected = Some(MenaceError::BadState(format!("bad box {}", key)));
+
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_play_self__learns_to_beat_random_play() {
+        let config = BoardConfig::default();
+        let mut menace = Menace::new(config, 1).unwrap();
+        let before = play_match(config, &mut menace.clone(), &mut RandomAgent::new(2), 100);
+
+        for _game in 0..1000 {
+            menace.play_self();
+        }
+        let after = play_match(config, &mut menace.clone(), &mut RandomAgent::new(2), 100);
+
+        assert!(after.wins > before.wins);
+        assert!(after.losses < before.losses);
+    }
+}
@@ -0,0 +1,539 @@
+use crate::board::CellValue::{Human, CPU};
+use crate::board::{Board, Cell, CellValue, Move, Outcome};
+
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+// Marks each player has
+const PIECES: usize = 3;
+
+// The game can go on forever so the computer only searches this deep
+const SEARCH_DEPTH: u8 = 6;
+const WIN_SCORE: i32 = 1000;
+
+// Times a position has to occur for the game to be drawn
+const REPETITIONS: usize = 3;
+
+/// What happens once a player has all of their marks on the board
+#[wasm_bindgen]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MorrisRule {
+    /// A mark is moved to an empty neighbouring cell, as in three men's morris
+    Slide,
+    /// A new mark is placed and the player's oldest mark is taken away
+    Vanish,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MorrisError {
+    GameOver,
+    IllegalMove,
+}
+
+impl fmt::Display for MorrisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MorrisError::GameOver => write!(f, "the game is over"),
+            MorrisError::IllegalMove => write!(f, "the move is not allowed"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Position {
+    values: Vec<CellValue>,
+    to_move: CellValue,
+    // Only matters when the oldest mark vanishes
+    order: Vec<usize>,
+}
+
+/// Tic-tac-toe where each player only has three marks
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct MorrisGame {
+    board: Board,
+    rule: MorrisRule,
+    // Marks on the board, oldest first
+    order: Vec<Cell>,
+    positions: Vec<Position>,
+    to_move: CellValue,
+}
+
+impl Default for MorrisGame {
+    fn default() -> Self {
+        MorrisGame::with_rule(MorrisRule::Slide)
+    }
+}
+
+impl MorrisGame {
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_player(&self) -> CellValue {
+        self.to_move
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.outcome() != Outcome::InProgress {
+            return vec![];
+        }
+        self.moves_of(self.to_move)
+    }
+
+    pub fn play(&mut self, mv: Move) -> Result<(), MorrisError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(MorrisError::GameOver);
+        }
+        if !self.moves_of(self.to_move).contains(&mv) {
+            return Err(MorrisError::IllegalMove);
+        }
+
+        self.board
+            .apply_move(mv.clone())
+            .ok_or(MorrisError::IllegalMove)?;
+        match mv {
+            Move::Place(cell) => self.order.push(cell),
+            Move::Step { from, to } => {
+                self.order.retain(|c| *c != from);
+                self.order.push(to);
+            }
+        }
+
+        self.to_move = self.to_move.opponent();
+        self.positions.push(self.position());
+        Ok(())
+    }
+
+    /// A player who cannot move loses, and the game is drawn when the same
+    /// position comes up for the third time
+    pub fn outcome(&self) -> Outcome {
+        if let Some(winner) = self.board.winner() {
+            return Outcome::Winner(winner);
+        }
+
+        let current = self.position();
+        let occurrences = self.positions.iter().filter(|p| **p == current).count();
+        if occurrences >= REPETITIONS {
+            return Outcome::Draw;
+        }
+
+        if self.moves_of(self.to_move).is_empty() {
+            return Outcome::Winner(self.to_move.opponent());
+        }
+
+        Outcome::InProgress
+    }
+
+    /// Whether the current position has come up before
+    pub fn is_repetition(&self) -> bool {
+        let current = self.position();
+        self.positions.iter().filter(|p| **p == current).count() > 1
+    }
+
+    fn moves_of(&self, player: CellValue) -> Vec<Move> {
+        let empty_cells: Vec<Cell> = self
+            .board
+            .get_cells()
+            .into_iter()
+            .filter(Cell::is_empty)
+            .map(|c| Cell { value: player, ..c })
+            .collect();
+        let own_marks: Vec<Cell> = self
+            .order
+            .iter()
+            .filter(|c| c.value == player)
+            .cloned()
+            .collect();
+
+        if own_marks.len() < PIECES {
+            return empty_cells.into_iter().map(Move::Place).collect();
+        }
+
+        match self.rule {
+            MorrisRule::Slide => own_marks
+                .iter()
+                .flat_map(|from| {
+                    empty_cells
+                        .iter()
+                        .filter(move |to| is_neighbour(from, to))
+                        .map(move |to| Move::Step {
+                            from: from.clone(),
+                            to: to.clone(),
+                        })
+                })
+                .collect(),
+            MorrisRule::Vanish => empty_cells
+                .into_iter()
+                .map(|to| Move::Step {
+                    from: own_marks[0].clone(),
+                    to,
+                })
+                .collect(),
+        }
+    }
+
+    fn position(&self) -> Position {
+        let config = self.board.config();
+        let order = match self.rule {
+            MorrisRule::Slide => vec![],
+            MorrisRule::Vanish => self
+                .order
+                .iter()
+                .filter_map(|c| config.index(c.row, c.column, c.layer))
+                .collect(),
+        };
+
+        Position {
+            values: self.board.values(),
+            to_move: self.to_move,
+            order,
+        }
+    }
+
+    fn cell(&self, row: u8, column: u8) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value: self.to_move,
+        }
+    }
+}
+
+/// Cells next to each other on a line. The diagonals only go through the
+/// corners and the middle cell.
+fn is_neighbour(a: &Cell, b: &Cell) -> bool {
+    let row_distance = (a.row as i8 - b.row as i8).abs();
+    let column_distance = (a.column as i8 - b.column as i8).abs();
+    let is_diagonal = row_distance == 1 && column_distance == 1;
+    let is_middle = |c: &Cell| (c.row, c.column) == (1, 1);
+
+    row_distance.max(column_distance) == 1 && (!is_diagonal || is_middle(a) || is_middle(b))
+}
+
+#[wasm_bindgen]
+impl MorrisGame {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> MorrisGame {
+        MorrisGame::default()
+    }
+
+    #[wasm_bindgen(js_name = withRule)]
+    pub fn with_rule(rule: MorrisRule) -> MorrisGame {
+        let mut game = MorrisGame {
+            board: Board::default(),
+            rule,
+            order: vec![],
+            positions: vec![],
+            to_move: Human,
+        };
+        game.positions.push(game.position());
+        game
+    }
+
+    #[wasm_bindgen(js_name = getCells)]
+    pub fn get_cells(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.get_cells()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getLegalMoves)]
+    pub fn get_legal_moves(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.legal_moves()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = humanPlace)]
+    pub fn human_place(&mut self, row: u8, column: u8) -> Result<(), JsValue> {
+        let mv = Move::Place(self.cell(row, column));
+        self.play(mv).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = humanMove)]
+    pub fn human_move(
+        &mut self,
+        from_row: u8,
+        from_column: u8,
+        to_row: u8,
+        to_column: u8,
+    ) -> Result<(), JsValue> {
+        let mv = Move::Step {
+            from: self.cell(from_row, from_column),
+            to: self.cell(to_row, to_column),
+        };
+        self.play(mv).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        if self.to_move != CPU {
+            return;
+        }
+        if let Some(mv) = find_best_move(self) {
+            self.play(mv).unwrap();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = MorrisGame::with_rule(self.rule);
+    }
+}
+
+/// Best move for the player to move, where a position that has come up
+/// before is scored as a draw
+pub fn find_best_move(game: &MorrisGame) -> Option<Move> {
+    let mut alpha = -WIN_SCORE - 1;
+    let mut best_move = None;
+
+    for mv in game.legal_moves() {
+        let mut next = game.clone();
+        next.play(mv.clone()).unwrap();
+
+        let score = -negamax(&next, SEARCH_DEPTH - 1, 1, -WIN_SCORE - 1, -alpha);
+        if best_move.is_none() || score > alpha {
+            alpha = score;
+            best_move = Some(mv);
+        }
+    }
+
+    best_move
+}
+
+fn negamax(game: &MorrisGame, depth: u8, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+    let side = game.current_player();
+    match game.outcome() {
+        Outcome::Winner(winner) if winner == side => return WIN_SCORE - ply as i32,
+        Outcome::Winner(_) => return -(WIN_SCORE - ply as i32),
+        Outcome::Draw => return 0,
+        Outcome::InProgress => {}
+    }
+    if game.is_repetition() {
+        return 0;
+    }
+    if depth == 0 {
+        return evaluate(game);
+    }
+
+    for mv in game.legal_moves() {
+        let mut next = game.clone();
+        next.play(mv).unwrap();
+
+        let score = -negamax(&next, depth - 1, ply + 1, -beta, -alpha);
+        if score > alpha {
+            alpha = score;
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    alpha
+}
+
+/// Lines with two of the player's marks and none of the opponent's count
+/// the most
+fn evaluate(game: &MorrisGame) -> i32 {
+    let side = game.current_player();
+    let values = game.board().values();
+
+    game.board()
+        .win_lines()
+        .iter()
+        .map(|line| {
+            let own = line.iter().filter(|&&i| values[i] == side).count() as i32;
+            let other = line
+                .iter()
+                .filter(|&&i| values[i] == side.opponent())
+                .count() as i32;
+            match (own, other) {
+                (own, 0) => own * own,
+                (0, other) => -other * other,
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, Cell, CellValue, Move, Outcome};
+    use crate::morris::{find_best_move, MorrisError, MorrisGame, MorrisRule};
+
+    fn cell(row: u8, column: u8, value: CellValue) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        }
+    }
+
+    fn step(from: (u8, u8), to: (u8, u8), value: CellValue) -> Move {
+        Move::Step {
+            from: cell(from.0, from.1, value),
+            to: cell(to.0, to.1, value),
+        }
+    }
+
+    fn game_with(
+        rule: MorrisRule,
+        human: &[(u8, u8)],
+        cpu: &[(u8, u8)],
+        to_move: CellValue,
+    ) -> MorrisGame {
+        let mut game = MorrisGame::with_rule(rule);
+        let mut board = Board::default();
+        for &(row, column) in human {
+            board.set_human(row, column);
+            game.order.push(cell(row, column, Human));
+        }
+        for &(row, column) in cpu {
+            board.set_cpu(row, column);
+            game.order.push(cell(row, column, CPU));
+        }
+        game.board = board;
+        game.to_move = to_move;
+        game.positions = vec![game.position()];
+        game
+    }
+
+    #[test]
+    fn test_legal_moves__when__placing() {
+        let game = MorrisGame::default();
+
+        let actual = game.legal_moves().len();
+        let expected = 9;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play__when__slide_not_to_neighbour() {
+        let mut game = game_with(
+            MorrisRule::Slide,
+            &[(0, 0), (1, 2), (2, 1)],
+            &[(0, 1), (1, 0), (2, 2)],
+            Human,
+        );
+
+        let actual = game.play(step((0, 0), (2, 0), Human));
+        let expected = Err(MorrisError::IllegalMove);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play__when__slide_along_no_line() {
+        let mut game = game_with(
+            MorrisRule::Slide,
+            &[(0, 1), (1, 2), (2, 2)],
+            &[(0, 0), (0, 2), (2, 1)],
+            Human,
+        );
+
+        let actual = game.play(step((0, 1), (1, 0), Human));
+        let expected = Err(MorrisError::IllegalMove);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play__when__placing_after_all_marks_placed() {
+        let mut game = game_with(
+            MorrisRule::Slide,
+            &[(0, 0), (1, 2), (2, 1)],
+            &[(0, 1), (1, 0), (2, 2)],
+            Human,
+        );
+
+        let actual = game.play(Move::Place(cell(1, 1, Human)));
+        let expected = Err(MorrisError::IllegalMove);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_legal_moves__when__vanish_takes_oldest() {
+        let game = game_with(
+            MorrisRule::Vanish,
+            &[(0, 0), (1, 2), (2, 1)],
+            &[(0, 1), (1, 0), (2, 2)],
+            Human,
+        );
+
+        let actual = game.legal_moves();
+        let expected: Vec<Move> = vec![(0, 2), (1, 1), (2, 0)]
+            .into_iter()
+            .map(|to| step((0, 0), to, Human))
+            .collect();
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__repeated_three_times() {
+        let mut game = game_with(
+            MorrisRule::Slide,
+            &[(0, 0), (1, 2), (2, 1)],
+            &[(0, 1), (1, 0), (2, 2)],
+            Human,
+        );
+
+        for _ in 0..2 {
+            game.play(step((0, 0), (1, 1), Human)).unwrap();
+            game.play(step((1, 0), (2, 0), CPU)).unwrap();
+            game.play(step((1, 1), (0, 0), Human)).unwrap();
+            assert_eq!(game.outcome(), Outcome::InProgress);
+            game.play(step((2, 0), (1, 0), CPU)).unwrap();
+        }
+
+        let actual = game.outcome();
+        let expected = Outcome::Draw;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__winning_step() {
+        let mut game = MorrisGame::default();
+        let placements = vec![
+            (1, 0, Human),
+            (0, 0, CPU),
+            (2, 1, Human),
+            (0, 1, CPU),
+            (0, 2, Human),
+            (1, 2, CPU),
+        ];
+        for (row, column, value) in placements {
+            game.play(Move::Place(cell(row, column, value))).unwrap();
+        }
+        game.play(step((0, 2), (1, 1), Human)).unwrap();
+
+        let actual = find_best_move(&game);
+        let expected = Some(step((1, 2), (0, 2), CPU));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__loss_at_risk() {
+        let mut game = MorrisGame::default();
+        game.play(Move::Place(cell(0, 0, Human))).unwrap();
+        game.play(Move::Place(cell(1, 1, CPU))).unwrap();
+        game.play(Move::Place(cell(0, 1, Human))).unwrap();
+
+        let actual = find_best_move(&game);
+        let expected = Some(Move::Place(cell(0, 2, CPU)));
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,608 @@
+use crate::board::CellValue::{Blocked, Empty, Human, CPU};
+use crate::board::{Board, BoardConfig, CellValue, Outcome, Topology};
+use crate::engine::Engine;
+use crate::utils::Random;
+
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"TTTN";
+const VERSION: u8 = 1;
+// Magic, version, the six fields of the board and the hidden units
+const HEADER_LENGTH: usize = 4 + 1 + 6 + 2;
+
+/// The most hidden units a network can have, as its file holds the count in
+/// two bytes
+pub const MAX_HIDDEN: usize = u16::MAX as usize;
+
+// Moves at the start of a self-play game that are played at random, so that
+// the games do not all go the same way
+const OPENING_PLIES: usize = 2;
+// One in this many later moves of a self-play game is played at random
+const RANDOM_MOVE_ODDS: usize = 10;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum NetworkError {
+    BadWeights(String),
+    /// The network or the samples are for another board
+    WrongBoard,
+    BadSample(String),
+    /// Hidden units outside 1 to `MAX_HIDDEN`
+    BadHidden(usize),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::BadWeights(e) => write!(f, "bad network weights: {}", e),
+            NetworkError::WrongBoard => write!(f, "the network was trained on another board"),
+            NetworkError::BadSample(position) => write!(f, "bad sample {}", position),
+            NetworkError::BadHidden(hidden) => write!(
+                f,
+                "a network has from 1 to {} hidden units, not {}",
+                MAX_HIDDEN, hidden
+            ),
+        }
+    }
+}
+
+/// A position from a self-play game, as the network learns from it
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Sample {
+    /// The board as seen by the player to move, with their marks as `x`,
+    /// the other marks as `o`, blocked cells as `#` and empty cells as `.`
+    pub position: String,
+    /// Board index of the move that was played
+    pub played: usize,
+    /// How the game ended for the player to move, 1 for a win, 0 for a draw
+    /// and -1 for a loss
+    pub result: f32,
+}
+
+impl Sample {
+    pub fn new(values: &[CellValue], side: CellValue, played: usize, result: f32) -> Sample {
+        let position = values
+            .iter()
+            .map(|&v| match v {
+                Empty => '.',
+                Blocked => '#',
+                v if v == side => 'x',
+                _ => 'o',
+            })
+            .collect();
+        Sample {
+            position,
+            played,
+            result,
+        }
+    }
+
+    /// The board of the sample, with `Human` to move
+    pub fn values(&self) -> Vec<CellValue> {
+        self.position
+            .chars()
+            .map(|c| match c {
+                'x' => Human,
+                'o' => CPU,
+                '#' => Blocked,
+                _ => Empty,
+            })
+            .collect()
+    }
+}
+
+/// A small neural network that looks at a board from the side of the player
+/// to move and tells how good the position is for them and which moves look
+/// best. `Engine` uses it, when it is set, to score the positions where its
+/// search stops and to pick which moves to search first. `Evaluator` does
+/// not, as it searches the classic board to the end and has nothing to judge.
+///
+/// Every cell is two inputs, one for a mark of the player to move and one
+/// for a mark of the other player. They feed one hidden layer of rectified
+/// units, which feeds a value between -1 and 1 and a chance for every cell.
+///
+/// The weights are saved with `to_bytes` in a few kilobytes, so a network
+/// trained offline can be built into the wasm module with `include_bytes!`
+/// and loaded with `from_bytes`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Network {
+    config: BoardConfig,
+    hidden: usize,
+    // A row of weights from the inputs for every hidden unit
+    hidden_weights: Vec<f32>,
+    hidden_biases: Vec<f32>,
+    value_weights: Vec<f32>,
+    value_bias: f32,
+    // A row of weights from the hidden units for every cell
+    policy_weights: Vec<f32>,
+    policy_biases: Vec<f32>,
+}
+
+impl Network {
+    /// A network with random weights, to be trained
+    pub fn new(config: BoardConfig, hidden: usize, seed: u64) -> Result<Network, NetworkError> {
+        if !(1..=MAX_HIDDEN).contains(&hidden) {
+            return Err(NetworkError::BadHidden(hidden));
+        }
+
+        let mut random = Random::new(seed);
+        let size = config.size();
+        let inputs = 2 * size;
+        let mut weights = |count: usize, fan_in: usize, fan_out: usize| -> Vec<f32> {
+            let scale = (6.0 / (fan_in + fan_out) as f64).sqrt();
+            (0..count)
+                .map(|_i| ((random.next_f64() * 2.0 - 1.0) * scale) as f32)
+                .collect()
+        };
+
+        Ok(Network {
+            config,
+            hidden,
+            hidden_weights: weights(hidden * inputs, inputs, hidden),
+            hidden_biases: vec![0.0; hidden],
+            value_weights: weights(hidden, hidden, 1),
+            value_bias: 0.0,
+            policy_weights: weights(size * hidden, hidden, size),
+            policy_biases: vec![0.0; size],
+        })
+    }
+
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
+
+    /// How good the position is for `side`, from -1 for a sure loss to 1
+    /// for a sure win
+    pub fn value(&self, values: &[CellValue], side: CellValue) -> f32 {
+        let hidden = self.hidden_layer(&self.inputs(values, side));
+        self.value_from(&hidden)
+    }
+
+    /// The chance of every cell being the best move for `side`, 0 for cells
+    /// that can not be played
+    pub fn policy(&self, values: &[CellValue], side: CellValue) -> Vec<f32> {
+        let hidden = self.hidden_layer(&self.inputs(values, side));
+        self.policy_from(&hidden, values)
+    }
+
+    /// Learns from every sample once, in a random order, and returns the
+    /// mean loss
+    pub fn train(
+        &mut self,
+        samples: &[Sample],
+        rate: f32,
+        random: &mut Random,
+    ) -> Result<f32, NetworkError> {
+        let size = self.config.size();
+        for sample in samples.iter() {
+            if sample.position.chars().count() != size {
+                return Err(NetworkError::WrongBoard);
+            }
+            let is_playable =
+                sample.played < size && self.config.is_playable(&sample.values(), sample.played);
+            if !is_playable {
+                return Err(NetworkError::BadSample(sample.position.clone()));
+            }
+        }
+
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        for i in (1..order.len()).rev() {
+            order.swap(i, random.below(i + 1));
+        }
+        let loss: f32 = order
+            .into_iter()
+            .map(|i| self.learn(&samples[i], rate))
+            .sum();
+        Ok(loss / samples.len().max(1) as f32)
+    }
+
+    /// The weights in a compact file: a header with the board and the number
+    /// of hidden units, then every weight as a little-endian `f32`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let config = self.config;
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&[
+            config.rows,
+            config.columns,
+            config.layers,
+            config.win_length,
+            config.gravity as u8,
+            match config.topology {
+                Topology::Flat => 0,
+                Topology::Torus => 1,
+            },
+        ]);
+        bytes.extend_from_slice(&(self.hidden as u16).to_le_bytes());
+
+        let weights = self
+            .hidden_weights
+            .iter()
+            .chain(self.hidden_biases.iter())
+            .chain(self.value_weights.iter())
+            .chain(std::iter::once(&self.value_bias))
+            .chain(self.policy_weights.iter())
+            .chain(self.policy_biases.iter());
+        for weight in weights {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, NetworkError> {
+        let bad = |e: &str| NetworkError::BadWeights(e.to_string());
+        if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC {
+            return Err(bad("not a network file"));
+        }
+        if bytes[4] != VERSION {
+            return Err(bad(&format!("unknown version {}", bytes[4])));
+        }
+
+        let header = &bytes[5..HEADER_LENGTH];
+        let config = BoardConfig {
+            rows: header[0],
+            columns: header[1],
+            layers: header[2],
+            win_length: header[3],
+            gravity: header[4] != 0,
+            topology: match header[5] {
+                0 => Topology::Flat,
+                1 => Topology::Torus,
+                _ => return Err(bad("unknown topology")),
+            },
+        };
+        let hidden = u16::from_le_bytes([header[6], header[7]]) as usize;
+
+        if !config.is_valid() || hidden == 0 {
+            return Err(bad("the header does not describe a network"));
+        }
+
+        // Checked so that no header can overflow the sizes on wasm32
+        let too_large = || bad("the network is too large");
+        let size = config.size();
+        let weights = size.checked_mul(hidden).ok_or_else(too_large)?;
+        let input_weights = weights.checked_mul(2).ok_or_else(too_large)?;
+        let counts = [input_weights, hidden, hidden, 1, weights, size];
+        let expected = counts
+            .iter()
+            .try_fold(0usize, |total, &count| total.checked_add(count))
+            .and_then(|total| total.checked_mul(4)?.checked_add(HEADER_LENGTH))
+            .ok_or_else(too_large)?;
+        if bytes.len() != expected {
+            return Err(bad("the file does not match its header"));
+        }
+
+        let mut weights = bytes[HEADER_LENGTH..]
+            .chunks(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        let mut take = |count: usize| -> Vec<f32> { weights.by_ref().take(count).collect() };
+
+        Ok(Network {
+            config,
+            hidden,
+            hidden_weights: take(counts[0]),
+            hidden_biases: take(counts[1]),
+            value_weights: take(counts[2]),
+            value_bias: take(counts[3])[0],
+            policy_weights: take(counts[4]),
+            policy_biases: take(counts[5]),
+        })
+    }
+
+    fn inputs(&self, values: &[CellValue], side: CellValue) -> Vec<f32> {
+        let mut inputs = vec![0.0; 2 * values.len()];
+        for (i, &value) in values.iter().enumerate() {
+            match value {
+                Empty | Blocked => {}
+                value if value == side => inputs[2 * i] = 1.0,
+                _ => inputs[2 * i + 1] = 1.0,
+            }
+        }
+        inputs
+    }
+
+    fn hidden_layer(&self, inputs: &[f32]) -> Vec<f32> {
+        let marked: Vec<usize> = (0..inputs.len()).filter(|&i| inputs[i] != 0.0).collect();
+        (0..self.hidden)
+            .map(|unit| {
+                let row = &self.hidden_weights[unit * inputs.len()..(unit + 1) * inputs.len()];
+                let sum: f32 = marked.iter().map(|&i| row[i] * inputs[i]).sum();
+                (sum + self.hidden_biases[unit]).max(0.0)
+            })
+            .collect()
+    }
+
+    fn value_from(&self, hidden: &[f32]) -> f32 {
+        let sum: f32 = hidden
+            .iter()
+            .zip(self.value_weights.iter())
+            .map(|(h, w)| h * w)
+            .sum();
+        (sum + self.value_bias).tanh()
+    }
+
+    fn policy_from(&self, hidden: &[f32], values: &[CellValue]) -> Vec<f32> {
+        let logits: Vec<Option<f32>> = (0..values.len())
+            .map(|cell| {
+                if !self.config.is_playable(values, cell) {
+                    return None;
+                }
+                let row = &self.policy_weights[cell * self.hidden..(cell + 1) * self.hidden];
+                let sum: f32 = hidden.iter().zip(row.iter()).map(|(h, w)| h * w).sum();
+                Some(sum + self.policy_biases[cell])
+            })
+            .collect();
+
+        let max = logits
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(f32::NEG_INFINITY, f32::max);
+        let exps: Vec<f32> = logits
+            .iter()
+            .map(|logit| logit.map_or(0.0, |logit| (logit - max).exp()))
+            .collect();
+        let total: f32 = exps.iter().sum();
+        exps.into_iter()
+            .map(|e| if total > 0.0 { e / total } else { 0.0 })
+            .collect()
+    }
+
+    /// One step of gradient descent on the squared error of the value and
+    /// the cross entropy of the move played, returning their sum
+    fn learn(&mut self, sample: &Sample, rate: f32) -> f32 {
+        let values = sample.values();
+        let inputs = self.inputs(&values, Human);
+        let hidden = self.hidden_layer(&inputs);
+        let value = self.value_from(&hidden);
+        let probabilities = self.policy_from(&hidden, &values);
+
+        let value_error = value - sample.result;
+        let loss = value_error * value_error - probabilities[sample.played].max(1e-7).ln();
+        let value_gradient = 2.0 * value_error * (1.0 - value * value);
+        let mut policy_gradient = probabilities;
+        policy_gradient[sample.played] -= 1.0;
+
+        let hidden_gradient: Vec<f32> = (0..self.hidden)
+            .map(|unit| {
+                if hidden[unit] <= 0.0 {
+                    return 0.0;
+                }
+                let from_policy: f32 = policy_gradient
+                    .iter()
+                    .enumerate()
+                    .map(|(cell, g)| g * self.policy_weights[cell * self.hidden + unit])
+                    .sum();
+                value_gradient * self.value_weights[unit] + from_policy
+            })
+            .collect();
+
+        for (cell, &gradient) in policy_gradient.iter().enumerate() {
+            let row = &mut self.policy_weights[cell * self.hidden..(cell + 1) * self.hidden];
+            for (weight, h) in row.iter_mut().zip(hidden.iter()) {
+                *weight -= rate * gradient * h;
+            }
+            self.policy_biases[cell] -= rate * gradient;
+        }
+        for (weight, h) in self.value_weights.iter_mut().zip(hidden.iter()) {
+            *weight -= rate * value_gradient * h;
+        }
+        self.value_bias -= rate * value_gradient;
+
+        let width = inputs.len();
+        for (unit, &gradient) in hidden_gradient.iter().enumerate() {
+            if gradient == 0.0 {
+                continue;
+            }
+            let row = &mut self.hidden_weights[unit * width..(unit + 1) * width];
+            for (weight, x) in row.iter_mut().zip(inputs.iter()) {
+                *weight -= rate * gradient * x;
+            }
+            self.hidden_biases[unit] -= rate * gradient;
+        }
+
+        loss
+    }
+}
+
+/// Plays `engine` against itself and returns a sample for every move it
+/// chose. The first moves of every game and some later ones are played at
+/// random instead, so that the games cover more positions.
+pub fn self_play(config: BoardConfig, engine: &Engine, games: u32, seed: u64) -> Vec<Sample> {
+    let mut random = Random::new(seed);
+    let mut engine = engine.clone();
+    let mut samples = vec![];
+
+    for _game in 0..games {
+        let mut board = Board::new(config);
+        let mut value = Human;
+        let mut moves = vec![];
+        let mut ply = 0;
+
+        while board.outcome() == Outcome::InProgress {
+            let is_random = ply < OPENING_PLIES || random.below(RANDOM_MOVE_ODDS) == 0;
+            let cell = if is_random {
+                let legal = board.legal_moves();
+                let mut cell = legal[random.below(legal.len())].clone();
+                cell.value = value;
+                cell
+            } else {
+                let cell = engine.find_best_move(&board, value);
+                let index = config.index(cell.row, cell.column, cell.layer).unwrap();
+                moves.push((board.values(), value, index));
+                cell
+            };
+            board.set(cell).unwrap();
+            value = value.opponent();
+            ply += 1;
+        }
+
+        let outcome = board.outcome();
+        for (values, side, index) in moves {
+            let result = match outcome {
+                Outcome::Winner(winner) if winner == side => 1.0,
+                Outcome::Winner(_winner) => -1.0,
+                _ => 0.0,
+            };
+            samples.push(Sample::new(&values, side, index, result));
+        }
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::BoardConfig;
+    use crate::board::CellValue::{Empty, Human, CPU};
+    use crate::engine::Engine;
+    use crate::network::{self_play, Network, NetworkError, Sample, MAX_HIDDEN};
+    use crate::utils::Random;
+
+    fn engine() -> Engine {
+        Engine {
+            time_budget_ms: f64::INFINITY,
+            max_depth: 1,
+            ..Engine::default()
+        }
+    }
+
+    #[test]
+    fn test_to_bytes__round_trip() {
+        let network = Network::new(BoardConfig::connect_four(), 8, 1).unwrap();
+
+        let bytes = network.to_bytes();
+        let actual = Network::from_bytes(&bytes);
+        let expected = Ok(network);
+
+        assert_eq!(bytes.len(), 13 + 4 * (2 * 42 * 8 + 8 + 8 + 1 + 42 * 8 + 42));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_bytes__when__bytes_are_cut_short() {
+        let bytes = Network::new(BoardConfig::default(), 4, 1)
+            .unwrap()
+            .to_bytes();
+
+        let actual = Network::from_bytes(&bytes[..bytes.len() - 1]);
+        let expected = Err(NetworkError::BadWeights(
+            "the file does not match its header".to_string(),
+        ));
+
+        assert_eq!(actual, expected);
+        assert!(Network::from_bytes(b"not a network").is_err());
+    }
+
+    #[test]
+    fn test_from_bytes__when__board_can_not_be_played() {
+        let mut bytes = Network::new(BoardConfig::default(), 4, 1)
+            .unwrap()
+            .to_bytes();
+        // The win length, as the header puts it after the magic, the version
+        // and the rows, columns and layers
+        bytes[8] = 0;
+
+        let actual = Network::from_bytes(&bytes);
+        let expected = Err(NetworkError::BadWeights(
+            "the header does not describe a network".to_string(),
+        ));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_from_bytes__when__header_is_as_large_as_it_goes() {
+        let mut bytes = b"TTTN\x01".to_vec();
+        bytes.extend_from_slice(&[255, 255, 255, 255, 0, 0, 255, 255]);
+
+        let actual = Network::from_bytes(&bytes);
+        let expected = Err(NetworkError::BadWeights(
+            "the header does not describe a network".to_string(),
+        ));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_new__when__hidden_does_not_fit() {
+        let config = BoardConfig::default();
+
+        let actual = Network::new(config, MAX_HIDDEN + 1, 1);
+        let expected = Err(NetworkError::BadHidden(MAX_HIDDEN + 1));
+
+        assert_eq!(actual, expected);
+        assert!(Network::new(config, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_policy__only_playable_cells() {
+        let network = Network::new(BoardConfig::default(), 4, 1).unwrap();
+        let mut values = vec![Empty; 9];
+        values[4] = Human;
+        values[0] = CPU;
+
+        let policy = network.policy(&values, CPU);
+        let total: f32 = policy.iter().sum();
+
+        assert_eq!(policy[0], 0.0);
+        assert_eq!(policy[4], 0.0);
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_self_play__samples_are_for_the_player_to_move() {
+        let samples = self_play(BoardConfig::default(), &engine(), 5, 1);
+
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|sample| {
+            let values = sample.values();
+            let own = values.iter().filter(|&&v| v == Human).count();
+            let other = values.iter().filter(|&&v| v == CPU).count();
+            (own == other || own + 1 == other) && values[sample.played] == Empty
+        }));
+    }
+
+    #[test]
+    fn test_train__learns_samples() {
+        let config = BoardConfig::connect_four();
+        let samples = self_play(config, &engine(), 20, 1);
+        let mut network = Network::new(config, 16, 1).unwrap();
+        let mut random = Random::new(1);
+
+        let first = network.train(&samples, 0.01, &mut random).unwrap();
+        let mut last = first;
+        for _epoch in 0..10 {
+            last = network.train(&samples, 0.01, &mut random).unwrap();
+        }
+
+        assert!(last < first);
+    }
+
+    #[test]
+    fn test_train__when__other_board() {
+        let samples = self_play(BoardConfig::default(), &engine(), 1, 1);
+        let mut network = Network::new(BoardConfig::connect_four(), 4, 1).unwrap();
+
+        let actual = network.train(&samples, 0.01, &mut Random::new(1));
+        let expected = Err(NetworkError::WrongBoard);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_train__when__move_is_not_playable() {
+        let sample = Sample {
+            position: "x........".to_string(),
+            played: 0,
+            result: 0.0,
+        };
+        let mut network = Network::new(BoardConfig::default(), 4, 1).unwrap();
+
+        let actual = network.train(&[sample], 0.01, &mut Random::new(1));
+        let expected = Err(NetworkError::BadSample("x........".to_string()));
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::board::CellValue::{Human, CPU};
+use crate::board::{Board, BoardConfig, Cell, CellValue, Mark, Outcome};
+
+use std::collections::HashMap;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+// Boards the engine can solve quickly, with every extra board the search
+// takes about twenty times as long and four already take a second
+pub const MAX_BOARDS: usize = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NotaktoError {
+    GameOver,
+    OutOfRange,
+    DeadBoard,
+    IllegalMove,
+}
+
+impl fmt::Display for NotaktoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            NotaktoError::GameOver => "the game is over",
+            NotaktoError::OutOfRange => "there is no such board",
+            NotaktoError::DeadBoard => "the board already has three in a row",
+            NotaktoError::IllegalMove => "the move is not allowed",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub struct NotaktoMove {
+    pub board: usize,
+    pub row: u8,
+    pub column: u8,
+}
+
+/// Both players put X on any of several boards. A board with three in a row
+/// is dead, and whoever kills the last board loses.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct NotaktoGame {
+    boards: Vec<Board>,
+    to_move: CellValue,
+}
+
+impl NotaktoGame {
+    /// A game with `count` boards, kept between 1 and `MAX_BOARDS` so that
+    /// the computer can always find its move
+    pub fn with_boards(count: usize) -> Self {
+        NotaktoGame {
+            boards: vec![Board::default(); count.clamp(1, MAX_BOARDS)],
+            to_move: Human,
+        }
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards
+    }
+
+    pub fn current_player(&self) -> CellValue {
+        self.to_move
+    }
+
+    pub fn is_dead(&self, board: usize) -> bool {
+        self.boards[board].winner().is_some()
+    }
+
+    pub fn legal_moves(&self) -> Vec<NotaktoMove> {
+        (0..self.boards.len())
+            .filter(|&board| !self.is_dead(board))
+            .flat_map(|board| {
+                self.boards[board]
+                    .legal_moves()
+                    .into_iter()
+                    .map(move |c| NotaktoMove {
+                        board,
+                        row: c.row,
+                        column: c.column,
+                    })
+            })
+            .collect()
+    }
+
+    pub fn play(&mut self, mv: NotaktoMove) -> Result<(), NotaktoError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(NotaktoError::GameOver);
+        }
+        if mv.board >= self.boards.len() {
+            return Err(NotaktoError::OutOfRange);
+        }
+        if self.is_dead(mv.board) {
+            return Err(NotaktoError::DeadBoard);
+        }
+
+        self.boards[mv.board]
+            .set(Cell {
+                row: mv.row,
+                column: mv.column,
+                layer: 0,
+                value: CellValue::Mark(Mark::X),
+            })
+            .ok_or(NotaktoError::IllegalMove)?;
+        self.to_move = self.to_move.opponent();
+        Ok(())
+    }
+
+    /// Once every board is dead the player to move wins, because the other
+    /// player killed the last one
+    pub fn outcome(&self) -> Outcome {
+        if (0..self.boards.len()).all(|board| self.is_dead(board)) {
+            Outcome::Winner(self.to_move)
+        } else {
+            Outcome::InProgress
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl NotaktoGame {
+    /// A game with up to `MAX_BOARDS` boards, see `with_boards`
+    #[wasm_bindgen(constructor)]
+    pub fn new(boards: u8) -> NotaktoGame {
+        NotaktoGame::with_boards(boards as usize)
+    }
+
+    #[wasm_bindgen(js_name = getBoards)]
+    pub fn get_boards(&self) -> JsValue {
+        let cells: Vec<Vec<Cell>> = self.boards.iter().map(Board::get_cells).collect();
+        serde_wasm_bindgen::to_value(&cells).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = isDead)]
+    pub fn is_dead_board(&self, board: usize) -> bool {
+        board < self.boards.len() && self.is_dead(board)
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = humanPlay)]
+    pub fn human_play(&mut self, board: usize, row: u8, column: u8) -> Result<(), JsValue> {
+        self.play(NotaktoMove { board, row, column })
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        if self.to_move != CPU {
+            return;
+        }
+        let mut engine = NotaktoEngine::default();
+        if let Some(mv) = engine.find_best_move(self) {
+            self.play(mv).unwrap();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = NotaktoGame::with_boards(self.boards.len());
+    }
+}
+
+/// Solves Notakto by searching every position. Boards are stored as bit
+/// masks of their X's and reduced by the symmetries of the square, and dead
+/// boards are left out, which keeps a handful of boards fast to solve.
+pub struct NotaktoEngine {
+    lines: Vec<u16>,
+    symmetries: Vec<[usize; 9]>,
+    is_win: HashMap<Vec<u16>, bool>,
+}
+
+impl Default for NotaktoEngine {
+    fn default() -> Self {
+        let lines = BoardConfig::default()
+            .win_lines()
+            .into_iter()
+            .map(|line| line.into_iter().fold(0, |mask, i| mask | 1 << i))
+            .collect();
+
+        let rotate = |i: usize| (i % 3) * 3 + (2 - i / 3);
+        let mirror = |i: usize| (i / 3) * 3 + (2 - i % 3);
+        let mut symmetries = vec![];
+        let mut symmetry = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        for _ in 0..4 {
+            symmetry = symmetry.map(rotate);
+            symmetries.push(symmetry);
+            symmetries.push(symmetry.map(mirror));
+        }
+
+        NotaktoEngine {
+            lines,
+            symmetries,
+            is_win: HashMap::new(),
+        }
+    }
+}
+
+impl NotaktoEngine {
+    /// A move that leaves the opponent in a lost position, or else a move
+    /// that does not kill the last board if there is one
+    pub fn find_best_move(&mut self, game: &NotaktoGame) -> Option<NotaktoMove> {
+        let masks: Vec<u16> = game.boards().iter().map(to_mask).collect();
+        let moves = game.legal_moves();
+
+        let mut fallback = None;
+        for mv in moves.iter() {
+            let mut next = masks.clone();
+            next[mv.board] |= 1 << (mv.row * 3 + mv.column);
+
+            let position = self.position(&next);
+            if position.is_empty() {
+                continue;
+            }
+            if !self.is_winning(position) {
+                return Some(*mv);
+            }
+            fallback.get_or_insert(*mv);
+        }
+
+        fallback.or_else(|| moves.first().copied())
+    }
+
+    /// Whether the player to move wins the game
+    pub fn is_winning_game(&mut self, game: &NotaktoGame) -> bool {
+        let masks: Vec<u16> = game.boards().iter().map(to_mask).collect();
+        let position = self.position(&masks);
+        self.is_winning(position)
+    }
+
+    fn is_winning(&mut self, position: Vec<u16>) -> bool {
+        // The opponent killed the last board
+        if position.is_empty() {
+            return true;
+        }
+        if let Some(is_win) = self.is_win.get(&position) {
+            return *is_win;
+        }
+
+        let mut is_win = false;
+        'boards: for board in 0..position.len() {
+            for cell in 0..9 {
+                if position[board] & 1 << cell != 0 {
+                    continue;
+                }
+                let mut next = position.clone();
+                next[board] |= 1 << cell;
+                let next = self.position(&next);
+                if !self.is_winning(next) {
+                    is_win = true;
+                    break 'boards;
+                }
+            }
+        }
+
+        self.is_win.insert(position, is_win);
+        is_win
+    }
+
+    /// Live boards in their smallest symmetric form, sorted
+    fn position(&self, masks: &[u16]) -> Vec<u16> {
+        let mut position: Vec<u16> = masks
+            .iter()
+            .filter(|&&mask| !self.is_dead(mask))
+            .map(|&mask| self.canonical(mask))
+            .collect();
+        position.sort_unstable();
+        position
+    }
+
+    fn is_dead(&self, mask: u16) -> bool {
+        self.lines.iter().any(|&line| line & !mask == 0)
+    }
+
+    fn canonical(&self, mask: u16) -> u16 {
+        self.symmetries
+            .iter()
+            .map(|symmetry| {
+                (0..9)
+                    .filter(|&i| mask & 1 << i != 0)
+                    .fold(0, |transformed, i| transformed | 1 << symmetry[i])
+            })
+            .min()
+            .unwrap()
+    }
+}
+
+fn to_mask(board: &Board) -> u16 {
+    board
+        .values()
+        .into_iter()
+        .enumerate()
+        .filter(|(_i, value)| *value != CellValue::Empty)
+        .fold(0, |mask, (i, _value)| mask | 1 << i)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::Outcome;
+    use crate::notakto::{NotaktoEngine, NotaktoError, NotaktoGame, NotaktoMove, MAX_BOARDS};
+
+    fn mv(board: usize, row: u8, column: u8) -> NotaktoMove {
+        NotaktoMove { board, row, column }
+    }
+
+    #[test]
+    fn test_play__when__board_dead() {
+        let mut game = NotaktoGame::with_boards(2);
+        game.play(mv(0, 0, 0)).unwrap();
+        game.play(mv(0, 0, 1)).unwrap();
+        game.play(mv(0, 0, 2)).unwrap();
+
+        let actual = game.play(mv(0, 2, 2));
+        let expected = Err(NotaktoError::DeadBoard);
+
+        assert_eq!(actual, expected);
+        assert_eq!(game.legal_moves().len(), 9);
+    }
+
+    #[test]
+    fn test_outcome__when__last_board_killed() {
+        let mut game = NotaktoGame::with_boards(1);
+        game.play(mv(0, 1, 0)).unwrap(); // Human
+        game.play(mv(0, 1, 1)).unwrap(); // CPU
+        game.play(mv(0, 1, 2)).unwrap(); // Human kills the last board
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_canonical__when__rotated() {
+        let engine = NotaktoEngine::default();
+        let top_left = 1;
+        let bottom_right = 1 << 8;
+
+        let actual = engine.canonical(bottom_right);
+        let expected = engine.canonical(top_left);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_with_boards__when__too_many_boards() {
+        let game = NotaktoGame::with_boards(50);
+
+        let actual = game.boards().len();
+        let expected = MAX_BOARDS;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_is_winning_game__when__single_board() {
+        let mut engine = NotaktoEngine::default();
+        let game = NotaktoGame::with_boards(1);
+
+        assert!(engine.is_winning_game(&game));
+    }
+
+    #[test]
+    fn test_is_winning_game__when__two_boards() {
+        let mut engine = NotaktoEngine::default();
+        let game = NotaktoGame::with_boards(2);
+
+        assert!(!engine.is_winning_game(&game));
+    }
+
+    #[test]
+    fn test_find_best_move__leaves_a_lost_position() {
+        let mut engine = NotaktoEngine::default();
+        let mut game = NotaktoGame::with_boards(2);
+        game.play(mv(0, 1, 1)).unwrap();
+
+        let best_move = engine.find_best_move(&game).unwrap();
+        game.play(best_move).unwrap();
+
+        assert_eq!(game.current_player(), Human);
+        assert!(!engine.is_winning_game(&game));
+    }
+
+    #[test]
+    fn test_find_best_move__when__only_killing_moves_left() {
+        let mut engine = NotaktoEngine::default();
+        let mut game = NotaktoGame::with_boards(1);
+        for (row, column) in [(0, 0), (0, 1), (1, 0), (1, 2), (2, 1), (2, 2)] {
+            game.play(mv(0, row, column)).unwrap();
+        }
+        assert_eq!(game.current_player(), Human);
+
+        let actual = engine.find_best_move(&game);
+
+        assert!(actual.is_some());
+    }
+}
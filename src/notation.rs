@@ -0,0 +1,367 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{Board, BoardConfig, CellValue, PositionError, Topology};
+
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NotationError {
+    MissingField,
+    UnknownCell(char),
+    UnevenRows,
+    BadSize,
+    BadConfig,
+    SizeMismatch,
+    WrongSideToMove,
+    UnsupportedValue,
+    Position(PositionError),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::MissingField => write!(f, "the cells or the player to move is missing"),
+            NotationError::UnknownCell(c) => write!(f, "'{}' is not a cell", c),
+            NotationError::UnevenRows => write!(f, "the rows or layers have different lengths"),
+            NotationError::BadSize => write!(f, "the board size can not be read"),
+            NotationError::BadConfig => write!(f, "a game can not be played on the board"),
+            NotationError::SizeMismatch => write!(f, "the cells do not fit the board size"),
+            NotationError::WrongSideToMove => write!(f, "it is the other player's move"),
+            NotationError::UnsupportedValue => write!(f, "the board has a value without a symbol"),
+            NotationError::Position(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<PositionError> for NotationError {
+    fn from(e: PositionError) -> Self {
+        NotationError::Position(e)
+    }
+}
+
+/// Writes a position in the notation read by `parse`
+pub fn format(board: &Board, to_move: CellValue) -> Result<String, NotationError> {
+    let config = board.config();
+    let symbols = board
+        .values()
+        .into_iter()
+        .map(to_symbol)
+        .collect::<Result<Vec<char>, NotationError>>()?;
+
+    let layers: Vec<String> = symbols
+        .chunks(config.rows as usize * config.columns as usize)
+        .map(|layer| {
+            layer
+                .chunks(config.columns as usize)
+                .map(|row| row.iter().collect::<String>())
+                .collect::<Vec<String>>()
+                .join("/")
+        })
+        .collect();
+
+    let mut text = format!("{} {}", layers.join("|"), to_symbol(to_move)?);
+    if config != implied_config(config.rows, config.columns, config.layers) {
+        text.push(' ');
+        text.push_str(&format_config(config));
+    }
+    Ok(text)
+}
+
+/// Reads a position written on one line, such as `x.o/.x./..o x`.
+///
+/// The first field has the cells, row by row from the top, with rows split
+/// by `/` and layers split by `|`. `x` is the human, who moves first, `o` is
+/// the CPU, `.` is empty and `#` is blocked. The second field is the player
+/// to move. An optional third field gives the size as rows `x` columns, then
+/// `x` layers if there is more than one, then `:` and the number of marks in
+/// a row needed to win, followed by `g` for gravity and `t` for a torus. It
+/// is left out when the board is flat and the shortest side is the number
+/// needed to win.
+///
+/// The position has to be one a game could reach, with the player to move
+/// matching the number of marks each player has.
+pub fn parse(text: &str) -> Result<(Board, CellValue), NotationError> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() < 2 || fields.len() > 3 {
+        return Err(NotationError::MissingField);
+    }
+
+    let layers: Vec<Vec<&str>> = fields[0]
+        .split('|')
+        .map(|l| l.split('/').collect())
+        .collect();
+    let rows = layers[0].len();
+    let columns = layers[0][0].chars().count();
+    let is_even = layers
+        .iter()
+        .all(|layer| layer.len() == rows && layer.iter().all(|row| row.chars().count() == columns));
+    if !is_even || columns == 0 {
+        return Err(NotationError::UnevenRows);
+    }
+    let to_u8 = |n: usize| u8::try_from(n).map_err(|_e| NotationError::BadSize);
+    let (rows, columns, layers_count) = (to_u8(rows)?, to_u8(columns)?, to_u8(layers.len())?);
+
+    let config = match fields.get(2) {
+        Some(field) => parse_config(field)?,
+        None => implied_config(rows, columns, layers_count),
+    };
+    if !config.is_valid() {
+        return Err(NotationError::BadConfig);
+    }
+    if (config.rows, config.columns, config.layers) != (rows, columns, layers_count) {
+        return Err(NotationError::SizeMismatch);
+    }
+
+    let values = layers
+        .iter()
+        .flatten()
+        .flat_map(|row| row.chars())
+        .map(from_symbol)
+        .collect::<Result<Vec<CellValue>, NotationError>>()?;
+    let to_move = match fields[1] {
+        "x" => Human,
+        "o" => CPU,
+        _ => return Err(NotationError::MissingField),
+    };
+
+    let count = |value| values.iter().filter(|v| **v == value).count();
+    let expected_to_move = if count(Human) > count(CPU) {
+        CPU
+    } else {
+        Human
+    };
+    if to_move != expected_to_move {
+        return Err(NotationError::WrongSideToMove);
+    }
+
+    Ok((Board::from_position(config, values)?, to_move))
+}
+
+fn implied_config(rows: u8, columns: u8, layers: u8) -> BoardConfig {
+    BoardConfig {
+        rows,
+        columns,
+        layers,
+        win_length: rows.min(columns),
+        gravity: false,
+        topology: Topology::Flat,
+    }
+}
+
+/// Board size and rules as written in the third field, such as `6x7:4g`
+pub fn format_config(config: BoardConfig) -> String {
+    let mut text = format!("{}x{}", config.rows, config.columns);
+    if config.layers > 1 {
+        text.push_str(&format!("x{}", config.layers));
+    }
+    text.push_str(&format!(":{}", config.win_length));
+    if config.gravity {
+        text.push('g');
+    }
+    if config.topology == Topology::Torus {
+        text.push('t');
+    }
+    text
+}
+
+/// Reads a board size and rules written by `format_config`, which have to
+/// be ones a game can be played with
+pub fn parse_config(text: &str) -> Result<BoardConfig, NotationError> {
+    let (size, rules) = text.split_once(':').ok_or(NotationError::BadSize)?;
+    let dimensions = size
+        .split('x')
+        .map(|n| n.parse::<u8>().map_err(|_e| NotationError::BadSize))
+        .collect::<Result<Vec<u8>, NotationError>>()?;
+    let (rows, columns, layers) = match dimensions[..] {
+        [rows, columns] => (rows, columns, 1),
+        [rows, columns, layers] => (rows, columns, layers),
+        _ => return Err(NotationError::BadSize),
+    };
+
+    let flags_start = rules
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rules.len());
+    let (win_length, flags) = rules.split_at(flags_start);
+    let win_length = win_length.parse().map_err(|_e| NotationError::BadSize)?;
+    let topology = if flags.contains('t') {
+        Topology::Torus
+    } else {
+        Topology::Flat
+    };
+    if !flags.chars().all(|c| c == 'g' || c == 't') {
+        return Err(NotationError::BadSize);
+    }
+
+    let config = BoardConfig {
+        rows,
+        columns,
+        layers,
+        win_length,
+        gravity: flags.contains('g'),
+        topology,
+    };
+    if !config.is_valid() {
+        return Err(NotationError::BadConfig);
+    }
+    Ok(config)
+}
+
+fn to_symbol(value: CellValue) -> Result<char, NotationError> {
+    match value {
+        Empty => Ok('.'),
+        Human => Ok('x'),
+        CPU => Ok('o'),
+        CellValue::Blocked => Ok('#'),
+        _ => Err(NotationError::UnsupportedValue),
+    }
+}
+
+fn from_symbol(symbol: char) -> Result<CellValue, NotationError> {
+    match symbol {
+        '.' => Ok(Empty),
+        'x' => Ok(Human),
+        'o' => Ok(CPU),
+        '#' => Ok(CellValue::Blocked),
+        c => Err(NotationError::UnknownCell(c)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome, Topology};
+    use crate::notation::{format, parse, parse_config, NotationError};
+
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_parse__when__classic() {
+        let (board, to_move) = parse("x.o/.x./..o x").unwrap();
+
+        assert_eq!(board.config(), BoardConfig::default());
+        assert_eq!(to_move, Human);
+        assert_eq!(board.values()[0], Human);
+        assert_eq!(board.values()[8], CPU);
+    }
+
+    #[test]
+    fn test_parse__when__connect_four() {
+        let text = "......./......./......./......./......./...x... o 6x7:4g";
+        let (board, to_move) = parse(text).unwrap();
+
+        assert_eq!(board.config(), BoardConfig::connect_four());
+        assert_eq!(to_move, CPU);
+    }
+
+    #[test]
+    fn test_parse__when__size_does_not_fit() {
+        let actual = parse("x../.../... o 4x4:3").err();
+        let expected = Some(NotationError::SizeMismatch);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse__when__win_length_zero() {
+        let actual = parse("x../.../... o 3x3:0").err();
+        let expected = Some(NotationError::BadConfig);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse_config__when__board_can_not_be_played() {
+        let actual: Vec<Option<NotationError>> = ["3x3:4", "0x3:1", "3x0x3:1", "50x50x50:3"]
+            .iter()
+            .map(|text| parse_config(text).err())
+            .collect();
+        let expected = vec![Some(NotationError::BadConfig); 4];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse__when__wrong_side_to_move() {
+        let actual = parse("x../.../... x").err();
+        let expected = Some(NotationError::WrongSideToMove);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse__when__unknown_cell() {
+        let actual = parse("x../.?./... o").err();
+        let expected = Some(NotationError::UnknownCell('?'));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_format__when__qubic_with_blocked_cell() {
+        let values = (0..64)
+            .map(|i| {
+                if i == 63 {
+                    CellValue::Blocked
+                } else {
+                    CellValue::Empty
+                }
+            })
+            .collect();
+        let board = Board::from_position(BoardConfig::qubic(), values).unwrap();
+
+        let actual = format(&board, Human).unwrap();
+        let expected =
+            "..../..../..../....|..../..../..../....|..../..../..../....|..../..../..../...# x";
+
+        assert_eq!(actual, expected);
+    }
+
+    fn configs() -> impl Strategy<Value = BoardConfig> {
+        prop_oneof![
+            Just(BoardConfig::default()),
+            Just(BoardConfig::qubic()),
+            Just(BoardConfig::connect_four()),
+            Just(BoardConfig::square(4, 3)),
+            Just(BoardConfig::square(5, 3).with_topology(Topology::Torus)),
+        ]
+    }
+
+    /// Plays `choices` as indexes into the legal moves until the game ends
+    fn play_out(config: BoardConfig, choices: &[usize]) -> (Board, CellValue) {
+        let mut board = Board::new(config);
+        let mut to_move = Human;
+        for &choice in choices {
+            let moves = board.legal_moves();
+            if moves.is_empty() || board.outcome() != Outcome::InProgress {
+                break;
+            }
+            let cell = &moves[choice % moves.len()];
+            board.set(Cell {
+                value: to_move,
+                ..cell.clone()
+            });
+            to_move = to_move.opponent();
+        }
+        (board, to_move)
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse__is_inverse_of_format(
+            config in configs(),
+            choices in prop::collection::vec(any::<usize>(), 0..40),
+        ) {
+            let (board, to_move) = play_out(config, &choices);
+
+            let text = format(&board, to_move).unwrap();
+            let (parsed, parsed_to_move) = parse(&text).unwrap();
+
+            prop_assert_eq!(parsed.config(), board.config());
+            prop_assert_eq!(parsed.values(), board.values());
+            prop_assert_eq!(parsed_to_move, to_move);
+            prop_assert_eq!(format(&parsed, parsed_to_move).unwrap(), text);
+        }
+    }
+}
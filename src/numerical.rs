@@ -0,0 +1,392 @@
+use crate::board::CellValue::{Human, CPU};
+use crate::board::{Board, Cell, CellValue, Outcome};
+
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+// A full line has to add up to this to win
+const TARGET_SUM: u8 = 15;
+
+// There are too many moves to search to the end
+const SEARCH_DEPTH: u8 = 4;
+const WIN_SCORE: i32 = 100;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumericalError {
+    GameOver,
+    NotInInventory,
+    IllegalMove,
+}
+
+impl fmt::Display for NumericalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            NumericalError::GameOver => "the game is over",
+            NumericalError::NotInInventory => "the number is not available to the player",
+            NumericalError::IllegalMove => "the move is not allowed",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+/// Tic-tac-toe with the numbers 1 to 9. The first player has the odd
+/// numbers and the second player the even ones, each number can be played
+/// once, and whoever completes a line that adds up to 15 wins.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct NumericalGame {
+    board: Board,
+    to_move: CellValue,
+}
+
+impl Default for NumericalGame {
+    fn default() -> Self {
+        NumericalGame {
+            board: Board::default(),
+            to_move: Human,
+        }
+    }
+}
+
+impl NumericalGame {
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_player(&self) -> CellValue {
+        self.to_move
+    }
+
+    /// Numbers the player has not played yet
+    pub fn inventory(&self, player: CellValue) -> Vec<u8> {
+        let first_number = if player == Human { 1 } else { 2 };
+        let values = self.board.values();
+
+        (first_number..=9)
+            .step_by(2)
+            .filter(|&n| !values.contains(&CellValue::Number(n)))
+            .collect()
+    }
+
+    pub fn legal_moves(&self) -> Vec<Cell> {
+        if self.outcome() != Outcome::InProgress {
+            return vec![];
+        }
+
+        let inventory = self.inventory(self.to_move);
+        self.board
+            .legal_moves()
+            .into_iter()
+            .flat_map(|c| {
+                inventory.iter().map(move |&n| Cell {
+                    value: CellValue::Number(n),
+                    ..c.clone()
+                })
+            })
+            .collect()
+    }
+
+    pub fn play(&mut self, cell: Cell) -> Result<(), NumericalError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(NumericalError::GameOver);
+        }
+        match cell.value {
+            CellValue::Number(n) if self.inventory(self.to_move).contains(&n) => {}
+            _ => return Err(NumericalError::NotInInventory),
+        }
+
+        self.board.set(cell).ok_or(NumericalError::IllegalMove)?;
+        self.to_move = self.to_move.opponent();
+        Ok(())
+    }
+
+    /// The game ends as soon as a line adds up to 15, so the line belongs to
+    /// whoever played last
+    pub fn outcome(&self) -> Outcome {
+        if has_target_line(&self.board) {
+            return Outcome::Winner(self.to_move.opponent());
+        }
+
+        let is_out_of_moves =
+            self.board.legal_moves().is_empty() || self.inventory(self.to_move).is_empty();
+        if is_out_of_moves {
+            Outcome::Draw
+        } else {
+            Outcome::InProgress
+        }
+    }
+}
+
+fn has_target_line(board: &Board) -> bool {
+    let values = board.values();
+    board.win_lines().iter().any(|line| {
+        let numbers: Vec<u8> = line
+            .iter()
+            .filter_map(|&i| match values[i] {
+                CellValue::Number(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        numbers.len() == line.len() && numbers.iter().sum::<u8>() == TARGET_SUM
+    })
+}
+
+#[wasm_bindgen]
+impl NumericalGame {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> NumericalGame {
+        NumericalGame::default()
+    }
+
+    #[wasm_bindgen(js_name = getCells)]
+    pub fn get_cells(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.get_cells()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getHumanInventory)]
+    pub fn get_human_inventory(&self) -> Vec<u8> {
+        self.inventory(Human)
+    }
+
+    #[wasm_bindgen(js_name = getCpuInventory)]
+    pub fn get_cpu_inventory(&self) -> Vec<u8> {
+        self.inventory(CPU)
+    }
+
+    #[wasm_bindgen(js_name = humanPlay)]
+    pub fn human_play(&mut self, row: u8, column: u8, number: u8) -> Result<(), JsValue> {
+        let cell = Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Number(number),
+        };
+        self.play(cell)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        if self.to_move != CPU {
+            return;
+        }
+        if let Some(cell) = find_best_move(self) {
+            self.play(cell).unwrap();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = NumericalGame::default();
+    }
+}
+
+/// Best move for the player to move, generated from the numbers each
+/// player has left
+pub fn find_best_move(game: &NumericalGame) -> Option<Cell> {
+    let mut alpha = -WIN_SCORE - 1;
+    let mut best_move = None;
+
+    for c in game.legal_moves() {
+        let mut next = game.clone();
+        next.play(c.clone()).unwrap();
+
+        let score = -negamax(&next, SEARCH_DEPTH - 1, -WIN_SCORE - 1, -alpha);
+        if best_move.is_none() || score > alpha {
+            alpha = score;
+            best_move = Some(c);
+        }
+    }
+
+    best_move
+}
+
+fn negamax(game: &NumericalGame, depth: u8, mut alpha: i32, beta: i32) -> i32 {
+    match game.outcome() {
+        // The player who just moved won, sooner is worse
+        Outcome::Winner(_) => return -(WIN_SCORE - (SEARCH_DEPTH - depth) as i32),
+        Outcome::Draw => return 0,
+        Outcome::InProgress if depth == 0 => return evaluate(game),
+        Outcome::InProgress => {}
+    }
+
+    for c in game.legal_moves() {
+        let mut next = game.clone();
+        next.play(c).unwrap();
+
+        let score = -negamax(&next, depth - 1, -beta, -alpha);
+        if score > alpha {
+            alpha = score;
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    alpha
+}
+
+/// Score for the player to move of a position the search stops at, from the
+/// lines that can still add up to 15. A line they can finish now is as good
+/// as won, a line only the opponent can finish has to be blocked, and a line
+/// they can bring one number short of 15 is a threat they can make.
+fn evaluate(game: &NumericalGame) -> i32 {
+    let side = game.current_player();
+    let own = game.inventory(side);
+    let other = game.inventory(side.opponent());
+    let values = game.board.values();
+    let mut score = 0;
+
+    for line in game.board.win_lines() {
+        let numbers: Vec<u8> = line
+            .iter()
+            .filter_map(|&i| match values[i] {
+                CellValue::Number(n) => Some(n),
+                _ => None,
+            })
+            .collect();
+        let sum: u8 = numbers.iter().sum();
+        if sum >= TARGET_SUM {
+            continue;
+        }
+        let missing = TARGET_SUM - sum;
+
+        match line.len() - numbers.len() {
+            1 if own.contains(&missing) => return WIN_SCORE / 2,
+            1 if other.contains(&missing) => score -= 10,
+            2 => {
+                let is_threat = own.iter().any(|&n| {
+                    n < missing
+                        && missing - n != n
+                        && (own.contains(&(missing - n)) || other.contains(&(missing - n)))
+                });
+                if is_threat {
+                    score += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Cell, CellValue, Outcome};
+    use crate::numerical::{evaluate, find_best_move, NumericalError, NumericalGame, WIN_SCORE};
+
+    fn number(row: u8, column: u8, number: u8) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Number(number),
+        }
+    }
+
+    #[test]
+    fn test_inventory__when__new_game() {
+        let game = NumericalGame::default();
+
+        assert_eq!(game.inventory(Human), vec![1, 3, 5, 7, 9]);
+        assert_eq!(game.inventory(CPU), vec![2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn test_play__when__opponents_number() {
+        let mut game = NumericalGame::default();
+
+        let actual = game.play(number(0, 0, 2));
+        let expected = Err(NumericalError::NotInInventory);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play__when__number_already_played() {
+        let mut game = NumericalGame::default();
+        game.play(number(0, 0, 5)).unwrap();
+        game.play(number(2, 2, 2)).unwrap();
+
+        let actual = game.play(number(1, 1, 5));
+        let expected = Err(NumericalError::NotInInventory);
+
+        assert_eq!(actual, expected);
+        assert_eq!(game.inventory(Human), vec![1, 3, 7, 9]);
+    }
+
+    #[test]
+    fn test_outcome__when__line_adds_up_to_15() {
+        let mut game = NumericalGame::default();
+        game.play(number(0, 0, 9)).unwrap(); // Human
+        game.play(number(0, 1, 2)).unwrap(); // CPU
+        game.play(number(2, 2, 1)).unwrap(); // Human
+        game.play(number(0, 2, 4)).unwrap(); // CPU completes 9 + 2 + 4
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__full_line_not_15() {
+        let mut game = NumericalGame::default();
+        game.play(number(0, 0, 1)).unwrap();
+        game.play(number(0, 1, 2)).unwrap();
+        game.play(number(0, 2, 3)).unwrap();
+
+        let actual = game.outcome();
+        let expected = Outcome::InProgress;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__win_available() {
+        let mut game = NumericalGame::default();
+        for (row, column, n) in [(0, 0, 9), (1, 1, 2), (0, 1, 1)] {
+            game.play(number(row, column, n)).unwrap();
+        }
+
+        let actual = find_best_move(&game);
+        let expected = Some(number(2, 2, 4));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_evaluate__when__line_can_be_finished() {
+        let mut game = NumericalGame::default();
+        for (row, column, n) in [(0, 0, 9), (1, 1, 2), (0, 1, 1)] {
+            game.play(number(row, column, n)).unwrap();
+        }
+
+        let actual = evaluate(&game);
+        let expected = WIN_SCORE / 2;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_evaluate__when__opponent_can_finish_line() {
+        let mut game = NumericalGame::default();
+        for (row, column, n) in [(0, 0, 9), (1, 2, 2), (0, 1, 1)] {
+            game.play(number(row, column, n)).unwrap();
+        }
+
+        let actual = evaluate(&game);
+
+        assert!(actual < 0);
+    }
+}
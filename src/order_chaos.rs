@@ -0,0 +1,470 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{Board, BoardConfig, Cell, CellValue, Mark, Outcome, Topology};
+use crate::search::{line_weight, Clock, WinLines};
+use crate::utils::now_ms;
+
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+const ORDER_WIN: i32 = 1_000_000;
+
+#[wasm_bindgen]
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub enum Role {
+    /// Wins with five of the same mark in a row
+    Order,
+    /// Wins by filling the board without five in a row
+    Chaos,
+}
+
+impl Role {
+    pub fn other(self) -> Role {
+        match self {
+            Role::Order => Role::Chaos,
+            Role::Chaos => Role::Order,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OrderChaosError {
+    GameOver,
+    IllegalMove,
+}
+
+impl fmt::Display for OrderChaosError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderChaosError::GameOver => write!(f, "the game is over"),
+            OrderChaosError::IllegalMove => write!(f, "the move is not allowed"),
+        }
+    }
+}
+
+/// Order and Chaos on a 6x6 board. Both players may play X or O, Order
+/// tries to get five of one of them in a row and Chaos tries to stop it.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OrderChaosGame {
+    board: Board,
+    human_role: Role,
+    to_move: CellValue,
+}
+
+impl OrderChaosGame {
+    pub fn config() -> BoardConfig {
+        BoardConfig {
+            rows: 6,
+            columns: 6,
+            layers: 1,
+            win_length: 5,
+            gravity: false,
+            topology: Topology::Flat,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_player(&self) -> CellValue {
+        self.to_move
+    }
+
+    pub fn role_of(&self, player: CellValue) -> Role {
+        if player == Human {
+            self.human_role
+        } else {
+            self.human_role.other()
+        }
+    }
+
+    pub fn legal_moves(&self) -> Vec<Cell> {
+        if self.outcome() != Outcome::InProgress {
+            return vec![];
+        }
+
+        self.board
+            .legal_moves()
+            .into_iter()
+            .flat_map(|c| {
+                vec![Mark::X, Mark::O].into_iter().map(move |mark| Cell {
+                    value: CellValue::Mark(mark),
+                    ..c.clone()
+                })
+            })
+            .collect()
+    }
+
+    pub fn play(&mut self, cell: Cell) -> Result<(), OrderChaosError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(OrderChaosError::GameOver);
+        }
+        if !matches!(cell.value, CellValue::Mark(_)) {
+            return Err(OrderChaosError::IllegalMove);
+        }
+
+        self.board.set(cell).ok_or(OrderChaosError::IllegalMove)?;
+        self.to_move = self.to_move.opponent();
+        Ok(())
+    }
+
+    /// Five in a row wins for Order whoever played it, a full board without
+    /// one wins for Chaos
+    pub fn outcome(&self) -> Outcome {
+        if self.board.winner().is_some() {
+            return Outcome::Winner(self.player_with(Role::Order));
+        }
+
+        if Board::is_moves_left(self.board.get_cells()) {
+            Outcome::InProgress
+        } else {
+            Outcome::Winner(self.player_with(Role::Chaos))
+        }
+    }
+
+    fn player_with(&self, role: Role) -> CellValue {
+        if self.human_role == role {
+            Human
+        } else {
+            CPU
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl OrderChaosGame {
+    /// Order always moves first
+    #[wasm_bindgen(constructor)]
+    pub fn new(human_role: Role) -> OrderChaosGame {
+        let mut game = OrderChaosGame {
+            board: Board::new(OrderChaosGame::config()),
+            human_role,
+            to_move: Human,
+        };
+        game.to_move = game.player_with(Role::Order);
+        game
+    }
+
+    #[wasm_bindgen(js_name = getCells)]
+    pub fn get_cells(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.get_cells()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getHumanRole)]
+    pub fn get_human_role(&self) -> Role {
+        self.human_role
+    }
+
+    #[wasm_bindgen(js_name = humanPlay)]
+    pub fn human_play(&mut self, row: u8, column: u8, mark: Mark) -> Result<(), JsValue> {
+        let cell = Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Mark(mark),
+        };
+        self.play(cell)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        if self.to_move != CPU {
+            return;
+        }
+        if let Some(cell) = OrderChaosEngine::default().find_best_move(self) {
+            self.play(cell).unwrap();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = OrderChaosGame::new(self.human_role);
+    }
+}
+
+/// Plays either role with a search that deepens until the time budget runs
+/// out. Positions are scored for Order, which Chaos tries to keep low.
+pub struct OrderChaosEngine {
+    pub time_budget_ms: f64,
+    pub max_depth: u8,
+}
+
+impl Default for OrderChaosEngine {
+    fn default() -> Self {
+        OrderChaosEngine {
+            time_budget_ms: 500.0,
+            max_depth: 8,
+        }
+    }
+}
+
+impl OrderChaosEngine {
+    pub fn find_best_move(&mut self, game: &OrderChaosGame) -> Option<Cell> {
+        let role = game.role_of(game.current_player());
+        let mut search = Search::new(game.board(), now_ms() + self.time_budget_ms);
+        let (index, mark) = search.iterate(role, self.max_depth)?;
+        let (row, column, layer) = game.board().config().coordinates(index);
+
+        Some(Cell {
+            row,
+            column,
+            layer,
+            value: CellValue::Mark(mark),
+        })
+    }
+}
+
+struct Search {
+    lines: WinLines,
+    values: Vec<CellValue>,
+    clock: Clock,
+}
+
+impl Search {
+    fn new(board: &Board, deadline: f64) -> Self {
+        Search {
+            lines: WinLines::new(board),
+            values: board.values(),
+            clock: Clock::new(deadline, u64::MAX),
+        }
+    }
+
+    fn iterate(&mut self, role: Role, max_depth: u8) -> Option<(usize, Mark)> {
+        let mut moves = self.ordered_moves(role);
+        let mut best = *moves.first()?;
+        let empty_cells = self.values.iter().filter(|&&v| v == Empty).count();
+
+        for depth in 1..=max_depth.max(1) {
+            let mut best_score = None;
+            let mut best_at_depth = best;
+
+            for &(index, mark) in moves.iter() {
+                let score = self.score_move(index, mark, role, depth);
+                if self.clock.stopped {
+                    return Some(best);
+                }
+
+                let is_better = match (best_score, role) {
+                    (None, _) => true,
+                    (Some(best), Role::Order) => score > best,
+                    (Some(best), Role::Chaos) => score < best,
+                };
+                if is_better {
+                    best_score = Some(score);
+                    best_at_depth = (index, mark);
+                }
+            }
+
+            best = best_at_depth;
+            moves.retain(|&mv| mv != best);
+            moves.insert(0, best);
+
+            let is_decided = best_score.unwrap_or(0).abs() >= ORDER_WIN - max_depth as i32;
+            if is_decided || depth as usize >= empty_cells {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+
+    fn score_move(&mut self, index: usize, mark: Mark, role: Role, depth: u8) -> i32 {
+        self.values[index] = CellValue::Mark(mark);
+        let score = self.minimax(
+            depth - 1,
+            -ORDER_WIN - 1,
+            ORDER_WIN + 1,
+            role.other(),
+            index,
+        );
+        self.values[index] = Empty;
+        score
+    }
+
+    /// Score for Order, which Order maximizes and Chaos minimizes
+    fn minimax(
+        &mut self,
+        depth: u8,
+        mut alpha: i32,
+        mut beta: i32,
+        role: Role,
+        last: usize,
+    ) -> i32 {
+        if self.lines.is_win_through(&self.values, last) {
+            return ORDER_WIN + depth as i32;
+        }
+        if !self.values.contains(&Empty) {
+            return -ORDER_WIN - depth as i32;
+        }
+
+        if self.clock.tick() || depth == 0 {
+            return self.evaluate();
+        }
+
+        for (index, mark) in self.ordered_moves(role) {
+            self.values[index] = CellValue::Mark(mark);
+            let score = self.minimax(depth - 1, alpha, beta, role.other(), index);
+            self.values[index] = Empty;
+
+            match role {
+                Role::Order => alpha = alpha.max(score),
+                Role::Chaos => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        match role {
+            Role::Order => alpha,
+            Role::Chaos => beta,
+        }
+    }
+
+    /// Every line with only one kind of mark in it is a chance for Order
+    fn evaluate(&self) -> i32 {
+        (0..self.lines.lines.len())
+            .map(|line| match self.count_line(line) {
+                (x, 0) => line_weight(x),
+                (0, o) => line_weight(o),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Xs and Os in a line
+    fn count_line(&self, line: usize) -> (usize, usize) {
+        self.lines
+            .count_line(&self.values, line, CellValue::Mark(Mark::X))
+    }
+
+    /// Empty cells in the busiest lines first. Order plays the mark most of
+    /// those lines already have, Chaos plays the other one.
+    fn ordered_moves(&self, role: Role) -> Vec<(usize, Mark)> {
+        let mut scored: Vec<(i32, usize, Mark)> = (0..self.values.len())
+            .filter(|&i| self.values[i] == Empty)
+            .flat_map(|i| {
+                let (x_weight, o_weight) = self.lines.through[i]
+                    .iter()
+                    .map(|&line| match self.count_line(line) {
+                        (x, 0) => (line_weight(x), 0),
+                        (0, o) => (0, line_weight(o)),
+                        _ => (0, 0),
+                    })
+                    .fold((0, 0), |(x, o), (x2, o2)| (x + x2, o + o2));
+
+                let (first, second) = match (role, x_weight >= o_weight) {
+                    (Role::Order, true) | (Role::Chaos, false) => (Mark::X, Mark::O),
+                    _ => (Mark::O, Mark::X),
+                };
+                let priority = x_weight + o_weight;
+                vec![(priority + 1, i, first), (priority, i, second)]
+            })
+            .collect();
+
+        scored.sort_by(|(score, _i, _m), (score2, _i2, _m2)| score2.cmp(score));
+        scored
+            .into_iter()
+            .map(|(_score, i, mark)| (i, mark))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Cell, CellValue, Mark, Outcome};
+    use crate::order_chaos::{OrderChaosEngine, OrderChaosGame, Role};
+
+    fn mark(row: u8, column: u8, mark: Mark) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Mark(mark),
+        }
+    }
+
+    fn engine() -> OrderChaosEngine {
+        OrderChaosEngine {
+            time_budget_ms: 300.0,
+            max_depth: 2,
+        }
+    }
+
+    #[test]
+    fn test_new__order_moves_first() {
+        let game = OrderChaosGame::new(Role::Chaos);
+
+        assert_eq!(game.current_player(), CPU);
+        assert_eq!(game.role_of(CPU), Role::Order);
+    }
+
+    #[test]
+    fn test_outcome__when__five_in_a_row() {
+        let mut game = OrderChaosGame::new(Role::Chaos);
+        for column in 0..5 {
+            game.board.set(mark(2, column, Mark::O));
+        }
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__full_without_five_in_a_row() {
+        let mut game = OrderChaosGame::new(Role::Chaos);
+        for row in 0..6 {
+            for column in 0..6 {
+                let is_x = (row + column / 2) % 2 == 0;
+                let value = if is_x { Mark::X } else { Mark::O };
+                game.board.set(mark(row, column, value));
+            }
+        }
+        assert_eq!(game.board.winner(), None);
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(Human);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__order_can_win() {
+        let mut game = OrderChaosGame::new(Role::Chaos);
+        for column in 1..5 {
+            game.board.set(mark(3, column, Mark::X));
+        }
+        game.board.set(mark(3, 0, Mark::O));
+
+        let actual = engine().find_best_move(&game);
+        let expected = Some(mark(3, 5, Mark::X));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__chaos_has_to_block() {
+        let mut game = OrderChaosGame::new(Role::Order);
+        for column in 0..4 {
+            game.board.set(mark(0, column, Mark::X));
+        }
+        game.to_move = CPU;
+
+        let actual = engine().find_best_move(&game);
+        let expected = Some(mark(0, 4, Mark::O));
+
+        assert_eq!(actual, expected);
+    }
+}
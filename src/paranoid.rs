@@ -0,0 +1,274 @@
+use crate::board::CellValue::Empty;
+use crate::board::{Board, BoardConfig, Cell, CellValue};
+use crate::search::{self, line_weight, Clock, WinLines};
+use crate::utils::now_ms;
+
+const WIN_SCORE: i32 = 1_000_000;
+
+/// Plays games with more than two players. The search is paranoid: it
+/// assumes every other player only tries to beat the player it moves for,
+/// which turns the game into a two-sided one that alpha-beta can prune.
+#[derive(Clone)]
+pub struct ParanoidEngine {
+    pub time_budget_ms: f64,
+    pub max_depth: u8,
+}
+
+impl Default for ParanoidEngine {
+    fn default() -> Self {
+        ParanoidEngine {
+            time_budget_ms: 500.0,
+            max_depth: 32,
+        }
+    }
+}
+
+impl ParanoidEngine {
+    /// Best move for `players[turn]`, where `players` is the order of play
+    pub fn find_best_move(&mut self, board: &Board, players: &[CellValue], turn: usize) -> Cell {
+        let deadline = now_ms() + self.time_budget_ms;
+        let mut search = Search::new(board, players, turn, deadline);
+
+        let index = search
+            .iterate(self.max_depth)
+            .expect("There are no moves left to play");
+        let (row, column, layer) = board.config().coordinates(index);
+
+        Cell {
+            row,
+            column,
+            layer,
+            value: players[turn],
+        }
+    }
+}
+
+struct Search<'a> {
+    config: BoardConfig,
+    lines: WinLines,
+    values: Vec<CellValue>,
+    players: &'a [CellValue],
+    root: usize,
+    clock: Clock,
+}
+
+impl<'a> Search<'a> {
+    fn new(board: &Board, players: &'a [CellValue], root: usize, deadline: f64) -> Self {
+        Search {
+            config: board.config(),
+            lines: WinLines::new(board),
+            values: board.values(),
+            players,
+            root,
+            clock: Clock::new(deadline, u64::MAX),
+        }
+    }
+
+    fn iterate(&mut self, max_depth: u8) -> Option<usize> {
+        let mut moves = self.ordered_moves();
+        let mut best = *moves.first()?;
+        let next = self.next(self.root);
+        let plies_left = search::plies_left(&self.values);
+
+        for depth in 1..=max_depth.max(1) {
+            let mut alpha = -WIN_SCORE - 1;
+            let mut best_at_depth = best;
+
+            for &mv in moves.iter() {
+                self.values[mv] = self.players[self.root];
+                let score = self.minimax(depth - 1, 1, alpha, WIN_SCORE + 1, next, mv);
+                self.values[mv] = Empty;
+
+                if self.clock.stopped {
+                    return Some(best);
+                }
+                if score > alpha {
+                    alpha = score;
+                    best_at_depth = mv;
+                }
+            }
+
+            best = best_at_depth;
+            moves.retain(|&mv| mv != best);
+            moves.insert(0, best);
+
+            let is_decided = alpha.abs() >= WIN_SCORE - max_depth as i32;
+            if is_decided || depth as usize >= plies_left {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+
+    /// Score for the root player, who maximizes while everyone else minimizes
+    fn minimax(
+        &mut self,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        mut beta: i32,
+        turn: usize,
+        last: usize,
+    ) -> i32 {
+        if self.lines.is_win_through(&self.values, last) {
+            let score = WIN_SCORE - ply as i32;
+            return if self.values[last] == self.players[self.root] {
+                score
+            } else {
+                -score
+            };
+        }
+
+        if self.clock.tick() {
+            return 0;
+        }
+
+        let moves = self.ordered_moves();
+        if moves.is_empty() {
+            return 0;
+        }
+        if depth == 0 {
+            return self.evaluate();
+        }
+
+        let is_root = turn == self.root;
+        for mv in moves {
+            self.values[mv] = self.players[turn];
+            let score = self.minimax(depth - 1, ply + 1, alpha, beta, self.next(turn), mv);
+            self.values[mv] = Empty;
+
+            if is_root {
+                alpha = alpha.max(score);
+            } else {
+                beta = beta.min(score);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if is_root {
+            alpha
+        } else {
+            beta
+        }
+    }
+
+    fn next(&self, turn: usize) -> usize {
+        (turn + 1) % self.players.len()
+    }
+
+    /// Lines only the root player has marks in count for it, lines only one
+    /// other player has marks in count against it
+    fn evaluate(&self) -> i32 {
+        (0..self.lines.lines.len())
+            .map(|line| match self.line_owner(line) {
+                Some((owner, marks)) if owner == self.players[self.root] => line_weight(marks),
+                Some((_owner, marks)) => -line_weight(marks),
+                None => 0,
+            })
+            .sum()
+    }
+
+    /// The only player with marks in the line and how many they have there
+    fn line_owner(&self, line: usize) -> Option<(CellValue, usize)> {
+        let mut owner = None;
+        let mut marks = 0;
+        for &i in self.lines.lines[line].iter() {
+            let value = self.values[i];
+            if value == Empty {
+                continue;
+            }
+            if owner.is_some() && owner != Some(value) {
+                return None;
+            }
+            owner = Some(value);
+            marks += 1;
+        }
+        owner.map(|owner| (owner, marks))
+    }
+
+    /// Playable cells, those in the most promising lines of any player first
+    fn ordered_moves(&self) -> Vec<usize> {
+        let mut scored: Vec<(i32, usize)> = (0..self.values.len())
+            .filter(|&i| self.config.is_playable(&self.values, i))
+            .map(|i| {
+                let priority = self.lines.through[i]
+                    .iter()
+                    .map(|&line| match self.line_owner(line) {
+                        Some((_owner, marks)) => line_weight(marks),
+                        None => 0,
+                    })
+                    .sum::<i32>();
+                (priority, i)
+            })
+            .collect();
+
+        scored.sort_by(|(score, _i), (score2, _i2)| score2.cmp(score));
+        scored.into_iter().map(|(_score, i)| i).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::{Board, BoardConfig, Cell, CellValue};
+    use crate::paranoid::ParanoidEngine;
+
+    fn players() -> Vec<CellValue> {
+        (0..3).map(CellValue::Player).collect()
+    }
+
+    fn board_with(marks: &[(u8, u8, u8)]) -> Board {
+        let mut board = Board::new(BoardConfig::square(4, 3));
+        for &(row, column, seat) in marks {
+            board.set(Cell {
+                row,
+                column,
+                layer: 0,
+                value: CellValue::Player(seat),
+            });
+        }
+        board
+    }
+
+    fn engine() -> ParanoidEngine {
+        ParanoidEngine {
+            time_budget_ms: 300.0,
+            max_depth: 3,
+        }
+    }
+
+    #[test]
+    fn test_find_best_move__when__can_win() {
+        let board = board_with(&[(0, 0, 1), (3, 3, 0), (0, 1, 1), (3, 2, 2)]);
+
+        let actual = engine().find_best_move(&board, &players(), 1);
+        let expected = Cell {
+            row: 0,
+            column: 2,
+            layer: 0,
+            value: CellValue::Player(1),
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__next_player_threatens() {
+        // Player 2 moves after player 1 and wins at (1, 2) unless blocked
+        let board = board_with(&[(1, 0, 2), (1, 1, 2), (3, 3, 0), (0, 0, 1), (3, 0, 0)]);
+
+        let actual = engine().find_best_move(&board, &players(), 1);
+        let expected = Cell {
+            row: 1,
+            column: 2,
+            layer: 0,
+            value: CellValue::Player(1),
+        };
+
+        assert_eq!(actual, expected);
+    }
+}
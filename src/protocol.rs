@@ -0,0 +1,291 @@
+use crate::board::{BoardConfig, Cell, CellValue, Outcome};
+use crate::engine::{Engine, SearchInfo};
+use crate::game::Game;
+use crate::notation::{self, NotationError};
+use crate::record;
+
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ProtocolError {
+    UnknownCommand(String),
+    BadPosition(NotationError),
+    IllegalMove(String),
+    BadLimit(String),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::UnknownCommand(c) => write!(f, "unknown command {}", c),
+            ProtocolError::BadPosition(e) => write!(f, "bad position: {}", e),
+            ProtocolError::IllegalMove(m) => write!(f, "illegal move {}", m),
+            ProtocolError::BadLimit(l) => write!(f, "bad search limit {}", l),
+        }
+    }
+}
+
+impl From<NotationError> for ProtocolError {
+    fn from(e: NotationError) -> Self {
+        ProtocolError::BadPosition(e)
+    }
+}
+
+/// An engine driven by text commands, one per line, so tournament managers
+/// and GUIs can run it as a subprocess. The commands are:
+///
+/// - `uci` answers with the engine's name and `uciok`
+/// - `isready` answers `readyok`
+/// - `newgame` clears the board
+/// - `position startpos [<config>] [moves <move> ...]`, with a config in
+///   the form of `notation::parse_config`, or
+///   `position <notation> [moves <move> ...]` sets the position, from an
+///   empty board or from the notation of `notation::parse`, then plays the
+///   moves, written as coordinates such as `b2`
+/// - `go [depth <n>] [nodes <n>] [time <ms>]` searches the position and
+///   answers with an `info` line for every depth, then `bestmove <move>`
+/// - `quit`
+///
+/// An `info` line has the depth, the score as `cp <n>` or as `mate <plies>`
+/// once the search has seen the end, negative when the player to move
+/// loses, the searched positions, the time taken and the best line of play
+/// after `pv`. Errors are reported as `info string <error>`.
+pub struct Session {
+    game: Game,
+    engine: Engine,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            game: Game::with_config(BoardConfig::default()),
+            engine: Engine::default(),
+        }
+    }
+}
+
+impl Session {
+    /// Carries out a command, writing the answers to `out`. Returns false
+    /// once the engine has been told to quit.
+    pub fn handle<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words.split_first() {
+            None => Ok(()),
+            Some((&"quit", _rest)) => return Ok(false),
+            Some((&"uci", _rest)) => {
+                writeln!(
+                    out,
+                    "id name impossible-tic-tac-toe {}",
+                    env!("CARGO_PKG_VERSION")
+                )?;
+                writeln!(out, "id author {}", env!("CARGO_PKG_AUTHORS"))?;
+                writeln!(out, "uciok")?;
+                Ok(())
+            }
+            Some((&"isready", _rest)) => {
+                writeln!(out, "readyok")?;
+                Ok(())
+            }
+            Some((&"newgame", _rest)) => {
+                self.game = Game::with_config(self.game.board().config());
+                Ok(())
+            }
+            Some((&"position", rest)) => self.set_position(rest),
+            Some((&"go", rest)) => match parse_limits(rest, &self.engine) {
+                Ok(engine) => return self.go(engine, out).map(|_| true),
+                Err(e) => Err(e),
+            },
+            Some((command, _rest)) => Err(ProtocolError::UnknownCommand(command.to_string())),
+        };
+
+        if let Err(e) = result {
+            writeln!(out, "info string {}", e)?;
+        }
+        Ok(true)
+    }
+
+    fn set_position(&mut self, words: &[&str]) -> Result<(), ProtocolError> {
+        let (setup, moves) = match words.iter().position(|w| *w == "moves") {
+            Some(i) => (&words[..i], &words[i + 1..]),
+            None => (words, &[][..]),
+        };
+
+        let mut game = match setup {
+            ["startpos"] => Game::with_config(BoardConfig::default()),
+            ["startpos", config] => Game::with_config(notation::parse_config(config)?),
+            _ => Game::from_notation(&setup.join(" "))?,
+        };
+        let config = game.board().config();
+        for &text in moves {
+            let value = game.current_seat().value;
+            record::parse_coordinate(config, text)
+                .and_then(|c| game.play(Cell { value, ..c }))
+                .ok_or_else(|| ProtocolError::IllegalMove(text.to_string()))?;
+        }
+
+        self.game = game;
+        Ok(())
+    }
+
+    fn go<W: Write>(&self, mut engine: Engine, out: &mut W) -> io::Result<()> {
+        if self.game.outcome() != Outcome::InProgress {
+            return writeln!(out, "bestmove none");
+        }
+
+        let board = self.game.board();
+        let mut written = Ok(());
+        let best = engine.analyse(board, self.game.current_seat().value, |info| {
+            if written.is_ok() {
+                written = writeln!(out, "{}", format_info(board.config(), info));
+            }
+        });
+        written?;
+
+        match best {
+            Some(cell) => writeln!(
+                out,
+                "bestmove {}",
+                record::format_coordinate(board.config(), &cell)
+            ),
+            None => writeln!(out, "bestmove none"),
+        }
+    }
+}
+
+/// A copy of `engine` with the limits of a `go` command. Without a time
+/// limit, a search limited by depth or positions runs until it reaches them.
+fn parse_limits(words: &[&str], engine: &Engine) -> Result<Engine, ProtocolError> {
+    let mut limited = engine.clone();
+    let mut has_time = false;
+    let mut has_other_limit = false;
+
+    for pair in words.chunks(2) {
+        let bad_limit = || ProtocolError::BadLimit(pair.join(" "));
+        let (name, value) = match pair {
+            [name, value] => (*name, *value),
+            _ => return Err(bad_limit()),
+        };
+        match name {
+            "depth" => limited.max_depth = value.parse().map_err(|_e| bad_limit())?,
+            "nodes" => limited.max_nodes = value.parse().map_err(|_e| bad_limit())?,
+            "time" => limited.time_budget_ms = value.parse().map_err(|_e| bad_limit())?,
+            _ => return Err(bad_limit()),
+        }
+        has_time |= name == "time";
+        has_other_limit |= name != "time";
+    }
+
+    if has_other_limit && !has_time {
+        limited.time_budget_ms = f64::INFINITY;
+    }
+    Ok(limited)
+}
+
+fn format_info(config: BoardConfig, info: &SearchInfo) -> String {
+    let score = match info.plies_to_win() {
+        Some(plies) => format!("mate {}", plies),
+        None => format!("cp {}", info.score),
+    };
+    let pv: Vec<String> = info
+        .pv
+        .iter()
+        .map(|&index| {
+            let (row, column, layer) = config.coordinates(index);
+            let cell = Cell {
+                row,
+                column,
+                layer,
+                value: CellValue::Empty,
+            };
+            record::format_coordinate(config, &cell)
+        })
+        .collect();
+
+    format!(
+        "info depth {} score {} nodes {} time {} pv {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.time_ms.round() as u64,
+        pv.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::protocol::Session;
+
+    fn run(commands: &[&str]) -> Vec<String> {
+        let mut session = Session::default();
+        let mut out = vec![];
+        for command in commands {
+            session.handle(command, &mut out).unwrap();
+        }
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_handle__when__uci() {
+        let actual = run(&["uci", "isready"]);
+
+        assert!(actual[0].starts_with("id name impossible-tic-tac-toe"));
+        assert_eq!(actual[actual.len() - 2..], ["uciok", "readyok"]);
+    }
+
+    #[test]
+    fn test_handle__when__go_finds_win() {
+        let actual = run(&["position startpos moves a1 a2 b1 b2", "go"]);
+
+        assert!(actual.iter().any(|l| l.contains("score mate 1 ")));
+        assert_eq!(actual.last().unwrap(), "bestmove c1");
+    }
+
+    #[test]
+    fn test_handle__when__position_from_notation() {
+        let actual = run(&["position oo./xx./..x o 3x3:3", "go depth 2"]);
+
+        assert_eq!(actual.last().unwrap(), "bestmove c3");
+    }
+
+    #[test]
+    fn test_handle__when__node_limit() {
+        let actual = run(&["position startpos 4x4x4:4", "go nodes 2000"]);
+
+        assert!(actual.iter().all(|l| !l.starts_with("info string")));
+        assert!(actual.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_handle__when__illegal_move() {
+        let actual = run(&["position startpos moves b2 b2"]);
+        let expected = vec!["info string illegal move b2".to_string()];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_handle__when__board_can_not_be_played() {
+        let actual = run(&["position startpos 3x3:0", "go depth 1"]);
+
+        assert_eq!(
+            actual[0],
+            "info string bad position: a game can not be played on the board"
+        );
+        assert!(actual.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn test_handle__when__game_over() {
+        let actual = run(&["position startpos moves a1 a2 b1 b2 c1", "go"]);
+        let expected = vec!["bestmove none".to_string()];
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,531 @@
+use crate::agent::Agent;
+use crate::board::CellValue::Human;
+use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome};
+use crate::symmetry;
+use crate::tournament;
+use crate::utils::{self, Random};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum QLearningError {
+    /// Only square boards without gravity have the symmetries it relies on
+    UnsupportedBoard,
+    BadTable(String),
+    /// The table was learned on another board than the one it is used on
+    WrongBoard,
+}
+
+impl fmt::Display for QLearningError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QLearningError::UnsupportedBoard => write!(f, "only flat square boards can be learned"),
+            QLearningError::BadTable(e) => write!(f, "bad Q-table: {}", e),
+            QLearningError::WrongBoard => write!(f, "the Q-table was learned on another board"),
+        }
+    }
+}
+
+/// What the player to move can expect from each move in every position it
+/// has learned, stored like `Menace` stores its boxes. Playing the move
+/// with the highest value is all a learned player needs, so this is what is
+/// saved and given to a `Game`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct QTable {
+    config: BoardConfig,
+    values: BTreeMap<String, Vec<f64>>,
+}
+
+impl QTable {
+    pub fn new(config: BoardConfig) -> Result<QTable, QLearningError> {
+        if !symmetry::has_symmetries(config) {
+            return Err(QLearningError::UnsupportedBoard);
+        }
+        Ok(QTable {
+            config,
+            values: BTreeMap::new(),
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<QTable, QLearningError> {
+        let table: QTable = utils::from_json(json).map_err(QLearningError::BadTable)?;
+        QTable::new(table.config)?;
+
+        let size = table.config.size();
+        if let Some((key, _values)) = table.values.iter().find(|(k, v)| {
+            k.len() != size || v.len() != size || !k.chars().all(|c| ".xo".contains(c))
+        }) {
+            return Err(QLearningError::BadTable(format!("bad position {}", key)));
+        }
+        Ok(table)
+    }
+
+    pub fn to_json(&self) -> String {
+        utils::to_json(self, false)
+    }
+
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
+
+    /// Positions it has values for
+    pub fn positions(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The move with the highest value for `value` to play, or `None` for a
+    /// position that was never learned, so that something else can choose
+    pub fn best_move(&self, board: &Board, value: CellValue) -> Option<Cell> {
+        if board.config() != self.config || board.outcome() != Outcome::InProgress {
+            return None;
+        }
+
+        let (key, orientation) = symmetry::canonical(self.config, &board.values(), value);
+        let values = self.values.get(&key)?;
+        let best = legal_moves(&key)
+            .max_by(|&a, &b| values[a].partial_cmp(&values[b]).unwrap().then(b.cmp(&a)))?;
+        Some(self.cell(orientation, best, value))
+    }
+
+    fn cell(&self, orientation: usize, index: usize, value: CellValue) -> Cell {
+        let index = symmetry::untransform(self.config, orientation, index);
+        let (row, column, layer) = self.config.coordinates(index);
+        Cell {
+            row,
+            column,
+            layer,
+            value,
+        }
+    }
+}
+
+impl Agent for QTable {
+    fn name(&self) -> String {
+        "qlearning".to_string()
+    }
+
+    /// Plays its first empty position in canonical form where it has
+    /// learned nothing, rather than giving up the game
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        if board.config() != self.config || board.outcome() != Outcome::InProgress {
+            return None;
+        }
+
+        self.best_move(board, value).or_else(|| {
+            let (key, orientation) = symmetry::canonical(self.config, &board.values(), value);
+            let first = legal_moves(&key).next()?;
+            Some(self.cell(orientation, first, value))
+        })
+    }
+}
+
+/// Empty positions of a position in canonical form
+fn legal_moves(key: &str) -> impl Iterator<Item = usize> + '_ {
+    key.chars()
+        .enumerate()
+        .filter(|(_i, c)| *c == '.')
+        .map(|(i, _c)| i)
+}
+
+/// The reward at the end of a game, and for every move before it so that
+/// shorter or longer games can be preferred
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Rewards {
+    pub win: f64,
+    pub draw: f64,
+    pub loss: f64,
+    pub step: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            win: 1.0,
+            draw: 0.5,
+            loss: -1.0,
+            step: 0.0,
+        }
+    }
+}
+
+/// How often a random move is made instead of the best one, falling in a
+/// straight line from `start` to `end` over the first `games` games
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Epsilon {
+    pub start: f64,
+    pub end: f64,
+    pub games: u32,
+}
+
+impl Default for Epsilon {
+    fn default() -> Self {
+        Epsilon {
+            start: 1.0,
+            end: 0.05,
+            games: 5000,
+        }
+    }
+}
+
+impl Epsilon {
+    pub fn after(&self, games: u32) -> f64 {
+        let done = (games as f64 / self.games.max(1) as f64).min(1.0);
+        self.start + (self.end - self.start) * done
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct Settings {
+    pub learning_rate: f64,
+    /// How much a value counts from one move to the one before it
+    pub discount: f64,
+    pub epsilon: Epsilon,
+    pub rewards: Rewards,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            learning_rate: 0.3,
+            discount: 0.95,
+            epsilon: Epsilon::default(),
+            rewards: Rewards::default(),
+        }
+    }
+}
+
+/// How the games of one epoch of training went, counted for the learner
+#[derive(Debug, PartialEq, Clone)]
+pub struct Epoch {
+    pub epoch: u32,
+    pub games: u32,
+    /// Chance of a random move at the end of the epoch
+    pub epsilon: f64,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub positions: usize,
+}
+
+/// The epochs as CSV, with the win and draw rates of each
+pub fn curve_csv(epochs: &[Epoch]) -> String {
+    let mut csv =
+        "epoch,games,epsilon,wins,draws,losses,win_rate,draw_rate,positions\n".to_string();
+    for e in epochs {
+        let games = (e.wins + e.draws + e.losses).max(1) as f64;
+        csv += &format!(
+            "{},{},{:.4},{},{},{},{:.4},{:.4},{}\n",
+            e.epoch,
+            e.games,
+            e.epsilon,
+            e.wins,
+            e.draws,
+            e.losses,
+            e.wins as f64 / games,
+            e.draws as f64 / games,
+            e.positions
+        );
+    }
+    csv
+}
+
+/// Tabular Q-learning: after every move it nudges the value of the move it
+/// made before towards what the new position promises, and at the end of a
+/// game towards the reward, while making random moves now and then to find
+/// better ones
+pub struct QLearner {
+    table: QTable,
+    settings: Settings,
+    random: Random,
+    games: u32,
+    // The position and move each side last played and has not learned from
+    pending: Vec<(CellValue, String, usize)>,
+}
+
+impl QLearner {
+    pub fn new(
+        config: BoardConfig,
+        settings: Settings,
+        seed: u64,
+    ) -> Result<QLearner, QLearningError> {
+        Ok(QLearner::with_table(QTable::new(config)?, settings, seed))
+    }
+
+    /// A learner that carries on from a table learned before
+    pub fn with_table(table: QTable, settings: Settings, seed: u64) -> QLearner {
+        QLearner {
+            table,
+            settings,
+            random: Random::new(seed),
+            games: 0,
+            pending: vec![],
+        }
+    }
+
+    pub fn table(&self) -> &QTable {
+        &self.table
+    }
+
+    pub fn epsilon(&self) -> f64 {
+        self.settings.epsilon.after(self.games)
+    }
+
+    /// Plays a game against itself and learns from both sides of it
+    pub fn play_self(&mut self) -> Outcome {
+        let mut board = Board::new(self.table.config);
+        let mut value = Human;
+        while board.outcome() == Outcome::InProgress {
+            let cell = self.choose(&board, value).unwrap();
+            board.set(cell).unwrap();
+            value = value.opponent();
+        }
+
+        let outcome = board.outcome();
+        self.finish(outcome);
+        outcome
+    }
+
+    /// Trains for `epochs` epochs of `games` games each, against `opponent`
+    /// taking turns to move first, or against itself without one. In
+    /// self-play the results are counted for the player who moves first.
+    pub fn train(
+        &mut self,
+        mut opponent: Option<&mut dyn Agent>,
+        epochs: u32,
+        games: u32,
+    ) -> Vec<Epoch> {
+        let config = self.table.config;
+        (1..=epochs)
+            .map(|epoch| {
+                let (wins, draws, losses) = match opponent.as_mut() {
+                    Some(opponent) => {
+                        let p = tournament::play_match(config, self, &mut **opponent, games);
+                        (p.wins, p.draws, p.losses)
+                    }
+                    None => (0..games).fold((0, 0, 0), |(w, d, l), _game| match self.play_self() {
+                        Outcome::Winner(Human) => (w + 1, d, l),
+                        Outcome::Winner(_value) => (w, d, l + 1),
+                        _ => (w, d + 1, l),
+                    }),
+                };
+                Epoch {
+                    epoch,
+                    games: self.games,
+                    epsilon: self.epsilon(),
+                    wins,
+                    draws,
+                    losses,
+                    positions: self.table.positions(),
+                }
+            })
+            .collect()
+    }
+
+    /// The highest value of any move in a position, 0 for one never seen
+    fn best_value(&self, key: &str) -> f64 {
+        match self.table.values.get(key) {
+            Some(values) => legal_moves(key)
+                .map(|i| values[i])
+                .fold(f64::NEG_INFINITY, f64::max),
+            None => 0.0,
+        }
+    }
+
+    fn learn(&mut self, key: &str, index: usize, target: f64) {
+        let size = self.table.config.size();
+        let values = self
+            .table
+            .values
+            .entry(key.to_string())
+            .or_insert_with(|| vec![0.0; size]);
+        values[index] += self.settings.learning_rate * (target - values[index]);
+    }
+}
+
+impl Agent for QLearner {
+    fn name(&self) -> String {
+        "qlearning".to_string()
+    }
+
+    fn choose(&mut self, board: &Board, value: CellValue) -> Option<Cell> {
+        let config = self.table.config;
+        if board.config() != config || board.outcome() != Outcome::InProgress {
+            return None;
+        }
+        let (key, orientation) = symmetry::canonical(config, &board.values(), value);
+
+        // The move before leads here, so it is worth what this position is
+        if let Some(i) = self.pending.iter().position(|(v, _k, _i)| *v == value) {
+            let (_value, last_key, last_index) = self.pending.remove(i);
+            let target =
+                self.settings.rewards.step + self.settings.discount * self.best_value(&key);
+            self.learn(&last_key, last_index, target);
+        }
+
+        let moves: Vec<usize> = legal_moves(&key).collect();
+        let is_exploring = (self.random.below(1_000_000) as f64) < self.epsilon() * 1_000_000.0;
+        let index = if is_exploring || !self.table.values.contains_key(&key) {
+            moves[self.random.below(moves.len())]
+        } else {
+            let values = &self.table.values[&key];
+            let best = self.best_value(&key);
+            let best_moves: Vec<usize> = moves.into_iter().filter(|&i| values[i] == best).collect();
+            best_moves[self.random.below(best_moves.len())]
+        };
+
+        self.pending.push((value, key, index));
+        Some(self.table.cell(orientation, index, value))
+    }
+
+    fn finish(&mut self, outcome: Outcome) {
+        let rewards = self.settings.rewards;
+        for (value, key, index) in std::mem::take(&mut self.pending) {
+            let reward = match outcome {
+                Outcome::Winner(winner) if winner == value => rewards.win,
+                Outcome::Winner(_winner) => rewards.loss,
+                _ => rewards.draw,
+            };
+            self.learn(&key, index, rewards.step + reward);
+        }
+        self.games += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::agent::{Agent, RandomAgent};
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Board, BoardConfig, Cell, Outcome};
+    use crate::qlearning::{curve_csv, Epoch, Epsilon, QLearner, QLearningError, QTable, Settings};
+    use crate::tournament::play_match;
+
+    fn cell(row: u8, column: u8, value: crate::board::CellValue) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_epsilon__after() {
+        let epsilon = Epsilon {
+            start: 1.0,
+            end: 0.0,
+            games: 100,
+        };
+
+        let actual = [epsilon.after(0), epsilon.after(50), epsilon.after(500)];
+        let expected = [1.0, 0.5, 0.0];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_finish__learns_winning_move() {
+        let mut learner = QLearner::new(BoardConfig::default(), Settings::default(), 1).unwrap();
+        let mut board = Board::default();
+        for &(row, column, value) in &[(0, 0, Human), (1, 0, CPU), (0, 1, Human), (1, 1, CPU)] {
+            board.set(cell(row, column, value)).unwrap();
+        }
+
+        for _game in 0..50 {
+            let chosen = learner.choose(&board, Human).unwrap();
+            let mut after = board.clone();
+            after.set(chosen).unwrap();
+            let outcome = match after.outcome() {
+                Outcome::InProgress => Outcome::Winner(CPU),
+                outcome => outcome,
+            };
+            learner.finish(outcome);
+        }
+        let actual = learner.table().best_move(&board, Human);
+        let expected = Some(cell(0, 2, Human));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_best_move__when__position_not_learned() {
+        let mut table = QTable::new(BoardConfig::default()).unwrap();
+        table.values.insert(".........".to_string(), vec![0.5; 9]);
+        let mut board = Board::default();
+        board.set(cell(1, 1, CPU)).unwrap();
+
+        let actual = table.best_move(&board, Human);
+        let expected = None;
+
+        assert_eq!(actual, expected);
+        assert!(table.choose(&board, Human).is_some());
+    }
+
+    #[test]
+    fn test_train__beats_random_play() {
+        let config = BoardConfig::default();
+        let settings = Settings {
+            epsilon: Epsilon {
+                games: 2000,
+                ..Epsilon::default()
+            },
+            ..Settings::default()
+        };
+        let mut learner = QLearner::new(config, settings, 1).unwrap();
+
+        let epochs = learner.train(None, 5, 600);
+        let mut table = learner.table().clone();
+        let actual = play_match(config, &mut table, &mut RandomAgent::new(2), 100);
+
+        assert_eq!(epochs.len(), 5);
+        assert!(epochs[4].epsilon < epochs[0].epsilon);
+        assert!(actual.losses < 10);
+        assert!(actual.wins > 60);
+    }
+
+    #[test]
+    fn test_from_json() {
+        let mut table = QTable::new(BoardConfig::default()).unwrap();
+        table.values.insert(".........".to_string(), vec![0.5; 9]);
+
+        let actual = QTable::from_json(&table.to_json());
+
+        assert_eq!(actual, Ok(table));
+    }
+
+    #[test]
+    fn test_from_json__when__bad_position() {
+        let mut table = QTable::new(BoardConfig::default()).unwrap();
+        table.values.insert("....".to_string(), vec![0.5; 4]);
+
+        let actual = QTable::from_json(&table.to_json());
+        let expected = Err(QLearningError::BadTable("bad position ....".to_string()));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_curve_csv() {
+        let epochs = [Epoch {
+            epoch: 1,
+            games: 10,
+            epsilon: 0.5,
+            wins: 6,
+            draws: 3,
+            losses: 1,
+            positions: 42,
+        }];
+
+        let actual = curve_csv(&epochs);
+        let expected = "epoch,games,epsilon,wins,draws,losses,win_rate,draw_rate,positions\n\
+                        1,10,0.5000,6,3,1,0.6000,0.3000,42\n";
+
+        assert_eq!(actual, expected);
+        assert_eq!(
+            QTable::new(BoardConfig::default()).unwrap().name(),
+            "qlearning"
+        );
+    }
+}
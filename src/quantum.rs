@@ -0,0 +1,566 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{Board, Cell, CellValue, Outcome};
+
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+/// A mark that is in two cells at once until it collapses into one of them
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+pub struct SpookyMark {
+    pub player: CellValue,
+    pub turn: u8,
+    pub cells: (usize, usize),
+}
+
+impl SpookyMark {
+    fn other_cell(&self, cell: usize) -> usize {
+        if self.cells.0 == cell {
+            self.cells.1
+        } else {
+            self.cells.0
+        }
+    }
+
+    fn touches(&self, cell: usize) -> bool {
+        self.cells.0 == cell || self.cells.1 == cell
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct QuantumCell {
+    pub row: u8,
+    pub column: u8,
+    pub value: CellValue,
+    pub turn: Option<u8>,
+}
+
+/// Everything the front-end needs to draw the board. Marks refer to cells by
+/// their position in `cells`.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct EntanglementGraph {
+    pub cells: Vec<QuantumCell>,
+    pub marks: Vec<SpookyMark>,
+    pub pending_collapse: Option<SpookyMark>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum QuantumError {
+    GameOver,
+    CollapsePending,
+    NoCollapsePending,
+    OutOfRange,
+    SameCell,
+    Classical,
+    NotInCycle,
+}
+
+impl fmt::Display for QuantumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            QuantumError::GameOver => "the game is over",
+            QuantumError::CollapsePending => "a cycle has to be collapsed first",
+            QuantumError::NoCollapsePending => "there is no cycle to collapse",
+            QuantumError::OutOfRange => "the cell is outside of the board",
+            QuantumError::SameCell => "a spooky mark needs two different cells",
+            QuantumError::Classical => "the cell already has a classical mark",
+            QuantumError::NotInCycle => "the mark cannot collapse into that cell",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum QuantumAction {
+    Place(usize, usize),
+    Collapse(usize),
+}
+
+/// Quantum tic-tac-toe. Every move puts a spooky mark in two cells. When
+/// the marks form a cycle the player who did not close it picks where the
+/// last mark collapses to, and every mark in the cycle becomes classical.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct QuantumGame {
+    board: Board,
+    turns: Vec<Option<u8>>,
+    marks: Vec<SpookyMark>,
+    pending_collapse: Option<SpookyMark>,
+    turn: u8,
+}
+
+impl Default for QuantumGame {
+    fn default() -> Self {
+        QuantumGame {
+            board: Board::default(),
+            turns: vec![None; 9],
+            marks: vec![],
+            pending_collapse: None,
+            turn: 1,
+        }
+    }
+}
+
+impl QuantumGame {
+    /// Human plays odd turns and the CPU plays even turns. A pending cycle
+    /// is collapsed by the player whose turn it is.
+    pub fn current_player(&self) -> CellValue {
+        if self.turn % 2 == 1 {
+            Human
+        } else {
+            CPU
+        }
+    }
+
+    pub fn graph(&self) -> EntanglementGraph {
+        let cells = self
+            .board
+            .get_cells()
+            .into_iter()
+            .zip(self.turns.iter())
+            .map(|(c, turn)| QuantumCell {
+                row: c.row,
+                column: c.column,
+                value: c.value,
+                turn: *turn,
+            })
+            .collect();
+
+        EntanglementGraph {
+            cells,
+            marks: self.marks.clone(),
+            pending_collapse: self.pending_collapse,
+        }
+    }
+
+    pub fn place(&mut self, first: usize, second: usize) -> Result<(), QuantumError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(QuantumError::GameOver);
+        }
+        if self.pending_collapse.is_some() {
+            return Err(QuantumError::CollapsePending);
+        }
+        if first >= self.turns.len() || second >= self.turns.len() {
+            return Err(QuantumError::OutOfRange);
+        }
+        if self.turns[first].is_some() || self.turns[second].is_some() {
+            return Err(QuantumError::Classical);
+        }
+
+        let player = self.current_player();
+        let turn = self.turn;
+
+        if first == second {
+            // Only the last free cell can be played classically
+            let free_cells = self.turns.iter().filter(|t| t.is_none()).count();
+            if free_cells != 1 {
+                return Err(QuantumError::SameCell);
+            }
+            self.set_classical(first, player, turn);
+            self.turn += 1;
+            return Ok(());
+        }
+
+        let mark = SpookyMark {
+            player,
+            turn,
+            cells: (first, second),
+        };
+        if self.is_connected(first, second) {
+            self.pending_collapse = Some(mark);
+        } else {
+            self.marks.push(mark);
+        }
+        self.turn += 1;
+
+        Ok(())
+    }
+
+    pub fn collapse(&mut self, cell: usize) -> Result<(), QuantumError> {
+        let mark = self
+            .pending_collapse
+            .ok_or(QuantumError::NoCollapsePending)?;
+        if !mark.touches(cell) {
+            return Err(QuantumError::NotInCycle);
+        }
+
+        self.pending_collapse = None;
+        self.set_classical(cell, mark.player, mark.turn);
+
+        // Every other mark in a collapsed cell is pushed into its other cell
+        let mut collapsed = vec![cell];
+        while let Some(cell) = collapsed.pop() {
+            let (touching, rest): (Vec<SpookyMark>, Vec<SpookyMark>) =
+                self.marks.iter().partition(|m| m.touches(cell));
+            self.marks = rest;
+
+            for m in touching {
+                let other = m.other_cell(cell);
+                self.set_classical(other, m.player, m.turn);
+                collapsed.push(other);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply(&mut self, action: QuantumAction) -> Result<(), QuantumError> {
+        match action {
+            QuantumAction::Place(first, second) => self.place(first, second),
+            QuantumAction::Collapse(cell) => self.collapse(cell),
+        }
+    }
+
+    pub fn actions(&self) -> Vec<QuantumAction> {
+        if self.outcome() != Outcome::InProgress {
+            return vec![];
+        }
+
+        if let Some(mark) = self.pending_collapse {
+            return vec![
+                QuantumAction::Collapse(mark.cells.0),
+                QuantumAction::Collapse(mark.cells.1),
+            ];
+        }
+
+        let free_cells: Vec<usize> = (0..self.turns.len())
+            .filter(|&i| self.turns[i].is_none())
+            .collect();
+        if free_cells.len() == 1 {
+            return vec![QuantumAction::Place(free_cells[0], free_cells[0])];
+        }
+
+        free_cells
+            .iter()
+            .enumerate()
+            .flat_map(|(n, &first)| {
+                free_cells[n + 1..]
+                    .iter()
+                    .map(move |&second| QuantumAction::Place(first, second))
+            })
+            .collect()
+    }
+
+    /// When both players complete a line in the same collapse, the line
+    /// whose last mark was played earliest wins
+    pub fn outcome(&self) -> Outcome {
+        let values = self.board.values();
+        let winner = self
+            .board
+            .win_lines()
+            .iter()
+            .filter(|line| {
+                values[line[0]] != Empty && line.iter().all(|&i| values[i] == values[line[0]])
+            })
+            .map(|line| {
+                let last_turn = line.iter().filter_map(|&i| self.turns[i]).max();
+                (last_turn, values[line[0]])
+            })
+            .min_by_key(|(last_turn, _value)| *last_turn)
+            .map(|(_last_turn, value)| value);
+
+        match winner {
+            Some(value) => Outcome::Winner(value),
+            None if self.turns.iter().all(Option::is_some) => Outcome::Draw,
+            None => Outcome::InProgress,
+        }
+    }
+
+    fn set_classical(&mut self, index: usize, player: CellValue, turn: u8) {
+        let (row, column, layer) = self.board.config().coordinates(index);
+        self.board.set(Cell {
+            row,
+            column,
+            layer,
+            value: player,
+        });
+        self.turns[index] = Some(turn);
+    }
+
+    fn is_connected(&self, from: usize, to: usize) -> bool {
+        let mut visited = vec![from];
+        let mut queue = vec![from];
+        while let Some(cell) = queue.pop() {
+            if cell == to {
+                return true;
+            }
+            for m in self.marks.iter().filter(|m| m.touches(cell)) {
+                let other = m.other_cell(cell);
+                if !visited.contains(&other) {
+                    visited.push(other);
+                    queue.push(other);
+                }
+            }
+        }
+        false
+    }
+}
+
+#[wasm_bindgen]
+impl QuantumGame {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> QuantumGame {
+        QuantumGame::default()
+    }
+
+    #[wasm_bindgen(js_name = getGraph)]
+    pub fn get_graph(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.graph()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = isCollapsePending)]
+    pub fn is_collapse_pending(&self) -> bool {
+        self.pending_collapse.is_some()
+    }
+
+    #[wasm_bindgen(js_name = humanPlay)]
+    pub fn human_play(
+        &mut self,
+        row: u8,
+        column: u8,
+        row2: u8,
+        column2: u8,
+    ) -> Result<(), JsValue> {
+        let config = self.board.config();
+        let first = config.index(row, column, 0);
+        let second = config.index(row2, column2, 0);
+        match (first, second) {
+            (Some(first), Some(second)) => self.place(first, second),
+            _ => Err(QuantumError::OutOfRange),
+        }
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = humanCollapse)]
+    pub fn human_collapse(&mut self, row: u8, column: u8) -> Result<(), JsValue> {
+        self.board
+            .config()
+            .index(row, column, 0)
+            .ok_or(QuantumError::OutOfRange)
+            .and_then(|cell| self.collapse(cell))
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Plays for the CPU until it is the human's turn again, which includes
+    /// collapsing a cycle the human closed
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        let mut engine = QuantumEngine::default();
+        while self.current_player() == CPU {
+            match engine.find_best_action(self) {
+                Some(action) => self.apply(action).unwrap(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = QuantumGame::default();
+    }
+}
+
+/// Looks a few actions ahead for the CPU, assuming the human picks the
+/// collapses that are worst for it
+pub struct QuantumEngine {
+    pub depth: u8,
+}
+
+impl Default for QuantumEngine {
+    fn default() -> Self {
+        QuantumEngine { depth: 2 }
+    }
+}
+
+impl QuantumEngine {
+    pub fn find_best_action(&mut self, game: &QuantumGame) -> Option<QuantumAction> {
+        let maximize = game.current_player() == CPU;
+        let scored = game.actions().into_iter().map(|action| {
+            let mut next = game.clone();
+            next.apply(action).unwrap();
+            (action, self.minimax(&next, self.depth.saturating_sub(1)))
+        });
+
+        if maximize {
+            scored.max_by_key(|(_action, score)| *score)
+        } else {
+            scored.min_by_key(|(_action, score)| *score)
+        }
+        .map(|(action, _score)| action)
+    }
+
+    /// Score from the CPU's point of view
+    fn minimax(&self, game: &QuantumGame, depth: u8) -> i32 {
+        match game.outcome() {
+            Outcome::Winner(CPU) => return 1000 + depth as i32,
+            Outcome::Winner(_) => return -1000 - depth as i32,
+            Outcome::Draw => return 0,
+            Outcome::InProgress => {}
+        }
+        if depth == 0 {
+            return self.evaluate(game);
+        }
+
+        let scores = game.actions().into_iter().map(|action| {
+            let mut next = game.clone();
+            next.apply(action).unwrap();
+            self.minimax(&next, depth - 1)
+        });
+
+        if game.current_player() == CPU {
+            scores.max().unwrap_or(0)
+        } else {
+            scores.min().unwrap_or(0)
+        }
+    }
+
+    /// Classical marks count for more than spooky ones, and only in lines
+    /// the opponent has no classical mark in
+    fn evaluate(&self, game: &QuantumGame) -> i32 {
+        let values = game.board.values();
+        let spooky_count = |cell: usize, player: CellValue| {
+            game.marks
+                .iter()
+                .filter(|m| m.player == player && m.touches(cell))
+                .count() as i32
+        };
+
+        game.board
+            .win_lines()
+            .iter()
+            .map(|line| {
+                let count = |player: CellValue| {
+                    line.iter()
+                        .map(|&i| {
+                            if values[i] == player {
+                                4
+                            } else {
+                                spooky_count(i, player)
+                            }
+                        })
+                        .sum::<i32>()
+                };
+                let is_open_for =
+                    |player: CellValue| line.iter().all(|&i| values[i] != player.opponent());
+
+                let cpu = if is_open_for(CPU) { count(CPU) } else { 0 };
+                let human = if is_open_for(Human) { count(Human) } else { 0 };
+                cpu - human
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Empty, Human, CPU};
+    use crate::board::Outcome;
+    use crate::quantum::{QuantumAction, QuantumEngine, QuantumError, QuantumGame};
+
+    #[test]
+    fn test_place__when__same_cell() {
+        let mut game = QuantumGame::default();
+
+        let actual = game.place(4, 4);
+        let expected = Err(QuantumError::SameCell);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_place__when__no_cycle() {
+        let mut game = QuantumGame::default();
+        game.place(0, 1).unwrap();
+        game.place(1, 2).unwrap();
+
+        let graph = game.graph();
+
+        assert_eq!(graph.marks.len(), 2);
+        assert_eq!(graph.pending_collapse, None);
+        assert_eq!(game.current_player(), Human);
+    }
+
+    #[test]
+    fn test_place__when__cycle() {
+        let mut game = QuantumGame::default();
+        game.place(0, 1).unwrap();
+        game.place(1, 2).unwrap();
+        game.place(2, 0).unwrap();
+
+        let actual = game.actions();
+        let expected = vec![QuantumAction::Collapse(2), QuantumAction::Collapse(0)];
+
+        assert_eq!(actual, expected);
+        assert_eq!(game.place(3, 4), Err(QuantumError::CollapsePending));
+    }
+
+    #[test]
+    fn test_collapse__collapses_whole_cycle() {
+        let mut game = QuantumGame::default();
+        game.place(0, 1).unwrap(); // X1
+        game.place(1, 2).unwrap(); // O2
+        game.place(2, 0).unwrap(); // X3
+        game.collapse(0).unwrap();
+
+        let graph = game.graph();
+        let values: Vec<_> = graph.cells.iter().map(|c| (c.value, c.turn)).collect();
+
+        assert_eq!(values[0], (Human, Some(3)));
+        assert_eq!(values[1], (Human, Some(1)));
+        assert_eq!(values[2], (CPU, Some(2)));
+        assert_eq!(values[3], (Empty, None));
+        assert!(graph.marks.is_empty());
+    }
+
+    #[test]
+    fn test_collapse__when__not_in_cycle() {
+        let mut game = QuantumGame::default();
+        game.place(0, 1).unwrap();
+        game.place(0, 1).unwrap();
+
+        let actual = game.collapse(5);
+        let expected = Err(QuantumError::NotInCycle);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__earliest_line_wins() {
+        let mut game = QuantumGame::default();
+        game.set_classical(0, Human, 1);
+        game.set_classical(1, Human, 3);
+        game.set_classical(2, Human, 7);
+        game.set_classical(3, CPU, 2);
+        game.set_classical(4, CPU, 4);
+        game.set_classical(5, CPU, 6);
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_action__when__collapse_wins() {
+        let mut engine = QuantumEngine::default();
+        let mut game = QuantumGame::default();
+        game.set_classical(0, CPU, 2);
+        game.set_classical(1, CPU, 4);
+        game.turn = 5;
+        game.place(5, 8).unwrap(); // X5
+        game.place(2, 8).unwrap(); // O6
+        game.place(2, 5).unwrap(); // X7, closes the cycle
+
+        let actual = engine.find_best_action(&game);
+        let expected = Some(QuantumAction::Collapse(5));
+
+        assert_eq!(actual, expected);
+    }
+}
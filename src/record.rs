@@ -0,0 +1,565 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{BoardConfig, Cell, Outcome};
+use crate::game::{Game, Variant};
+use crate::notation::{self, NotationError};
+
+use std::fmt;
+
+// Tags written for every game, in this order
+const VARIANT: &str = "Variant";
+const HUMAN: &str = "Human";
+const CPU_TAG: &str = "Cpu";
+const LEVEL: &str = "Level";
+const DATE: &str = "Date";
+const RESULT: &str = "Result";
+// Only written for games that did not start on an empty board
+const POSITION: &str = "Position";
+
+const UNKNOWN: &str = "?";
+
+/// Who played a game and when, as it is written in the tags of a record.
+/// Anything not known is written as `?`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Metadata {
+    pub human: String,
+    pub cpu: String,
+    pub level: String,
+    pub date: String,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            human: UNKNOWN.to_string(),
+            cpu: UNKNOWN.to_string(),
+            level: UNKNOWN.to_string(),
+            date: UNKNOWN.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RecordErrorKind {
+    BadTag,
+    TagAfterMoves,
+    UnknownVariant,
+    BadPosition(NotationError),
+    BadCoordinate,
+    IllegalMove,
+    ResultMismatch(String),
+}
+
+/// What went wrong and where, counting lines and columns from 1
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordError {
+    pub kind: RecordErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            RecordErrorKind::BadTag => write!(f, "a tag looks like [Name \"value\"]"),
+            RecordErrorKind::TagAfterMoves => write!(f, "tags have to come before the moves"),
+            RecordErrorKind::UnknownVariant => write!(f, "the variant is not known"),
+            RecordErrorKind::BadPosition(e) => write!(f, "{}", e),
+            RecordErrorKind::BadCoordinate => write!(f, "a move looks like b2, or b2:3 in 3D"),
+            RecordErrorKind::IllegalMove => write!(f, "the move is not allowed"),
+            RecordErrorKind::ResultMismatch(result) => {
+                write!(f, "the moves end the game as {}", result)
+            }
+        }
+    }
+}
+
+impl RecordError {
+    fn new(kind: RecordErrorKind, line: usize, column: usize) -> Self {
+        RecordError { kind, line, column }
+    }
+}
+
+/// Writes a game as a record. The tags come first, one per line, followed
+/// by an empty line and the moves:
+///
+/// ```text
+/// [Variant "classic"]
+/// [Human "Ada"]
+/// [Cpu "impossible-tic-tac-toe"]
+/// [Level "perfect"]
+/// [Date "2024.05.01"]
+/// [Result "1/2-1/2"]
+///
+/// b2 a1 c3 a3 a2 c2 b3 b1 c1
+/// ```
+///
+/// The variant is `classic`, `qubic` or `connect-four`, or else the board
+/// size as in `notation::format_config`. The result is `1-0` when the human
+/// wins, `0-1` when the CPU wins, `1/2-1/2` for a draw and `*` for a game
+/// that is not over. A game that did not start on an empty board also has
+/// a `Position` tag with the start position in the notation of
+/// `notation::format`. Moves are a column letter and a row number, counting
+/// from the bottom left, with `:` and the layer after it on 3D boards.
+/// Columns after `z` are `aa`, `ab` and so on.
+pub fn format(game: &Game, metadata: &Metadata) -> Result<String, NotationError> {
+    let config = game.board().config();
+    let (start, to_move) = game.start_position();
+    // The result and the position only have symbols for two players
+    if game.seats().len() != 2 {
+        return Err(NotationError::UnsupportedValue);
+    }
+
+    let mut tags = vec![
+        (VARIANT, format_variant(config)),
+        (HUMAN, metadata.human.clone()),
+        (CPU_TAG, metadata.cpu.clone()),
+        (LEVEL, metadata.level.clone()),
+        (DATE, metadata.date.clone()),
+        (RESULT, format_result(game.outcome())),
+    ];
+    if start.values().iter().any(|v| *v != Empty) {
+        tags.push((POSITION, notation::format(&start, to_move)?));
+    }
+
+    let mut text: String = tags
+        .into_iter()
+        .map(|(name, value)| format!("[{} \"{}\"]\n", name, escape(&value)))
+        .collect();
+    text.push('\n');
+    let moves: Vec<String> = game
+        .history()
+        .iter()
+        .map(|c| format_coordinate(config, c))
+        .collect();
+    text.push_str(&moves.join(" "));
+    text.push('\n');
+    Ok(text)
+}
+
+/// Reads a record written by `format` and plays its moves, so the game has
+/// them in its history
+pub fn parse(text: &str) -> Result<(Game, Metadata), RecordError> {
+    let mut tags: Vec<(String, String, usize)> = vec![];
+    let mut moves: Vec<(&str, usize, usize)> = vec![];
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let trimmed = line.trim_start();
+        let indent = line.chars().count() - trimmed.chars().count();
+
+        if trimmed.starts_with('[') {
+            if !moves.is_empty() {
+                let kind = RecordErrorKind::TagAfterMoves;
+                return Err(RecordError::new(kind, line_number, indent + 1));
+            }
+            let (name, value) = parse_tag(trimmed.trim_end()).map_err(|column| {
+                RecordError::new(RecordErrorKind::BadTag, line_number, indent + column)
+            })?;
+            tags.push((name, value, line_number));
+            continue;
+        }
+
+        let mut start = None;
+        for (i, c) in line
+            .char_indices()
+            .chain(std::iter::once((line.len(), ' ')))
+        {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(i),
+                (true, Some(from)) => {
+                    let column = line[..from].chars().count() + 1;
+                    moves.push((&line[from..i], line_number, column));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let tag = |name: &str| tags.iter().find(|(tag, _value, _line)| tag == name);
+    let metadata_tag = |name: &str| {
+        tag(name)
+            .map(|(_tag, value, _line)| value.clone())
+            .unwrap_or_else(|| UNKNOWN.to_string())
+    };
+
+    let config = match tag(VARIANT) {
+        Some((_tag, value, line)) => parse_variant(value)
+            .ok_or_else(|| RecordError::new(RecordErrorKind::UnknownVariant, *line, 1))?,
+        None => BoardConfig::default(),
+    };
+
+    let mut game = match tag(POSITION) {
+        Some((_tag, value, line)) => {
+            let bad_position = |e| RecordError::new(RecordErrorKind::BadPosition(e), *line, 1);
+            let game = Game::from_notation(value).map_err(bad_position)?;
+            if game.board().config() != config {
+                return Err(bad_position(NotationError::SizeMismatch));
+            }
+            game
+        }
+        None => Game::with_config(config),
+    };
+
+    for (coordinate, line, column) in moves {
+        let cell = parse_coordinate(config, coordinate)
+            .ok_or_else(|| RecordError::new(RecordErrorKind::BadCoordinate, line, column))?;
+        let cell = Cell {
+            value: game.current_seat().value,
+            ..cell
+        };
+        game.play(cell)
+            .ok_or_else(|| RecordError::new(RecordErrorKind::IllegalMove, line, column))?;
+    }
+
+    if let Some((_tag, value, line)) = tag(RESULT) {
+        let result = format_result(game.outcome());
+        if *value != result {
+            let kind = RecordErrorKind::ResultMismatch(result);
+            return Err(RecordError::new(kind, *line, 1));
+        }
+    }
+
+    let metadata = Metadata {
+        human: metadata_tag(HUMAN),
+        cpu: metadata_tag(CPU_TAG),
+        level: metadata_tag(LEVEL),
+        date: metadata_tag(DATE),
+    };
+    Ok((game, metadata))
+}
+
+/// Name and value of a tag, or the column the tag stops making sense at
+fn parse_tag(line: &str) -> Result<(String, String), usize> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|l| l.strip_suffix(']'))
+        .ok_or(line.chars().count())?;
+    let name_end = inner.find(' ').ok_or(line.chars().count())?;
+    let name = &inner[..name_end];
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(2);
+    }
+
+    let quoted = inner[name_end..].trim_start();
+    let value_column = line.chars().count() - quoted.chars().count();
+    let quoted = quoted
+        .strip_prefix('"')
+        .and_then(|q| q.strip_suffix('"'))
+        .ok_or(value_column)?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next().ok_or(value_column)? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                c => value.push(c),
+            },
+            '"' => return Err(value_column),
+            c => value.push(c),
+        }
+    }
+    Ok((name.to_string(), value))
+}
+
+/// A tag value with the characters that would end it or its line written
+/// as `\` and a letter
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn format_variant(config: BoardConfig) -> String {
+    let name = VARIANT_NAMES
+        .iter()
+        .find(|(_name, variant)| variant.config() == config);
+    match name {
+        Some((name, _variant)) => name.to_string(),
+        None => notation::format_config(config),
+    }
+}
+
+fn parse_variant(text: &str) -> Option<BoardConfig> {
+    VARIANT_NAMES
+        .iter()
+        .find(|(name, _variant)| *name == text)
+        .map(|(_name, variant)| variant.config())
+        .or_else(|| notation::parse_config(text).ok())
+}
+
+const VARIANT_NAMES: [(&str, Variant); 3] = [
+    ("classic", Variant::Classic),
+    ("qubic", Variant::Qubic),
+    ("connect-four", Variant::ConnectFour),
+];
+
+fn format_result(outcome: Outcome) -> String {
+    let result = match outcome {
+        Outcome::Winner(Human) => "1-0",
+        Outcome::Winner(CPU) => "0-1",
+        Outcome::Draw => "1/2-1/2",
+        _ => "*",
+    };
+    result.to_string()
+}
+
+/// A cell as a column letter and a row number counting from the bottom, such
+/// as `b2`, with `:` and the layer after it on boards with layers
+pub fn format_coordinate(config: BoardConfig, cell: &Cell) -> String {
+    let column = format_column(cell.column);
+    let row = config.rows - cell.row;
+    if config.layers > 1 {
+        format!("{}{}:{}", column, row, cell.layer + 1)
+    } else {
+        format!("{}{}", column, row)
+    }
+}
+
+/// The empty cell a coordinate from `format_coordinate` points to, if it is
+/// on the board
+pub fn parse_coordinate(config: BoardConfig, text: &str) -> Option<Cell> {
+    let (square, layer) = match text.split_once(':') {
+        Some((square, layer)) => (square, layer.parse::<u8>().ok()?.checked_sub(1)?),
+        None => (text, 0),
+    };
+
+    let letters_end = square
+        .find(|c: char| !c.is_ascii_lowercase())
+        .unwrap_or(square.len());
+    let (letters, row) = square.split_at(letters_end);
+    let column = parse_column(letters)?;
+    let row: u8 = row.parse().ok()?;
+    if column >= config.columns as u32 || row == 0 || row > config.rows || layer >= config.layers {
+        return None;
+    }
+
+    Some(Cell {
+        row: config.rows - row,
+        column: column as u8,
+        layer,
+        value: Empty,
+    })
+}
+
+/// Letters for a column counting from 0, going on from `z` to `aa`
+pub fn format_column(column: u8) -> String {
+    let mut letters = vec![];
+    let mut n = column as u32 + 1;
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// The column counting from 0 that letters from `format_column` stand for,
+/// whether or not the board has it
+pub fn parse_column(letters: &str) -> Option<u32> {
+    if letters.is_empty() || !letters.bytes().all(|letter| letter.is_ascii_lowercase()) {
+        return None;
+    }
+    let n = letters.bytes().try_fold(0u32, |n, letter| {
+        n.checked_mul(26)?.checked_add((letter - b'a') as u32 + 1)
+    })?;
+    Some(n - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::{BoardConfig, Cell, CellValue, Outcome};
+    use crate::game::{Game, Variant};
+    use crate::record::{
+        format, format_coordinate, parse, parse_column, parse_coordinate, Metadata, RecordErrorKind,
+    };
+
+    fn play_all(game: &mut Game, moves: &[(u8, u8, u8)]) {
+        for &(row, column, layer) in moves {
+            let value = game.current_seat().value;
+            game.play(Cell {
+                row,
+                column,
+                layer,
+                value,
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_format__when__classic_draw() {
+        let mut game = Game::new();
+        play_all(
+            &mut game,
+            &[
+                (1, 1, 0),
+                (2, 0, 0),
+                (0, 2, 0),
+                (0, 0, 0),
+                (1, 0, 0),
+                (1, 2, 0),
+                (0, 1, 0),
+                (2, 1, 0),
+                (2, 2, 0),
+            ],
+        );
+        let metadata = Metadata {
+            human: "Ada".to_string(),
+            date: "2024.05.01".to_string(),
+            ..Metadata::default()
+        };
+
+        let actual = format(&game, &metadata).unwrap();
+        let expected = "[Variant \"classic\"]\n[Human \"Ada\"]\n[Cpu \"?\"]\n[Level \"?\"]\n\
+            [Date \"2024.05.01\"]\n[Result \"1/2-1/2\"]\n\nb2 a1 c3 a3 a2 c2 b3 b1 c1\n";
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_parse__is_inverse_of_format() {
+        let mut game = Game::with_variant(Variant::Qubic);
+        play_all(&mut game, &[(0, 0, 0), (3, 3, 3), (1, 2, 1)]);
+        let metadata = Metadata {
+            human: "Say \"hi\"".to_string(),
+            ..Metadata::default()
+        };
+        let text = format(&game, &metadata).unwrap();
+
+        let (parsed, parsed_metadata) = parse(&text).unwrap();
+
+        assert_eq!(parsed.board().config(), BoardConfig::qubic());
+        assert_eq!(parsed.history(), game.history());
+        assert_eq!(parsed_metadata, metadata);
+    }
+
+    #[test]
+    fn test_parse__is_inverse_of_format__when__tag_has_line_breaks() {
+        let game = Game::new();
+        let metadata = Metadata {
+            human: "Ada\nLovelace\r\\n".to_string(),
+            ..Metadata::default()
+        };
+        let text = format(&game, &metadata).unwrap();
+
+        let (_parsed, parsed_metadata) = parse(&text).unwrap();
+
+        assert!(text.contains("[Human \"Ada\\nLovelace\\r\\\\n\"]\n"));
+        assert_eq!(parsed_metadata, metadata);
+    }
+
+    #[test]
+    fn test_parse__when__started_from_position() {
+        let mut game = Game::from_notation("x.o/.x./... o").unwrap();
+        play_all(&mut game, &[(2, 2, 0), (2, 0, 0)]);
+        let text = format(&game, &Metadata::default()).unwrap();
+
+        let (parsed, _metadata) = parse(&text).unwrap();
+
+        assert!(text.contains("[Position \"x.o/.x./... o\"]"));
+        assert_eq!(parsed.board().values(), game.board().values());
+        assert_eq!(parsed.outcome(), Outcome::InProgress);
+    }
+
+    #[test]
+    fn test_parse__when__illegal_move() {
+        let text = "[Variant \"classic\"]\n\nb2 a1\nb2 c3\n";
+
+        let error = parse(text).err().unwrap();
+
+        assert_eq!(error.kind, RecordErrorKind::IllegalMove);
+        assert_eq!((error.line, error.column), (4, 1));
+        assert_eq!(
+            error.to_string(),
+            "line 4, column 1: the move is not allowed"
+        );
+    }
+
+    #[test]
+    fn test_parse__when__coordinate_off_the_board() {
+        let text = "b2  d1";
+
+        let error = parse(text).err().unwrap();
+
+        assert_eq!(error.kind, RecordErrorKind::BadCoordinate);
+        assert_eq!((error.line, error.column), (1, 5));
+    }
+
+    #[test]
+    fn test_parse__when__variant_can_not_be_played() {
+        let text = "[Variant \"3x3:0\"]\n\nb2\n";
+
+        let error = parse(text).err().unwrap();
+
+        assert_eq!(error.kind, RecordErrorKind::UnknownVariant);
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn test_parse_coordinate__is_inverse_of_format_coordinate__when__wide_board() {
+        let config = BoardConfig {
+            rows: 1,
+            columns: 255,
+            win_length: 3,
+            ..BoardConfig::default()
+        };
+        let cells: Vec<Cell> = [0, 25, 26, 51, 254]
+            .iter()
+            .map(|&column| Cell {
+                row: 0,
+                column,
+                layer: 0,
+                value: CellValue::Empty,
+            })
+            .collect();
+
+        let coordinates: Vec<String> = cells
+            .iter()
+            .map(|cell| format_coordinate(config, cell))
+            .collect();
+        let parsed: Vec<Option<Cell>> = coordinates
+            .iter()
+            .map(|text| parse_coordinate(config, text))
+            .collect();
+
+        assert_eq!(coordinates, vec!["a1", "z1", "aa1", "az1", "iu1"]);
+        assert_eq!(parsed, cells.into_iter().map(Some).collect::<Vec<_>>());
+        assert_eq!(parse_coordinate(config, "zzzzzzzzzzzz1"), None);
+    }
+
+    #[test]
+    fn test_parse_column__when__not_only_letters() {
+        assert_eq!(parse_column("ab"), Some(27));
+        assert_eq!(parse_column("a1"), None);
+        assert_eq!(parse_column("A"), None);
+        assert_eq!(parse_column(""), None);
+    }
+
+    #[test]
+    fn test_parse__when__tag_not_closed() {
+        let text = "[Variant \"classic\"]\n[Human \"Ada\n";
+
+        let error = parse(text).err().unwrap();
+
+        assert_eq!(error.kind, RecordErrorKind::BadTag);
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn test_parse__when__result_does_not_match() {
+        let text = "[Result \"1-0\"]\n\nb2 a1\n";
+
+        let error = parse(text).err().unwrap();
+
+        assert_eq!(error.kind, RecordErrorKind::ResultMismatch("*".to_string()));
+        assert_eq!(error.line, 1);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::board::CellValue::Empty;
+use crate::board::{Board, CellValue};
+use crate::utils::now_ms;
+
+// How often the clock is checked, in searched positions
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// The lines of a board that can still be completed and the lines through
+/// every cell, which the searches share. They only differ in how they score
+/// positions and order moves with them.
+pub struct WinLines {
+    pub lines: Vec<Vec<usize>>,
+    pub through: Vec<Vec<usize>>,
+}
+
+impl WinLines {
+    pub fn new(board: &Board) -> Self {
+        let values = board.values();
+        // Lines through a blocked cell can never be completed
+        let lines: Vec<Vec<usize>> = board
+            .win_lines()
+            .iter()
+            .filter(|line| line.iter().all(|&i| values[i] != CellValue::Blocked))
+            .cloned()
+            .collect();
+
+        let mut through = vec![vec![]; values.len()];
+        for (line_index, line) in lines.iter().enumerate() {
+            for &i in line.iter() {
+                through[i].push(line_index);
+            }
+        }
+
+        WinLines { lines, through }
+    }
+
+    /// Whether the mark at `index` completes a line
+    pub fn is_win_through(&self, values: &[CellValue], index: usize) -> bool {
+        let value = values[index];
+        value != Empty
+            && self.through[index]
+                .iter()
+                .any(|&line| self.lines[line].iter().all(|&i| values[i] == value))
+    }
+
+    /// Marks of `side` and of everyone else in a line
+    pub fn count_line(&self, values: &[CellValue], line: usize, side: CellValue) -> (usize, usize) {
+        self.lines[line]
+            .iter()
+            .fold((0, 0), |(own, other), &i| match values[i] {
+                Empty => (own, other),
+                value if value == side => (own + 1, other),
+                _ => (own, other + 1),
+            })
+    }
+}
+
+/// Tells a search to stop once it is past its deadline or has searched
+/// enough positions
+pub struct Clock {
+    deadline: f64,
+    max_nodes: u64,
+    pub nodes: u64,
+    pub stopped: bool,
+}
+
+impl Clock {
+    pub fn new(deadline: f64, max_nodes: u64) -> Self {
+        Clock {
+            deadline,
+            max_nodes,
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    /// Counts a searched position and returns whether the search must stop
+    pub fn tick(&mut self) -> bool {
+        self.nodes += 1;
+        let is_out_of_time = self.nodes.is_multiple_of(CLOCK_INTERVAL) && now_ms() > self.deadline;
+        if is_out_of_time || self.nodes >= self.max_nodes {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+}
+
+/// Moves left before the board is full, which is as deep as a search from
+/// it can go. On a board with gravity only some of them can be played now.
+pub fn plies_left(values: &[CellValue]) -> usize {
+    values.iter().filter(|&&value| value == Empty).count()
+}
+
+/// Worth of a line holding only one player's marks, growing quickly with
+/// the number of marks
+pub fn line_weight(marks: usize) -> i32 {
+    match marks {
+        0 => 0,
+        marks => 1 << (3 * (marks - 1)).min(24),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Blocked, Empty, Human, CPU};
+    use crate::board::{Board, BoardConfig};
+    use crate::search::{plies_left, WinLines};
+
+    #[test]
+    fn test_new__when__blocked_cell() {
+        let mut values = vec![Empty; 9];
+        values[4] = Blocked;
+        let board = Board::from_position(BoardConfig::square(3, 3), values).unwrap();
+
+        let actual = WinLines::new(&board);
+
+        assert_eq!(actual.lines.len(), 4);
+        assert!(actual.through[4].is_empty());
+        assert_eq!(actual.through[0].len(), 2);
+    }
+
+    #[test]
+    fn test_is_win_through() {
+        let lines = WinLines::new(&Board::new(BoardConfig::square(3, 3)));
+        let values = vec![Human, Human, Human, CPU, CPU, Empty, Empty, Empty, Empty];
+
+        assert!(lines.is_win_through(&values, 1));
+        assert!(!lines.is_win_through(&values, 4));
+        assert!(!lines.is_win_through(&values, 8));
+    }
+
+    #[test]
+    fn test_plies_left__when__gravity() {
+        let mut board = Board::new(BoardConfig::connect_four());
+        board.set_human(5, 3);
+
+        let actual = plies_left(&board.values());
+        let expected = 41;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_count_line() {
+        let lines = WinLines::new(&Board::new(BoardConfig::square(3, 3)));
+        let values = vec![Human, CPU, Empty, Empty, Empty, Empty, Empty, Empty, Empty];
+
+        let actual = lines.count_line(&values, lines.through[0][0], Human);
+        let expected = (1, 1);
+
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{Board, BoardConfig, Cell, CellValue, PositionError, Topology};
+use crate::game::Game;
+
+use std::fmt;
+
+const VERSION: u8 = 1;
+
+const GRAVITY: u8 = 1;
+const TORUS: u8 = 1 << 1;
+const HAS_POSITION: u8 = 1 << 2;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ShareError {
+    BadCharacter(char),
+    TooShort,
+    ChecksumMismatch,
+    UnknownVersion(u8),
+    BadConfig,
+    BadPosition(PositionError),
+    /// The move, counting from 1, that could not be played
+    IllegalMove(usize),
+    UnsupportedGame,
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShareError::BadCharacter(c) => write!(f, "'{}' can not be in a game code", c),
+            ShareError::TooShort => write!(f, "the game code is cut short"),
+            ShareError::ChecksumMismatch => write!(f, "the game code has been changed"),
+            ShareError::UnknownVersion(v) => write!(f, "game codes of version {} are not known", v),
+            ShareError::BadConfig => write!(f, "the game code has a board that can not be played"),
+            ShareError::BadPosition(e) => write!(f, "{}", e),
+            ShareError::IllegalMove(n) => write!(f, "move {} is not allowed", n),
+            ShareError::UnsupportedGame => write!(f, "only games against the CPU can be shared"),
+        }
+    }
+}
+
+/// A code for a game that is safe to put in a URL, made of the board size
+/// and rules, the start position when the game did not start on an empty
+/// board, and every move so far. It ends with a checksum, so a code that has
+/// been edited by hand is turned down, though it is not meant to stop
+/// anyone who recomputes the checksum.
+pub fn encode(game: &Game) -> Result<String, ShareError> {
+    if game.seats().len() != 2 {
+        return Err(ShareError::UnsupportedGame);
+    }
+    let config = game.board().config();
+    let (start, _to_move) = game.start_position();
+    let start_values = start.values();
+    let has_position = start_values.iter().any(|v| *v != Empty);
+
+    let mut flags = 0;
+    if config.gravity {
+        flags |= GRAVITY;
+    }
+    if config.topology == Topology::Torus {
+        flags |= TORUS;
+    }
+    if has_position {
+        flags |= HAS_POSITION;
+    }
+
+    let mut bytes = vec![
+        VERSION,
+        config.rows,
+        config.columns,
+        config.layers,
+        config.win_length,
+        flags,
+    ];
+    if has_position {
+        let codes = start_values
+            .into_iter()
+            .map(to_code)
+            .collect::<Result<Vec<u8>, ShareError>>()?;
+        // Four cells to a byte
+        for chunk in codes.chunks(4) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, code)| byte | code << (2 * i));
+            bytes.push(byte);
+        }
+    }
+    for c in game.history() {
+        let index = config.index(c.row, c.column, c.layer).unwrap();
+        push_varint(&mut bytes, index);
+    }
+
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum.to_be_bytes());
+    Ok(to_base64(&bytes))
+}
+
+/// The game a code from `encode` was made from, with its moves played again
+pub fn decode(code: &str) -> Result<Game, ShareError> {
+    let bytes = from_base64(code)?;
+    if bytes.len() < 10 {
+        return Err(ShareError::TooShort);
+    }
+
+    let (payload, checksum_bytes) = bytes.split_at(bytes.len() - 4);
+    let expected = u32::from_be_bytes([
+        checksum_bytes[0],
+        checksum_bytes[1],
+        checksum_bytes[2],
+        checksum_bytes[3],
+    ]);
+    if checksum(payload) != expected {
+        return Err(ShareError::ChecksumMismatch);
+    }
+    if payload[0] != VERSION {
+        return Err(ShareError::UnknownVersion(payload[0]));
+    }
+
+    let flags = payload[5];
+    let config = BoardConfig {
+        rows: payload[1],
+        columns: payload[2],
+        layers: payload[3],
+        win_length: payload[4],
+        gravity: flags & GRAVITY != 0,
+        topology: if flags & TORUS != 0 {
+            Topology::Torus
+        } else {
+            Topology::Flat
+        },
+    };
+    if !config.is_valid() {
+        return Err(ShareError::BadConfig);
+    }
+
+    let mut rest = &payload[6..];
+    let mut game = if flags & HAS_POSITION != 0 {
+        let position_length = config.size().div_ceil(4);
+        if rest.len() < position_length {
+            return Err(ShareError::TooShort);
+        }
+        let (position, moves) = rest.split_at(position_length);
+        rest = moves;
+
+        let values = (0..config.size())
+            .map(|i| CellValue::from_code(position[i / 4] >> (2 * (i % 4)) & 0b11).unwrap())
+            .collect();
+        let board = Board::from_position(config, values).map_err(ShareError::BadPosition)?;
+        Game::with_board(board)
+    } else {
+        Game::with_config(config)
+    };
+
+    let mut number = 0;
+    while !rest.is_empty() {
+        number += 1;
+        let index = read_varint(&mut rest).ok_or(ShareError::TooShort)?;
+        if index >= config.size() {
+            return Err(ShareError::IllegalMove(number));
+        }
+
+        let (row, column, layer) = config.coordinates(index);
+        let cell = Cell {
+            row,
+            column,
+            layer,
+            value: game.current_seat().value,
+        };
+        game.play(cell).ok_or(ShareError::IllegalMove(number))?;
+    }
+
+    Ok(game)
+}
+
+fn to_code(value: CellValue) -> Result<u8, ShareError> {
+    match value {
+        Empty => Ok(0),
+        CPU => Ok(1),
+        Human => Ok(2),
+        CellValue::Blocked => Ok(3),
+        _ => Err(ShareError::UnsupportedGame),
+    }
+}
+
+/// Seven bits to a byte, with the top bit set on every byte but the last
+fn push_varint(bytes: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        bytes.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<usize> {
+    let mut n = 0;
+    for shift in (0..28).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// 32 bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Base64 with the URL-safe alphabet and no padding
+fn to_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            text.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    text
+}
+
+fn from_base64(text: &str) -> Result<Vec<u8>, ShareError> {
+    let sextets = text
+        .chars()
+        .map(|c| {
+            ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .ok_or(ShareError::BadCharacter(c))
+        })
+        .collect::<Result<Vec<usize>, ShareError>>()?;
+    if sextets.len() % 4 == 1 {
+        return Err(ShareError::TooShort);
+    }
+
+    let mut bytes = vec![];
+    for chunk in sextets.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &sextet)| n | (sextet as u32) << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::{BoardConfig, Cell, Topology};
+    use crate::game::{Game, Variant};
+    use crate::share::{checksum, decode, encode, from_base64, to_base64, ShareError};
+
+    fn play_all(game: &mut Game, moves: &[(u8, u8, u8)]) {
+        for &(row, column, layer) in moves {
+            let value = game.current_seat().value;
+            game.play(Cell {
+                row,
+                column,
+                layer,
+                value,
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_from_base64__is_inverse_of_to_base64() {
+        for length in 0..8 {
+            let bytes: Vec<u8> = (0..length).map(|i| (i * 97 + 200) as u8).collect();
+
+            assert_eq!(from_base64(&to_base64(&bytes)), Ok(bytes));
+        }
+    }
+
+    #[test]
+    fn test_decode__is_inverse_of_encode() {
+        let mut game = Game::with_config(BoardConfig::square(5, 3).with_topology(Topology::Torus));
+        play_all(&mut game, &[(0, 0, 0), (2, 2, 0), (4, 4, 0)]);
+
+        let code = encode(&game).unwrap();
+        let decoded = decode(&code).unwrap();
+
+        assert!(code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(decoded.board().config(), game.board().config());
+        assert_eq!(decoded.history(), game.history());
+    }
+
+    #[test]
+    fn test_decode__when__started_from_position() {
+        let mut game = Game::from_notation("x#o/.x./... o").unwrap();
+        play_all(&mut game, &[(2, 2, 0)]);
+
+        let decoded = decode(&encode(&game).unwrap()).unwrap();
+
+        assert_eq!(decoded.board().values(), game.board().values());
+        assert_eq!(decoded.history().len(), 1);
+    }
+
+    #[test]
+    fn test_decode__when__tampered() {
+        let mut game = Game::new();
+        play_all(&mut game, &[(1, 1, 0), (0, 0, 0)]);
+        let code = encode(&game).unwrap();
+
+        let mut tampered: Vec<char> = code.chars().collect();
+        tampered[9] = if tampered[9] == 'A' { 'B' } else { 'A' };
+        let tampered: String = tampered.into_iter().collect();
+
+        assert_eq!(decode(&tampered).err(), Some(ShareError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_decode__when__illegal_sequence() {
+        // Both moves on the middle cell, with a valid checksum
+        let mut bytes = vec![1, 3, 3, 1, 3, 0, 4, 4];
+        bytes.extend_from_slice(&checksum(&bytes).to_be_bytes());
+
+        let actual = decode(&to_base64(&bytes)).err();
+        let expected = Some(ShareError::IllegalMove(2));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_decode__when__board_too_large() {
+        let mut bytes = vec![1, 255, 255, 1, 3, 0];
+        bytes.extend_from_slice(&checksum(&bytes).to_be_bytes());
+
+        let actual = decode(&to_base64(&bytes)).err();
+        let expected = Some(ShareError::BadConfig);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_encode__when__qubic_game_fits_in_a_short_code() {
+        let mut game = Game::with_variant(Variant::Qubic);
+        play_all(&mut game, &[(0, 0, 0), (1, 1, 1), (2, 2, 2), (3, 3, 3)]);
+
+        let actual = encode(&game).unwrap().len();
+
+        assert!(actual <= 20);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::board::CellValue::Empty;
+use crate::board::{BoardConfig, CellValue, Topology};
+
+/// Whether the board can be turned and flipped without changing the game,
+/// which needs a flat square board without gravity
+pub fn has_symmetries(config: BoardConfig) -> bool {
+    config.rows == config.columns
+        && config.layers == 1
+        && !config.gravity
+        && config.topology == Topology::Flat
+}
+
+/// The position as seen by the player to move, with their marks as `x`, the
+/// other marks as `o` and empty positions as `.`, turned and flipped to
+/// whichever of its eight forms comes first. Also returns the symmetry that
+/// takes the board to that form.
+pub fn canonical(config: BoardConfig, values: &[CellValue], value: CellValue) -> (String, usize) {
+    let symbols: Vec<char> = values
+        .iter()
+        .map(|&v| match v {
+            Empty => '.',
+            v if v == value => 'x',
+            _ => 'o',
+        })
+        .collect();
+
+    (0..8)
+        .map(|symmetry| {
+            let mut key = vec!['.'; symbols.len()];
+            for (i, &symbol) in symbols.iter().enumerate() {
+                key[transform(config, symmetry, i)] = symbol;
+            }
+            (key.into_iter().collect::<String>(), symmetry)
+        })
+        .min()
+        .unwrap()
+}
+
+/// Where position `index` goes under one of the eight symmetries of a
+/// square
+pub fn transform(config: BoardConfig, symmetry: usize, index: usize) -> usize {
+    let n = config.rows as usize;
+    let last = n - 1;
+    let (row, column) = (index / n, index % n);
+    let (row, column) = match symmetry {
+        0 => (row, column),
+        1 => (column, last - row),
+        2 => (last - row, last - column),
+        3 => (last - column, row),
+        4 => (row, last - column),
+        5 => (last - row, column),
+        6 => (column, row),
+        _ => (last - column, last - row),
+    };
+    row * n + column
+}
+
+/// The position that `transform` takes to `index`
+pub fn untransform(config: BoardConfig, symmetry: usize, index: usize) -> usize {
+    (0..config.size())
+        .find(|&i| transform(config, symmetry, i) == index)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::BoardConfig;
+    use crate::board::CellValue::{Empty, Human, CPU};
+    use crate::symmetry::{canonical, has_symmetries, transform, untransform};
+
+    #[test]
+    fn test_canonical__when__board_is_turned() {
+        let config = BoardConfig::default();
+        let corner = vec![Human, Empty, Empty, Empty, CPU, Empty, Empty, Empty, Empty];
+        let turned = vec![Empty, Empty, Empty, Empty, CPU, Empty, Empty, Empty, Human];
+
+        let actual = canonical(config, &turned, CPU).0;
+        let expected = canonical(config, &corner, CPU).0;
+
+        assert_eq!(actual, expected);
+        assert_eq!(actual, "....x...o");
+    }
+
+    #[test]
+    fn test_untransform() {
+        let config = BoardConfig::square(4, 4);
+
+        let actual = (0..8).all(|symmetry| {
+            (0..16).all(|i| untransform(config, symmetry, transform(config, symmetry, i)) == i)
+        });
+
+        assert!(actual);
+    }
+
+    #[test]
+    fn test_has_symmetries() {
+        assert!(has_symmetries(BoardConfig::default()));
+        assert!(!has_symmetries(BoardConfig::connect_four()));
+        assert!(!has_symmetries(BoardConfig::qubic()));
+    }
+}
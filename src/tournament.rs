@@ -0,0 +1,328 @@
+use crate::agent::Agent;
+use crate::board::CellValue::{Human, CPU};
+use crate::board::{Board, BoardConfig, Outcome};
+use crate::utils;
+
+use std::f64::consts::LN_10;
+
+// Rounds of improving the Elo estimates, far more than they need to settle
+const ELO_ROUNDS: usize = 100;
+
+/// How one agent did against another, counted for the first agent
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Pairing {
+    pub first: String,
+    pub second: String,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Pairing {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points for the first agent, one for a win and half for a draw
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+/// How one agent did over the whole tournament
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Standing {
+    pub name: String,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Rating estimated from the results, with the field averaging 0
+    pub elo: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Tournament {
+    pub games_per_pairing: u32,
+    pub pairings: Vec<Pairing>,
+    /// Best first
+    pub standings: Vec<Standing>,
+}
+
+impl Tournament {
+    pub fn to_json(&self) -> String {
+        utils::to_json(self, true)
+    }
+
+    /// The pairings, one row each
+    pub fn to_csv(&self) -> String {
+        let mut csv = "first,second,wins,draws,losses\n".to_string();
+        for p in &self.pairings {
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                p.first, p.second, p.wins, p.draws, p.losses
+            );
+        }
+        csv
+    }
+}
+
+/// Plays one game, where `first` plays the human and moves first. An agent
+/// that has no move or makes an illegal one loses. Both agents are told the
+/// outcome.
+pub fn play_game(config: BoardConfig, first: &mut dyn Agent, second: &mut dyn Agent) -> Outcome {
+    let mut board = Board::new(config);
+    let mut value = Human;
+
+    let outcome = loop {
+        if board.outcome() != Outcome::InProgress {
+            break board.outcome();
+        }
+        let agent: &mut dyn Agent = if value == Human {
+            &mut *first
+        } else {
+            &mut *second
+        };
+        let is_played = agent
+            .choose(&board, value)
+            .filter(|c| c.value == value)
+            .and_then(|c| board.set(c))
+            .is_some();
+        if !is_played {
+            break Outcome::Winner(value.opponent());
+        }
+        value = value.opponent();
+    };
+
+    first.finish(outcome);
+    second.finish(outcome);
+    outcome
+}
+
+/// Plays `games` games between two agents, taking turns to move first
+pub fn play_match(
+    config: BoardConfig,
+    first: &mut dyn Agent,
+    second: &mut dyn Agent,
+    games: u32,
+) -> Pairing {
+    let mut pairing = Pairing {
+        first: first.name(),
+        second: second.name(),
+        wins: 0,
+        draws: 0,
+        losses: 0,
+    };
+
+    for game in 0..games {
+        let (outcome, first_value) = if game % 2 == 0 {
+            (play_game(config, first, second), Human)
+        } else {
+            (play_game(config, second, first), CPU)
+        };
+        match outcome {
+            Outcome::Winner(winner) if winner == first_value => pairing.wins += 1,
+            Outcome::Winner(_winner) => pairing.losses += 1,
+            _ => pairing.draws += 1,
+        }
+    }
+
+    pairing
+}
+
+/// Plays a match of `games` games between every two agents
+pub fn round_robin(config: BoardConfig, agents: &mut [Box<dyn Agent>], games: u32) -> Tournament {
+    let mut pairings = vec![];
+    let mut results = vec![];
+    for i in 0..agents.len() {
+        for j in i + 1..agents.len() {
+            let (before, after) = agents.split_at_mut(j);
+            let pairing = play_match(config, &mut *before[i], &mut *after[0], games);
+            results.push((i, j, pairing.points(), pairing.games() as f64));
+            pairings.push(pairing);
+        }
+    }
+
+    let elo = estimate_elo(agents.len(), &results);
+    let mut standings: Vec<Standing> = agents
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| {
+            let mut standing = Standing {
+                name: agent.name(),
+                wins: 0,
+                draws: 0,
+                losses: 0,
+                elo: elo[i],
+            };
+            for (&(a, b, _points, _games), p) in results.iter().zip(&pairings) {
+                if a == i {
+                    standing.wins += p.wins;
+                    standing.losses += p.losses;
+                } else if b == i {
+                    standing.wins += p.losses;
+                    standing.losses += p.wins;
+                } else {
+                    continue;
+                }
+                standing.draws += p.draws;
+            }
+            standing
+        })
+        .collect();
+    standings.sort_by(|a, b| b.elo.partial_cmp(&a.elo).unwrap());
+
+    Tournament {
+        games_per_pairing: games,
+        pairings,
+        standings,
+    }
+}
+
+/// Expected points per game for a player rated `difference` above the other
+fn expected_score(difference: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-difference / 400.0))
+}
+
+/// Ratings for `players` players that best explain the results, each given
+/// as the two players, the points of the first and the games played. Every
+/// player is also counted as having drawn a game with an average player, so
+/// one who won or lost every game still gets a finite rating.
+fn estimate_elo(players: usize, results: &[(usize, usize, f64, f64)]) -> Vec<f64> {
+    let mut ratings = vec![0.0; players];
+
+    for _round in 0..ELO_ROUNDS {
+        for player in 0..players {
+            let mut points = 0.5;
+            let mut expected = expected_score(ratings[player]);
+            let mut slope = expected * (1.0 - expected);
+            for &(a, b, a_points, games) in results {
+                let (opponent, player_points) = match player {
+                    p if p == a => (b, a_points),
+                    p if p == b => (a, games - a_points),
+                    _ => continue,
+                };
+                let e = expected_score(ratings[player] - ratings[opponent]);
+                points += player_points;
+                expected += games * e;
+                slope += games * e * (1.0 - e);
+            }
+            ratings[player] += (points - expected) / (slope * LN_10 / 400.0);
+        }
+    }
+
+    let mean = ratings.iter().sum::<f64>() / players.max(1) as f64;
+    ratings.iter().map(|r| r - mean).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::agent::{Agent, EvaluatorAgent, MinimaxAgent, RandomAgent};
+    use crate::board::CellValue::Human;
+    use crate::board::{Board, BoardConfig, Cell, CellValue, Outcome};
+    use crate::tournament::{estimate_elo, play_game, play_match, round_robin, Pairing};
+
+    /// Always tries the same cell, which is soon taken
+    struct Stubborn;
+
+    impl Agent for Stubborn {
+        fn name(&self) -> String {
+            "stubborn".to_string()
+        }
+
+        fn choose(&mut self, _board: &Board, value: CellValue) -> Option<Cell> {
+            Some(Cell {
+                row: 1,
+                column: 1,
+                layer: 0,
+                value,
+            })
+        }
+    }
+
+    #[test]
+    fn test_play_game__when__evaluator_plays_itself() {
+        let actual = play_game(
+            BoardConfig::default(),
+            &mut EvaluatorAgent,
+            &mut EvaluatorAgent,
+        );
+        let expected = Outcome::Draw;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play_game__when__illegal_move_loses() {
+        let actual = play_game(BoardConfig::default(), &mut Stubborn, &mut Stubborn);
+        let expected = Outcome::Winner(Human);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play_match__alternates_colours() {
+        // Whoever moves second breaks the rules first
+        let actual = play_match(BoardConfig::default(), &mut Stubborn, &mut Stubborn, 4);
+        let expected = Pairing {
+            first: "stubborn".to_string(),
+            second: "stubborn".to_string(),
+            wins: 2,
+            draws: 0,
+            losses: 2,
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play_match__minimax_beats_random() {
+        let mut minimax = MinimaxAgent::new(None);
+        let mut random = RandomAgent::new(3);
+
+        let actual = play_match(BoardConfig::default(), &mut minimax, &mut random, 10);
+
+        assert_eq!(actual.losses, 0);
+        assert!(actual.wins > actual.draws);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut agents: Vec<Box<dyn Agent>> = vec![
+            Box::new(RandomAgent::new(1)),
+            Box::new(EvaluatorAgent),
+            Box::new(MinimaxAgent::new(Some(1))),
+        ];
+
+        let actual = round_robin(BoardConfig::default(), &mut agents, 6);
+
+        assert_eq!(actual.pairings.len(), 3);
+        assert_eq!(actual.standings[2].name, "random");
+        assert!(actual.standings[2].elo < 0.0);
+        let total: f64 = actual.standings.iter().map(|s| s.elo).sum();
+        assert!(total.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_estimate_elo__when__three_quarters_of_points() {
+        // Three points out of four is about 190 points better
+        let actual = estimate_elo(2, &[(0, 1, 30.0, 40.0)]);
+
+        assert!(actual[0] > 0.0 && actual[0] == -actual[1]);
+        assert!((150.0..200.0).contains(&(actual[0] - actual[1])));
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut agents: Vec<Box<dyn Agent>> =
+            vec![Box::new(EvaluatorAgent), Box::new(EvaluatorAgent)];
+        let tournament = round_robin(BoardConfig::default(), &mut agents, 2);
+
+        let actual = tournament.to_csv();
+        let expected = "first,second,wins,draws,losses\nevaluator,evaluator,0,2,0\n";
+
+        assert_eq!(actual, expected);
+        assert!(tournament.to_json().contains("\"games_per_pairing\": 2"));
+    }
+}
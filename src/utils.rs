@@ -8,3 +8,95 @@ pub fn set_panic_hook() {
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
 }
+
+/// Milliseconds since the Unix epoch. `std::time` is not available in the
+/// browser so the JS clock is used there instead.
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// A value read from JSON text. The browser's own parser is used there so
+/// that the wasm build does not carry a JSON library.
+#[cfg(target_arch = "wasm32")]
+pub fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, String> {
+    use wasm_bindgen::JsCast;
+
+    let value = js_sys::JSON::parse(json)
+        .map_err(|e| String::from(e.unchecked_into::<js_sys::Error>().message()))?;
+    serde_wasm_bindgen::from_value(value).map_err(|e| e.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+/// A value written as JSON text, spaced out for people to read when
+/// `pretty`
+#[cfg(target_arch = "wasm32")]
+pub fn to_json<T: serde::Serialize>(value: &T, pretty: bool) -> String {
+    use wasm_bindgen::JsValue;
+
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    let value = value
+        .serialize(&serializer)
+        .expect("Saved values only hold values JSON can write");
+    let space = if pretty { 2.into() } else { JsValue::UNDEFINED };
+    js_sys::JSON::stringify_with_replacer_and_space(&value, &JsValue::NULL, &space)
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn to_json<T: serde::Serialize>(value: &T, pretty: bool) -> String {
+    let json = if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    json.expect("Saved values only hold values JSON can write")
+}
+
+/// A small xorshift generator, for choices that only have to look random and
+/// that should come out the same again from the same seed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Spread the seed over all the bits so that close seeds give
+        // different numbers, and keep it from being 0, which xorshift never
+        // leaves
+        Random {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A number from 0 up to but not including `n`, which must not be 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A number from 0 up to but not including 1
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,322 @@
+use crate::board::CellValue::{Empty, Human, CPU};
+use crate::board::{Board, BoardConfig, Cell, CellValue, Mark, Outcome};
+
+use std::collections::HashMap;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsValue;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WildError {
+    GameOver,
+    IllegalMove,
+}
+
+impl fmt::Display for WildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WildError::GameOver => write!(f, "the game is over"),
+            WildError::IllegalMove => write!(f, "the move is not allowed"),
+        }
+    }
+}
+
+/// Tic-tac-toe where either player may play X or O, and whoever completes
+/// a line of either symbol wins. The board holds the symbols, so lines are
+/// found the same whoever played them, and the player who placed every mark
+/// is kept next to it by board index.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct WildGame {
+    board: Board,
+    to_move: CellValue,
+    history: Vec<(CellValue, Cell)>,
+    // The player who placed the mark on every cell, by board index
+    owners: Vec<Option<CellValue>>,
+}
+
+impl Default for WildGame {
+    fn default() -> Self {
+        WildGame {
+            board: Board::default(),
+            to_move: Human,
+            history: vec![],
+            owners: vec![None; BoardConfig::default().size()],
+        }
+    }
+}
+
+impl WildGame {
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn current_player(&self) -> CellValue {
+        self.to_move
+    }
+
+    /// Every mark played so far together with the player who played it
+    pub fn history(&self) -> &[(CellValue, Cell)] {
+        &self.history
+    }
+
+    /// The player who placed the mark on a cell, if there is one
+    pub fn owner(&self, row: u8, column: u8) -> Option<CellValue> {
+        let index = self.board.config().index(row, column, 0)?;
+        self.owners[index]
+    }
+
+    pub fn legal_moves(&self) -> Vec<Cell> {
+        if self.outcome() != Outcome::InProgress {
+            return vec![];
+        }
+
+        self.board
+            .legal_moves()
+            .into_iter()
+            .flat_map(|c| {
+                vec![Mark::X, Mark::O].into_iter().map(move |mark| Cell {
+                    value: CellValue::Mark(mark),
+                    ..c.clone()
+                })
+            })
+            .collect()
+    }
+
+    pub fn play(&mut self, cell: Cell) -> Result<(), WildError> {
+        if self.outcome() != Outcome::InProgress {
+            return Err(WildError::GameOver);
+        }
+        if !matches!(cell.value, CellValue::Mark(_)) {
+            return Err(WildError::IllegalMove);
+        }
+
+        self.board.set(cell.clone()).ok_or(WildError::IllegalMove)?;
+        if let Some(index) = self.board.config().index(cell.row, cell.column, cell.layer) {
+            self.owners[index] = Some(self.to_move);
+        }
+        self.history.push((self.to_move, cell));
+        self.to_move = self.to_move.opponent();
+        Ok(())
+    }
+
+    /// The game ends as soon as a line is complete, so the line belongs to
+    /// whoever played last
+    pub fn outcome(&self) -> Outcome {
+        if self.board.winner().is_some() {
+            let (owner, _cell) = self.history.last().unwrap();
+            return Outcome::Winner(*owner);
+        }
+
+        if self.board.legal_moves().is_empty() {
+            Outcome::Draw
+        } else {
+            Outcome::InProgress
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl WildGame {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> WildGame {
+        WildGame::default()
+    }
+
+    #[wasm_bindgen(js_name = getCells)]
+    pub fn get_cells(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.board.get_cells()).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = getOutcome)]
+    pub fn get_outcome(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.outcome()).unwrap()
+    }
+
+    /// The player who placed the mark on a cell, or `undefined`
+    #[wasm_bindgen(js_name = getOwner)]
+    pub fn get_owner(&self, row: u8, column: u8) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.owner(row, column)).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = humanPlay)]
+    pub fn human_play(&mut self, row: u8, column: u8, mark: Mark) -> Result<(), JsValue> {
+        let cell = Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Mark(mark),
+        };
+        self.play(cell)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = cpuPlay)]
+    pub fn cpu_play(&mut self) {
+        if self.to_move != CPU {
+            return;
+        }
+        if let Some(cell) = find_best_move(self) {
+            self.play(cell).unwrap();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        *self = WildGame::default();
+    }
+}
+
+/// Best move for the player to move. Both players can make the same moves,
+/// so a position is scored for whoever is to move and every position is
+/// only searched once.
+pub fn find_best_move(game: &WildGame) -> Option<Cell> {
+    let board = game.board();
+    let config = board.config();
+    let mut values = board.values();
+    let mut scores = HashMap::new();
+
+    game.legal_moves()
+        .into_iter()
+        .map(|c| {
+            let index = config.index(c.row, c.column, c.layer).unwrap();
+            values[index] = c.value;
+            let score = -negamax(&config, board.win_lines(), &mut values, &mut scores);
+            values[index] = Empty;
+            (c, score)
+        })
+        .min_by(|(_c, score), (_c2, score2)| score2.cmp(score))
+        .map(|(c, _score)| c)
+}
+
+fn negamax(
+    config: &BoardConfig,
+    lines: &[Vec<usize>],
+    values: &mut Vec<CellValue>,
+    scores: &mut HashMap<Vec<CellValue>, i8>,
+) -> i8 {
+    if let Some(score) = scores.get(values) {
+        return *score;
+    }
+
+    let empty_count = values.iter().filter(|&&v| v == Empty).count() as i8;
+    let is_line_complete = lines.iter().any(|line| {
+        let first = values[line[0]];
+        first != Empty && line.iter().all(|&i| values[i] == first)
+    });
+
+    // The player who just moved completed a line, sooner is worse
+    let score = if is_line_complete {
+        -(1 + empty_count)
+    } else {
+        let moves: Vec<usize> = (0..values.len())
+            .filter(|&i| config.is_playable(values, i))
+            .collect();
+        let mut best = if moves.is_empty() { 0 } else { i8::MIN };
+        for i in moves {
+            for mark in [Mark::X, Mark::O] {
+                values[i] = CellValue::Mark(mark);
+                best = best.max(-negamax(config, lines, values, scores));
+                values[i] = Empty;
+            }
+        }
+        best
+    };
+
+    scores.insert(values.clone(), score);
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+
+    use crate::board::CellValue::{Human, CPU};
+    use crate::board::{Cell, CellValue, Mark, Outcome};
+    use crate::wild::{find_best_move, WildError, WildGame};
+
+    fn mark(row: u8, column: u8, mark: Mark) -> Cell {
+        Cell {
+            row,
+            column,
+            layer: 0,
+            value: CellValue::Mark(mark),
+        }
+    }
+
+    #[test]
+    fn test_legal_moves__offers_both_marks() {
+        let game = WildGame::default();
+
+        let actual = game.legal_moves().len();
+        let expected = 18;
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_play__when__player_value() {
+        let mut game = WildGame::default();
+        let cell = Cell {
+            row: 0,
+            column: 0,
+            layer: 0,
+            value: Human,
+        };
+
+        let actual = game.play(cell);
+        let expected = Err(WildError::IllegalMove);
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_outcome__when__line_completed_with_opponents_marks() {
+        let mut game = WildGame::default();
+        game.play(mark(0, 0, Mark::O)).unwrap(); // Human
+        game.play(mark(1, 1, Mark::X)).unwrap(); // CPU
+        game.play(mark(0, 1, Mark::O)).unwrap(); // Human
+        game.play(mark(0, 2, Mark::O)).unwrap(); // CPU
+
+        let actual = game.outcome();
+        let expected = Outcome::Winner(CPU);
+
+        assert_eq!(actual, expected);
+        assert_eq!(game.history().len(), 4);
+    }
+
+    #[test]
+    fn test_owner__when__both_players_play_same_mark() {
+        let mut game = WildGame::default();
+        game.play(mark(0, 0, Mark::X)).unwrap();
+        game.play(mark(1, 1, Mark::X)).unwrap();
+
+        let actual = [game.owner(0, 0), game.owner(1, 1), game.owner(2, 2)];
+        let expected = [Some(Human), Some(CPU), None];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__win_available() {
+        let mut game = WildGame::default();
+        game.play(mark(0, 0, Mark::O)).unwrap();
+        game.play(mark(2, 2, Mark::X)).unwrap();
+        game.play(mark(0, 1, Mark::O)).unwrap();
+
+        let actual = find_best_move(&game);
+        let expected = Some(mark(0, 2, Mark::O));
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_find_best_move__when__first_move() {
+        let game = WildGame::default();
+
+        let actual = find_best_move(&game);
+        let expected = Some(mark(1, 1, Mark::X));
+
+        assert_eq!(actual, expected);
+    }
+}